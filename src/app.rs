@@ -1,6 +1,5 @@
 use crate::{panels::*, ray_tracer::*, Time};
 use eframe::{egui, epi};
-use rand_distr::{Distribution, UnitDisc};
 
 pub struct App {
     ray_tracer: RayTracer,
@@ -10,120 +9,25 @@ pub struct App {
 
 impl App {
     pub fn new(width: u32, height: u32) -> Self {
-        let min_radius: f64 = 3.;
-        let max_radius: f64 = 8.;
-        let placement_radius = 50.;
-        let random_sphere_count = 100;
-
-        let mut objects: Vec<Object> = vec![];
-
-        for i in 0..random_sphere_count {
-            // if it failed 100 times, then there's probably no space left
-            for _ in 0..100 {
-                let radius: f64 = rand::random::<f64>() * (max_radius - min_radius) + min_radius;
-                let [x, y]: [f64; 2] = UnitDisc.sample(&mut rand::thread_rng());
-                let x = x * placement_radius;
-                let y = y * placement_radius;
-                let position = Vec3 { x, y: radius, z: y };
-
-                // reject spheres that are intersecting others
-                if objects.iter().any(|object| {
-                    let other_radius = match object.geometry {
-                        Geometry::Sphere { radius, .. } => radius,
-                        _ => return false,
-                    };
-                    let min_dst = radius + other_radius;
-                    (*object.geometry.position() - position).length() < min_dst
-                }) {
-                    continue;
-                }
-
-                objects.push(Object {
-                    name: i.to_string(),
-                    material: Material {
-                        colour: (rand::random(), rand::random(), rand::random()),
-                        // some sort of distribution would be better here
-                        specular: rand::random::<f64>() * 1000.,
-                        metallic: if rand::random::<f64>() > 0.3 {
-                            rand::random()
-                        } else {
-                            0.
-                        },
-                    },
-                    geometry: Geometry::Sphere {
-                        center: position,
-                        radius,
-                    },
-                });
-
-                break;
-            }
-        }
-
-        objects.push(Object {
-            name: "plane".to_string(),
-            geometry: Geometry::Plane {
-                center: Vec3 {
-                    x: 0.,
-                    y: 0.,
-                    z: 0.,
-                },
-                normal: Vec3 {
-                    x: 0.,
-                    y: 1.,
-                    z: 0.,
-                },
-                size: 100000.,
+        let settings = GeneratorSettings::default();
+        let (camera, target) = settings.view();
+
+        let mut ray_tracer = RayTracer {
+            camera,
+            rotation: Vec3 {
+                x: 0.,
+                y: 0.,
+                z: 0.,
             },
-            material: Material {
-                colour: (0.5, 0.5, 0.5),
-                specular: 10.,
-                metallic: 0.2,
-            },
-        });
+            fov: 70.,
+            width,
+            height,
+            scene: settings.generate(),
+        };
+        ray_tracer.look_at(target);
 
         Self {
-            ray_tracer: RayTracer {
-                camera: Vec3 {
-                    x: 5.,
-                    y: 5.,
-                    z: 5.,
-                },
-                rotation: Vec3 {
-                    x: 0.7,
-                    y: -std::f64::consts::PI / 4.,
-                    z: 0.,
-                },
-                fov: 70.,
-                width,
-                height,
-                scene: Scene {
-                    objects,
-                    lights: vec![
-                        Light::Direction {
-                            intensity: (0.4, 0.4, 0.4),
-                            direction: Vec3 {
-                                x: -1.,
-                                y: -1.5,
-                                z: -0.5,
-                            }
-                            .normalize(),
-                        },
-                        Light::Point {
-                            intensity: (0.4, 0.4, 0.4),
-                            position: Vec3 {
-                                x: 0.,
-                                y: 2.,
-                                z: 0.,
-                            },
-                        },
-                    ],
-                    background_colour: (0.5, 0.8, 1.),
-                    ambient_light: (0.2, 0.2, 0.2),
-                    reflection_limit: 4,
-                    do_objects_spin: false,
-                },
-            },
+            ray_tracer,
             texture: None,
             last_time: Time::now(),
         }
@@ -186,20 +90,13 @@ impl epi::App for App {
             );
 
             if ray_tracer.scene.do_objects_spin {
-                ray_tracer.scene.objects.iter_mut().for_each(|object| {
-                    let position = object.geometry.position_as_mut();
-                    let length = position.length();
-
-                    let theta: f64 = 0.5 * std::f64::consts::PI * delta_time;
-
-                    *position = position.transform_point(Mat44::create_rotation(Axis::Y, theta));
-
-                    // fix rounding errors?
-                    *position = *position * (length / position.length());
-                });
+                ray_tracer.scene.spin(Scene::SPIN_SPEED * delta_time);
             }
         }
 
+        egui::TopBottomPanel::bottom("timeline_panel")
+            .show(ctx, |ui| timeline_panel(ui, &mut self.ray_tracer));
+
         if is_portrait {
             egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
                 egui::SidePanel::left("object_panel")
@@ -207,6 +104,8 @@ impl epi::App for App {
                 egui::SidePanel::right("settings_panel").show_inside(ui, |ui| {
                     settings_panel(ui, fps, &mut self.ray_tracer, &mut has_size_changed)
                 });
+                egui::CentralPanel::default()
+                    .show_inside(ui, |ui| library_panel(ui, &mut self.ray_tracer.scene));
             });
         } else {
             egui::SidePanel::right("settings_panel").show(ctx, |ui| {
@@ -214,6 +113,8 @@ impl epi::App for App {
             });
             egui::SidePanel::right("object_panel")
                 .show(ctx, |ui| object_panel(ui, &mut self.ray_tracer.scene));
+            egui::SidePanel::left("library_panel")
+                .show(ctx, |ui| library_panel(ui, &mut self.ray_tracer.scene));
         }
 
         egui::CentralPanel::default().show(ctx, |ui| {
@@ -23,48 +23,250 @@ fn vec3_widget(ui: &mut egui::Ui, label: impl Into<egui::WidgetText>, vec3: &mut
     });
 }
 
+/// What the ➕ buttons add
+fn new_object(name: &str, geometry: Geometry) -> Object {
+    Object {
+        name: String::from(name),
+        material: MaterialRef::Own(Box::new(Material {
+            model: MaterialModel::Phong,
+            colour: (1., 0., 0.),
+            specular: 500.,
+            metallic: 0.5,
+            roughness: 0.,
+            emission: (0., 0., 0.),
+            emission_strength: 0.,
+            normal_map: None,
+            textures: MaterialTextures::default(),
+            graph: None,
+        })),
+        geometry,
+        transform: Transform::default(),
+        motion: None,
+    }
+}
+
+const GEOMETRY_KINDS: [&str; 10] = [
+    "sphere",
+    "plane",
+    "box",
+    "cylinder",
+    "cone",
+    "disc",
+    "torus",
+    "csg",
+    "sdf",
+    "heightfield",
+];
+
+fn geometry_kind(geometry: &Geometry) -> &'static str {
+    match geometry {
+        Geometry::Sphere { .. } => "sphere",
+        Geometry::Plane { .. } => "plane",
+        Geometry::Box { .. } => "box",
+        Geometry::Cylinder { .. } => "cylinder",
+        Geometry::Cone { .. } => "cone",
+        Geometry::Disc { .. } => "disc",
+        Geometry::Torus { .. } => "torus",
+        Geometry::Csg { .. } => "csg",
+        Geometry::Sdf { .. } => "sdf",
+        Geometry::Heightfield { .. } => "heightfield",
+        Geometry::Mesh { .. } => "mesh",
+    }
+}
+
+/// The geometry a new object of each kind starts with
+fn default_geometry(kind: &str) -> Geometry {
+    let origin = Vec3 {
+        x: 0.,
+        y: 0.,
+        z: 0.,
+    };
+    let up = Vec3 {
+        x: 0.,
+        y: 1.,
+        z: 0.,
+    };
+
+    match kind {
+        "plane" => Geometry::Plane {
+            center: origin,
+            normal: up,
+            size: 5.,
+        },
+        "box" => Geometry::Box {
+            center: origin,
+            half_extents: Vec3 {
+                x: 1.,
+                y: 1.,
+                z: 1.,
+            },
+            rotation: Orientation::default(),
+        },
+        "cylinder" => Geometry::Cylinder {
+            center: origin,
+            axis: up,
+            radius: 1.,
+            height: 2.,
+        },
+        "cone" => Geometry::Cone {
+            center: origin,
+            axis: up,
+            radius: 1.,
+            height: 2.,
+        },
+        "disc" => Geometry::Disc {
+            center: origin,
+            normal: up,
+            radius: 1.,
+        },
+        "torus" => Geometry::Torus {
+            center: origin,
+            axis: up,
+            major_radius: 1.,
+            minor_radius: 0.3,
+        },
+        // a sphere with a hole through it
+        "csg" => Geometry::Csg {
+            center: origin,
+            operation: CsgOperation::Difference,
+            left: Box::new(default_geometry("sphere")),
+            right: Box::new(Geometry::Cylinder {
+                center: origin,
+                axis: up,
+                radius: 0.5,
+                height: 3.,
+            }),
+        },
+        // a twisted rounded box blended into a torus
+        "sdf" => Geometry::Sdf {
+            center: origin,
+            root: SdfNode::SmoothUnion {
+                left: Box::new(SdfNode::Twist {
+                    node: Box::new(SdfNode::Round {
+                        node: Box::new(SdfNode::Primitive(Box::new(Geometry::Box {
+                            center: origin,
+                            half_extents: Vec3 {
+                                x: 0.5,
+                                y: 1.,
+                                z: 0.5,
+                            },
+                            rotation: Orientation::default(),
+                        }))),
+                        radius: 0.1,
+                    }),
+                    amount: 1.,
+                }),
+                right: Box::new(SdfNode::Primitive(Box::new(Geometry::Torus {
+                    center: origin,
+                    axis: up,
+                    major_radius: 1.,
+                    minor_radius: 0.2,
+                }))),
+                smoothness: 0.3,
+            },
+            bounding_radius: 2.,
+        },
+        "heightfield" => Geometry::Heightfield {
+            center: origin,
+            size: Vec3 {
+                x: 10.,
+                y: 2.,
+                z: 10.,
+            },
+            map: Heightmap::new(default_height_source("noise"))
+                .expect("noise heightmaps are always big enough"),
+        },
+        _ => Geometry::Sphere {
+            center: origin,
+            radius: 1.,
+        },
+    }
+}
+
+fn default_height_source(kind: &str) -> HeightSource {
+    match kind {
+        "image" => HeightSource::Image {
+            path: String::new(),
+        },
+        _ => HeightSource::Noise {
+            seed: 0,
+            resolution: 128,
+            frequency: 4.,
+            octaves: 5,
+        },
+    }
+}
+
+const SDF_NODE_KINDS: [&str; 8] = [
+    "primitive",
+    "union",
+    "intersection",
+    "difference",
+    "smooth union",
+    "twist",
+    "repeat",
+    "round",
+];
+
+fn sdf_node_kind(node: &SdfNode) -> &'static str {
+    match node {
+        SdfNode::Primitive(_) => "primitive",
+        SdfNode::Union { .. } => "union",
+        SdfNode::Intersection { .. } => "intersection",
+        SdfNode::Difference { .. } => "difference",
+        SdfNode::SmoothUnion { .. } => "smooth union",
+        SdfNode::Twist { .. } => "twist",
+        SdfNode::Repeat { .. } => "repeat",
+        SdfNode::Round { .. } => "round",
+    }
+}
+
+/// Changes the kind of a node, keeping the old one as its first child
+fn with_sdf_node_kind(kind: &str, node: SdfNode) -> SdfNode {
+    let sphere = SdfNode::Primitive(Box::new(default_geometry("sphere")));
+    let node = Box::new(node);
+
+    match kind {
+        "union" => SdfNode::Union {
+            left: node,
+            right: Box::new(sphere),
+        },
+        "intersection" => SdfNode::Intersection {
+            left: node,
+            right: Box::new(sphere),
+        },
+        "difference" => SdfNode::Difference {
+            left: node,
+            right: Box::new(sphere),
+        },
+        "smooth union" => SdfNode::SmoothUnion {
+            left: node,
+            right: Box::new(sphere),
+            smoothness: 0.3,
+        },
+        "twist" => SdfNode::Twist { node, amount: 1. },
+        "repeat" => SdfNode::Repeat {
+            node,
+            period: Vec3 {
+                x: 3.,
+                y: 0.,
+                z: 3.,
+            },
+        },
+        "round" => SdfNode::Round { node, radius: 0.1 },
+        _ => sphere,
+    }
+}
+
 pub fn object_panel(ui: &mut egui::Ui, scene: &mut Scene) {
-    ui.horizontal(|ui| {
-        if ui.add(egui::Button::new("➕ sphere")).clicked() {
-            scene.objects.push(Object {
-                name: String::from("sphere"),
-                material: Material {
-                    colour: (1., 0., 0.),
-                    specular: 500.,
-                    metallic: 0.5,
-                },
-                geometry: Geometry::Sphere {
-                    center: Vec3 {
-                        x: 0.,
-                        y: 0.,
-                        z: 0.,
-                    },
-                    radius: 1.,
-                },
-            });
-        }
-        if ui.add(egui::Button::new("➕ plane")).clicked() {
-            scene.objects.push(Object {
-                name: String::from("plane"),
-                material: Material {
-                    colour: (1., 0., 0.),
-                    specular: 500.,
-                    metallic: 0.5,
-                },
-                geometry: Geometry::Plane {
-                    center: Vec3 {
-                        x: 0.,
-                        y: 0.,
-                        z: 0.,
-                    },
-                    normal: Vec3 {
-                        x: 0.,
-                        y: 1.,
-                        z: 0.,
-                    },
-                    size: 5.,
-                },
-            });
+    // copied so the objects can be changed while looking at it
+    let library = scene.materials.clone();
+
+    ui.horizontal_wrapped(|ui| {
+        for kind in GEOMETRY_KINDS {
+            if ui.add(egui::Button::new(format!("➕ {}", kind))).clicked() {
+                scene.objects.push(new_object(kind, default_geometry(kind)));
+            }
         }
         if ui.add(egui::Button::new("print")).clicked() {
             println!("{:#?}", scene.objects);
@@ -103,117 +305,2480 @@ pub fn object_panel(ui: &mut egui::Ui, scene: &mut Scene) {
 
         let object = &mut scene.objects[index];
 
-        match &mut object.geometry {
-            Geometry::Sphere { center: _, radius } => {
-                ui.horizontal(|ui| {
-                    ui.label("radius");
-                    ui.add(egui::DragValue::new(radius).fixed_decimals(1).speed(0.1));
-                });
+        geometry_widget(
+            ui,
+            ui.make_persistent_id(("object", index)),
+            &mut object.geometry,
+        );
+        transform_widget(ui, index, &mut object.transform);
+        motion_widget(
+            ui,
+            ("object_motion", index),
+            &object.transform,
+            &mut object.motion,
+        );
+        material_ref_widget(
+            ui,
+            ui.make_persistent_id(("object_material", index)),
+            &library,
+            &mut object.material,
+        );
+
+        ui.separator();
+    }
+
+    ui.heading("Prototypes");
+
+    let instance_counts: Vec<usize> = scene
+        .prototypes
+        .iter()
+        .map(|prototype| {
+            scene
+                .instances
+                .iter()
+                .filter(|instance| instance.prototype == prototype.name)
+                .count()
+        })
+        .collect();
+
+    let mut renamed_prototype = None;
+    let mut removed_prototype = None;
+
+    for (index, prototype) in scene.prototypes.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            // the name is only changed once it's finished, so the instances can follow it
+            let name_id = ui.make_persistent_id(("prototype_name", index));
+            let mut name = ui
+                .memory()
+                .data
+                .get_temp::<String>(name_id)
+                .unwrap_or_else(|| prototype.name.clone());
+
+            let response = ui.text_edit_singleline(&mut name);
+            if response.lost_focus() {
+                if name != prototype.name && !name.is_empty() {
+                    renamed_prototype = Some((prototype.name.clone(), name));
+                }
+                ui.memory().data.remove::<String>(name_id);
+            } else if response.has_focus() {
+                ui.memory().data.insert_temp(name_id, name);
             }
-            Geometry::Plane {
-                center: _,
-                normal,
-                size,
-            } => {
-                ui.horizontal(|ui| {
-                    ui.label("normal");
-                    ui.add(
-                        egui::DragValue::new(&mut normal.x)
-                            .fixed_decimals(1)
-                            .speed(0.1),
-                    );
-                    ui.add(
-                        egui::DragValue::new(&mut normal.y)
-                            .fixed_decimals(1)
-                            .speed(0.1),
-                    );
-                    ui.add(
-                        egui::DragValue::new(&mut normal.z)
-                            .fixed_decimals(1)
-                            .speed(0.1),
-                    );
-
-                    *normal = normal.normalize();
-                });
 
-                ui.horizontal(|ui| {
-                    ui.label("size");
-                    ui.add(egui::DragValue::new(size).fixed_decimals(1).speed(0.1));
+            // its instances go with it
+            if ui
+                .add(egui::Button::new("❌"))
+                .on_hover_text(format!("also removes {} instances", instance_counts[index]))
+                .clicked()
+            {
+                removed_prototype = Some(index);
+            }
+        });
+
+        vec3_widget(ui, "pos", prototype.geometry.position_as_mut());
+        geometry_widget(
+            ui,
+            ui.make_persistent_id(("prototype", index)),
+            &mut prototype.geometry,
+        );
+        material_ref_widget(
+            ui,
+            ui.make_persistent_id(("prototype_material", index)),
+            &library,
+            &mut prototype.material,
+        );
+
+        ui.separator();
+    }
+
+    if let Some((old_name, new_name)) = renamed_prototype {
+        let new_name = scene.unique_prototype_name(&new_name);
+        scene.rename_prototype(&old_name, &new_name);
+    }
+
+    if let Some(index) = removed_prototype {
+        scene.remove_prototype(index);
+    }
+
+    ui.heading("Instances");
+
+    let prototype_names: Vec<String> = scene
+        .prototypes
+        .iter()
+        .map(|prototype| prototype.name.clone())
+        .collect();
+
+    if let Some(first_prototype) = scene.prototypes.first() {
+        if ui.add(egui::Button::new("➕ instance")).clicked() {
+            scene.instances.push(Instance {
+                name: String::from("instance"),
+                prototype: first_prototype.name.clone(),
+                transform: Transform::default(),
+                material: None,
+                motion: None,
+            });
+        }
+    }
+
+    let mut removed_instance = None;
+
+    for (index, instance) in scene.instances.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(&instance.name);
+
+            egui::ComboBox::from_id_source(("instance_prototype", index))
+                .selected_text(&instance.prototype)
+                .show_ui(ui, |ui| {
+                    for name in &prototype_names {
+                        ui.selectable_value(&mut instance.prototype, name.clone(), name);
+                    }
                 });
+
+            if ui.add(egui::Button::new("❌")).clicked() {
+                removed_instance = Some(index);
+            }
+        });
+
+        transform_widget(ui, ("instance_transform", index), &mut instance.transform);
+        motion_widget(
+            ui,
+            ("instance_motion", index),
+            &instance.transform,
+            &mut instance.motion,
+        );
+
+        let mut has_override = instance.material.is_some();
+        ui.checkbox(&mut has_override, "override material");
+
+        match (has_override, &mut instance.material) {
+            (true, Some(material)) => material_ref_widget(
+                ui,
+                ui.make_persistent_id(("instance_material", index)),
+                &library,
+                material,
+            ),
+            (true, None) => {
+                // start from the prototype's material so the override isn't a surprise
+                instance.material = Some(
+                    prototype_names
+                        .iter()
+                        .position(|name| *name == instance.prototype)
+                        .map(|i| scene.prototypes[i].material.clone())
+                        .unwrap_or_else(|| MaterialRef::Own(Box::new(default_material()))),
+                );
             }
+            (false, _) => instance.material = None,
         }
 
+        ui.separator();
+    }
+
+    if let Some(index) = removed_instance {
+        scene.instances.remove(index);
+    }
+
+    ui.heading("Volumes");
+
+    if ui.add(egui::Button::new("➕ volume")).clicked() {
+        scene.volumes.push(Volume {
+            name: String::from("smoke"),
+            center: Vec3 {
+                x: 0.,
+                y: 1.,
+                z: 0.,
+            },
+            half_extents: Vec3 {
+                x: 1.,
+                y: 1.,
+                z: 1.,
+            },
+            colour: (1., 1., 1.),
+            density: 1.,
+            albedo: 0.8,
+            source: DensitySource::Noise {
+                frequency: 1.,
+                seed: 0,
+                octaves: 4,
+            },
+        });
+    }
+
+    let mut removed_volume = None;
+
+    for (index, volume) in scene.volumes.iter_mut().enumerate() {
         ui.horizontal(|ui| {
-            ui.label("col");
+            ui.text_edit_singleline(&mut volume.name);
 
-            let mut colour = [
-                object.material.colour.0 as f32,
-                object.material.colour.1 as f32,
-                object.material.colour.2 as f32,
-            ];
+            if ui.add(egui::Button::new("❌")).clicked() {
+                removed_volume = Some(index);
+            }
+        });
 
-            ui.color_edit_button_rgb(&mut colour);
+        vec3_widget(ui, "pos", &mut volume.center);
+        vec3_widget(ui, "size", &mut volume.half_extents);
+        volume_widget(
+            ui,
+            &mut volume.colour,
+            &mut volume.density,
+            &mut volume.albedo,
+        );
 
-            object.material.colour = (colour[0] as f64, colour[1] as f64, colour[2] as f64);
+        ui.horizontal(|ui| {
+            let mut kind = match volume.source {
+                DensitySource::Uniform => "uniform",
+                DensitySource::Noise { .. } => "noise",
+                DensitySource::Voxels { .. } => "voxels",
+            };
+            let old_kind = kind;
 
-            ui.label("spec");
-            ui.add(
-                egui::DragValue::new(&mut object.material.specular).clamp_range::<f64>(0.0..=1000.),
-            );
+            egui::ComboBox::from_id_source(("volume_source", index))
+                .selected_text(kind)
+                .show_ui(ui, |ui| {
+                    // voxels can only come from a scene file
+                    for option in ["uniform", "noise"] {
+                        ui.selectable_value(&mut kind, option, option);
+                    }
+                });
 
-            ui.label("met");
-            ui.add(
-                egui::DragValue::new(&mut object.material.metallic)
-                    .clamp_range::<f64>(0.0..=1.)
-                    .speed(0.1),
-            );
+            if kind != old_kind {
+                volume.source = match kind {
+                    "noise" => DensitySource::Noise {
+                        frequency: 1.,
+                        seed: 0,
+                        octaves: 4,
+                    },
+                    _ => DensitySource::Uniform,
+                };
+            }
+
+            match &mut volume.source {
+                DensitySource::Uniform => (),
+                DensitySource::Noise {
+                    frequency,
+                    seed,
+                    octaves,
+                } => {
+                    ui.label("freq");
+                    ui.add(egui::DragValue::new(frequency).speed(0.1));
+                    ui.label("seed");
+                    ui.add(egui::DragValue::new(seed));
+                    ui.label("octaves");
+                    ui.add(egui::DragValue::new(octaves).clamp_range(1..=10));
+                }
+                DensitySource::Voxels { resolution, .. } => {
+                    ui.label(format!(
+                        "{}x{}x{}",
+                        resolution.0, resolution.1, resolution.2
+                    ));
+                }
+            }
         });
 
         ui.separator();
     }
+
+    if let Some(index) = removed_volume {
+        scene.volumes.remove(index);
+    }
 }
 
-pub fn settings_panel(
+/// The settings shared by fog and volumes
+fn volume_widget(
     ui: &mut egui::Ui,
-    fps: f32,
-    ray_tracer: &mut RayTracer,
-    has_size_changed: &mut bool,
+    colour: &mut (f64, f64, f64),
+    density: &mut f64,
+    albedo: &mut f64,
 ) {
-    ui.heading("Settings");
+    ui.horizontal(|ui| {
+        ui.label("col");
 
-    // this isn't perfect as I'm not using a fixed width font
-    // but it still looks better than nothing
-    ui.label(format!("fps: {: >4}", fps.round()));
+        let mut rgb = [colour.0 as f32, colour.1 as f32, colour.2 as f32];
+        ui.color_edit_button_rgb(&mut rgb);
+        *colour = (rgb[0] as f64, rgb[1] as f64, rgb[2] as f64);
 
-    ui.separator();
+        ui.label("density");
+        ui.add(
+            egui::DragValue::new(density)
+                .clamp_range::<f64>(0.0..=10.)
+                .speed(0.01),
+        );
 
-    ui.horizontal(|ui| {
-        let mut new_width = ray_tracer.width;
-        let mut new_height = ray_tracer.height;
+        ui.label("albedo");
+        ui.add(
+            egui::DragValue::new(albedo)
+                .clamp_range::<f64>(0.0..=1.)
+                .speed(0.05),
+        );
+    });
+}
 
-        ui.label("width");
-        ui.add(egui::DragValue::new(&mut new_width).speed(20));
-        ui.label("height");
-        ui.add(egui::DragValue::new(&mut new_height).speed(20));
+/// id keeps the csg tree's widgets apart between objects
+fn geometry_widget(ui: &mut egui::Ui, id: egui::Id, geometry: &mut Geometry) {
+    match geometry {
+        Geometry::Sphere { center: _, radius } => drag_widget(ui, "radius", radius),
+        Geometry::Plane {
+            center: _,
+            normal,
+            size,
+        } => {
+            direction_widget(ui, "normal", normal);
+            drag_widget(ui, "size", size);
+        }
+        Geometry::Box {
+            center: _,
+            half_extents,
+            rotation,
+        } => {
+            vec3_widget(ui, "size", half_extents);
 
-        if new_width != ray_tracer.width || new_height != ray_tracer.height {
-            *has_size_changed = true;
-            ray_tracer.width = new_width;
-            ray_tracer.height = new_height;
+            let mut angles = rotation.angles();
+            vec3_widget(ui, "rot", &mut angles);
+            if angles != rotation.angles() {
+                *rotation = Orientation::new(angles);
+            }
+        }
+        Geometry::Cylinder {
+            center: _,
+            axis,
+            radius,
+            height,
+        }
+        | Geometry::Cone {
+            center: _,
+            axis,
+            radius,
+            height,
+        } => {
+            direction_widget(ui, "axis", axis);
+            drag_widget(ui, "radius", radius);
+            drag_widget(ui, "height", height);
+        }
+        Geometry::Disc {
+            center: _,
+            normal,
+            radius,
+        } => {
+            direction_widget(ui, "normal", normal);
+            drag_widget(ui, "radius", radius);
+        }
+        Geometry::Torus {
+            center: _,
+            axis,
+            major_radius,
+            minor_radius,
+        } => {
+            direction_widget(ui, "axis", axis);
+            drag_widget(ui, "major radius", major_radius);
+            drag_widget(ui, "minor radius", minor_radius);
+        }
+        Geometry::Csg {
+            center: _,
+            operation,
+            left,
+            right,
+        } => {
+            ui.horizontal(|ui| {
+                ui.label("operation");
+                egui::ComboBox::from_id_source(id.with("operation"))
+                    .selected_text(format!("{:?}", operation))
+                    .show_ui(ui, |ui| {
+                        for option in [
+                            CsgOperation::Union,
+                            CsgOperation::Intersection,
+                            CsgOperation::Difference,
+                        ] {
+                            ui.selectable_value(operation, option, format!("{:?}", option));
+                        }
+                    });
+            });
+
+            csg_child_widget(ui, id.with("left"), "left", left);
+            csg_child_widget(ui, id.with("right"), "right", right);
+        }
+        Geometry::Sdf {
+            center: _,
+            root,
+            bounding_radius,
+        } => {
+            drag_widget(ui, "bounds", bounding_radius);
+            sdf_node_widget(ui, id.with("root"), "root", root);
+        }
+        Geometry::Heightfield {
+            center: _,
+            size,
+            map,
+        } => {
+            vec3_widget(ui, "size", size);
+            heightmap_widget(ui, id.with("heightmap"), map);
+        }
+        // meshes are only made by importing them
+        Geometry::Mesh { center: _, mesh } => {
+            ui.label(format!(
+                "{} triangles, {} vertices",
+                mesh.data().triangles.len(),
+                mesh.data().positions.len()
+            ));
+        }
+    }
+}
+
+/// Edits the source of a heightmap, remaking it when anything changes
+fn heightmap_widget(ui: &mut egui::Ui, id: egui::Id, map: &mut Heightmap) {
+    let mut source = map.source.clone();
+    let mut should_load = false;
+
+    let mut kind = match source {
+        HeightSource::Image { .. } => "image",
+        HeightSource::Noise { .. } => "noise",
+    };
+    let old_kind = kind;
+
+    egui::ComboBox::from_id_source(id.with("kind"))
+        .selected_text(kind)
+        .show_ui(ui, |ui| {
+            for option in ["noise", "image"] {
+                ui.selectable_value(&mut kind, option, option);
+            }
+        });
+
+    if kind != old_kind {
+        source = default_height_source(kind);
+    }
+
+    match &mut source {
+        HeightSource::Image { path } => {
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(path);
+                should_load = ui.button("load").clicked();
+            });
+        }
+        HeightSource::Noise {
+            seed,
+            resolution,
+            frequency,
+            octaves,
+        } => {
+            ui.horizontal(|ui| {
+                ui.label("seed");
+                ui.add(egui::DragValue::new(seed));
+            });
+            ui.horizontal(|ui| {
+                ui.label("resolution");
+                ui.add(egui::DragValue::new(resolution).clamp_range(2..=1024));
+            });
+            drag_widget(ui, "frequency", frequency);
+            ui.horizontal(|ui| {
+                ui.label("octaves");
+                ui.add(egui::DragValue::new(octaves).clamp_range(1..=10));
+            });
+
+            should_load = source != map.source;
+        }
+    }
+
+    if should_load {
+        match Heightmap::new(source) {
+            Ok(new_map) => {
+                *map = new_map;
+                ui.memory().data.remove::<String>(id);
+            }
+            Err(error) => ui.memory().data.insert_temp(id, error.to_string()),
         }
+    }
+
+    let error = ui.memory().data.get_temp::<String>(id);
+    if let Some(error) = error {
+        ui.colored_label(egui::Color32::RED, error);
+    }
+}
+
+/// One node of an sdf tree, and all the nodes below it
+fn sdf_node_widget(ui: &mut egui::Ui, id: egui::Id, label: &str, node: &mut SdfNode) {
+    egui::CollapsingHeader::new(format!("{} ({})", label, sdf_node_kind(node)))
+        .id_source(id)
+        .show(ui, |ui| {
+            let mut kind = sdf_node_kind(node);
+            egui::ComboBox::from_id_source(id.with("kind"))
+                .selected_text(kind)
+                .show_ui(ui, |ui| {
+                    for option in SDF_NODE_KINDS {
+                        ui.selectable_value(&mut kind, option, option);
+                    }
+                });
+
+            if kind != sdf_node_kind(node) {
+                *node = with_sdf_node_kind(kind, node.clone());
+            }
+
+            match node {
+                SdfNode::Primitive(geometry) => {
+                    csg_child_widget(ui, id.with("geometry"), "geometry", geometry)
+                }
+                SdfNode::Union { left, right }
+                | SdfNode::Intersection { left, right }
+                | SdfNode::Difference { left, right } => {
+                    sdf_node_widget(ui, id.with("left"), "left", left);
+                    sdf_node_widget(ui, id.with("right"), "right", right);
+                }
+                SdfNode::SmoothUnion {
+                    left,
+                    right,
+                    smoothness,
+                } => {
+                    drag_widget(ui, "smoothness", smoothness);
+                    sdf_node_widget(ui, id.with("left"), "left", left);
+                    sdf_node_widget(ui, id.with("right"), "right", right);
+                }
+                SdfNode::Twist { node, amount } => {
+                    drag_widget(ui, "amount", amount);
+                    sdf_node_widget(ui, id.with("node"), "node", node);
+                }
+                SdfNode::Repeat { node, period } => {
+                    vec3_widget(ui, "period", period);
+                    sdf_node_widget(ui, id.with("node"), "node", node);
+                }
+                SdfNode::Round { node, radius } => {
+                    drag_widget(ui, "radius", radius);
+                    sdf_node_widget(ui, id.with("node"), "node", node);
+                }
+            }
+        });
+}
+
+/// One branch of the csg tree, which can be any geometry, including more csg
+fn csg_child_widget(ui: &mut egui::Ui, id: egui::Id, label: &str, child: &mut Geometry) {
+    egui::CollapsingHeader::new(format!("{} ({})", label, geometry_kind(child)))
+        .id_source(id)
+        .show(ui, |ui| {
+            let mut kind = geometry_kind(child);
+            egui::ComboBox::from_id_source(id.with("kind"))
+                .selected_text(kind)
+                .show_ui(ui, |ui| {
+                    for option in GEOMETRY_KINDS {
+                        ui.selectable_value(&mut kind, option, option);
+                    }
+                });
+
+            if kind != geometry_kind(child) {
+                let position = *child.position();
+                *child = default_geometry(kind);
+                *child.position_as_mut() = position;
+            }
+
+            vec3_widget(ui, "pos", child.position_as_mut());
+            geometry_widget(ui, id, child);
+        });
+}
+
+fn drag_widget(ui: &mut egui::Ui, label: impl Into<egui::WidgetText>, value: &mut f64) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(egui::DragValue::new(value).fixed_decimals(1).speed(0.1));
     });
+}
 
-    ui.separator();
+/// A vec3_widget that keeps the vector normalized
+fn direction_widget(ui: &mut egui::Ui, label: impl Into<egui::WidgetText>, direction: &mut Vec3) {
+    vec3_widget(ui, label, direction);
+    *direction = direction.normalize();
+}
 
-    vec3_widget(ui, "pos", &mut ray_tracer.camera);
-    vec3_widget(ui, "rot", &mut ray_tracer.rotation);
+fn transform_widget(ui: &mut egui::Ui, id_source: impl std::hash::Hash, transform: &mut Transform) {
+    egui::CollapsingHeader::new("transform")
+        .id_source(id_source)
+        .show(ui, |ui| {
+            vec3_widget(ui, "pos", &mut transform.position);
+            vec3_widget(ui, "rot", &mut transform.rotation);
+            vec3_widget(ui, "scale", &mut transform.scale);
+        });
+}
 
-    ui.separator();
+/// How something moves while the shutter is open, if it does
+fn motion_widget(
+    ui: &mut egui::Ui,
+    id_source: impl std::hash::Hash,
+    transform: &Transform,
+    motion: &mut Option<Motion>,
+) {
+    egui::CollapsingHeader::new("motion")
+        .id_source(id_source)
+        .show(ui, |ui| {
+            let mut kind = motion.as_ref().map_or("none", |motion| motion.kind());
+
+            egui::ComboBox::from_id_source(ui.id().with("kind"))
+                .selected_text(kind)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut kind, "none", "none");
+                    for option in Motion::KINDS {
+                        ui.selectable_value(&mut kind, option, option);
+                    }
+                });
+
+            if kind != motion.as_ref().map_or("none", |motion| motion.kind()) {
+                *motion = match kind {
+                    "none" => None,
+                    kind => Some(Motion::new(kind, transform)),
+                };
+            }
+
+            match motion {
+                Some(Motion::Velocity(velocity)) => vec3_widget(ui, "velocity", velocity),
+                Some(Motion::Transforms { open, close }) => {
+                    ui.label("when the shutter opens");
+                    vec3_widget(ui, "pos", &mut open.position);
+                    vec3_widget(ui, "rot", &mut open.rotation);
+                    vec3_widget(ui, "scale", &mut open.scale);
+                    ui.label("when it closes");
+                    vec3_widget(ui, "pos", &mut close.position);
+                    vec3_widget(ui, "rot", &mut close.rotation);
+                    vec3_widget(ui, "scale", &mut close.scale);
+                }
+                None => (),
+            }
+        });
+}
+
+fn default_material() -> Material {
+    Material {
+        model: MaterialModel::Phong,
+        colour: (1., 0., 0.),
+        specular: 500.,
+        metallic: 0.5,
+        roughness: 0.,
+        emission: (0., 0., 0.),
+        emission_strength: 0.,
+        normal_map: None,
+        textures: MaterialTextures::default(),
+        graph: None,
+    }
+}
 
+/// Picks between a material of its own and one from the library
+fn material_ref_widget(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    library: &[LibraryMaterial],
+    material: &mut MaterialRef,
+) {
     ui.horizontal(|ui| {
-        ui.label("bounces");
+        ui.label("material");
+
+        let selected = match material {
+            MaterialRef::Library(name) => name.clone(),
+            MaterialRef::Own(_) => String::from("own"),
+        };
+
+        egui::ComboBox::from_id_source(id.with("library"))
+            .selected_text(&selected)
+            .show_ui(ui, |ui| {
+                // keep the look of the shared material when making it its own
+                if ui
+                    .selectable_label(matches!(material, MaterialRef::Own(_)), "own")
+                    .clicked()
+                {
+                    if let MaterialRef::Library(name) = material {
+                        *material = MaterialRef::Own(Box::new(
+                            library
+                                .iter()
+                                .find(|shared| shared.name == *name)
+                                .map_or_else(default_material, |shared| shared.material.clone()),
+                        ));
+                    }
+                }
+
+                for shared in library {
+                    if ui
+                        .selectable_label(selected == shared.name, &shared.name)
+                        .clicked()
+                    {
+                        *material = MaterialRef::Library(shared.name.clone());
+                    }
+                }
+            });
+    });
+
+    if let MaterialRef::Own(material) = material {
+        material_widget(ui, id, material);
+    }
+}
+
+fn material_widget(ui: &mut egui::Ui, id: egui::Id, material: &mut Material) {
+    ui.horizontal(|ui| {
+        ui.label("col");
+
+        let mut colour = [
+            material.colour.0 as f32,
+            material.colour.1 as f32,
+            material.colour.2 as f32,
+        ];
+
+        ui.color_edit_button_rgb(&mut colour);
+
+        material.colour = (colour[0] as f64, colour[1] as f64, colour[2] as f64);
+
+        ui.selectable_value(&mut material.model, MaterialModel::Phong, "phong");
+        ui.selectable_value(&mut material.model, MaterialModel::Pbr, "pbr");
+
+        // pbr materials get their highlights from roughness instead
+        if material.model == MaterialModel::Phong {
+            ui.label("spec");
+            ui.add(egui::DragValue::new(&mut material.specular).clamp_range::<f64>(0.0..=1000.));
+        }
+
+        ui.label("met");
         ui.add(
-            egui::DragValue::new(&mut ray_tracer.scene.reflection_limit).clamp_range::<u32>(0..=10),
+            egui::DragValue::new(&mut material.metallic)
+                .clamp_range::<f64>(0.0..=1.)
+                .speed(0.1),
+        );
+
+        ui.label("rough");
+        ui.add(
+            egui::DragValue::new(&mut material.roughness)
+                .clamp_range::<f64>(0.0..=1.)
+                .speed(0.05),
+        );
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("glow");
+
+        let mut emission = [
+            material.emission.0 as f32,
+            material.emission.1 as f32,
+            material.emission.2 as f32,
+        ];
+
+        ui.color_edit_button_rgb(&mut emission);
+
+        material.emission = (emission[0] as f64, emission[1] as f64, emission[2] as f64);
+
+        ui.label("strength");
+        ui.add(
+            egui::DragValue::new(&mut material.emission_strength)
+                .clamp_range::<f64>(0.0..=100.)
+                .speed(0.1),
         );
     });
+
+    ui.horizontal(|ui| {
+        ui.label("normals");
+
+        if ui
+            .selectable_label(material.normal_map.is_none(), "none")
+            .clicked()
+        {
+            material.normal_map = None;
+        }
+
+        let is_noise = matches!(material.normal_map, Some(NormalMap::Noise { .. }));
+        if ui.selectable_label(is_noise, "bumps").clicked() && !is_noise {
+            material.normal_map = Some(NormalMap::Noise {
+                frequency: 4.,
+                strength: 0.5,
+                seed: 0,
+                octaves: 4,
+            });
+        }
+
+        match &mut material.normal_map {
+            Some(NormalMap::Image {
+                texture: _,
+                strength,
+            }) => {
+                ui.label("strength");
+                ui.add(egui::DragValue::new(strength).speed(0.05));
+            }
+            Some(NormalMap::Noise {
+                frequency,
+                strength,
+                seed,
+                octaves,
+            }) => {
+                ui.label("freq");
+                ui.add(egui::DragValue::new(frequency).speed(0.1));
+                ui.label("strength");
+                ui.add(egui::DragValue::new(strength).speed(0.05));
+                ui.label("seed");
+                ui.add(egui::DragValue::new(seed));
+                ui.label("octaves");
+                ui.add(egui::DragValue::new(octaves).clamp_range(1..=10));
+            }
+            None => (),
+        }
+    });
+
+    ui.horizontal(|ui| {
+        // the path being typed is kept until it's loaded
+        let path_id = id.with("normal_map_path");
+        let mut path = ui
+            .memory()
+            .data
+            .get_temp::<String>(path_id)
+            .unwrap_or_else(|| match &material.normal_map {
+                Some(NormalMap::Image {
+                    texture,
+                    strength: _,
+                }) => texture.path.clone(),
+                _ => String::new(),
+            });
+
+        ui.text_edit_singleline(&mut path);
+
+        if ui.button("load normal map").clicked() {
+            match ImageTexture::load(path.clone()) {
+                Ok(texture) => {
+                    material.normal_map = Some(NormalMap::Image {
+                        texture,
+                        strength: 1.,
+                    });
+                    ui.memory().data.remove::<String>(id);
+                }
+                Err(error) => ui.memory().data.insert_temp(id, error.to_string()),
+            }
+        }
+
+        ui.memory().data.insert_temp(path_id, path);
+    });
+
+    material_textures_widget(ui, id.with("textures"), &mut material.textures);
+
+    ui.horizontal(|ui| {
+        let open_id = id.with("graph_open");
+        let mut open = ui.memory().data.get_temp::<bool>(open_id).unwrap_or(false);
+
+        if ui.button("edit graph").clicked() {
+            open = true;
+            if material.graph.is_none() {
+                material.graph = Some(MaterialGraph::default());
+            }
+        }
+
+        if material.graph.is_some() && ui.button("remove graph").clicked() {
+            material.graph = None;
+        }
+
+        match &mut material.graph {
+            Some(graph) if open => {
+                egui::Window::new("material graph")
+                    .id(id.with("graph_window"))
+                    .open(&mut open)
+                    .default_size((760., 460.))
+                    .show(ui.ctx(), |ui| {
+                        material_graph_editor(ui, id.with("graph"), graph)
+                    });
+            }
+            _ => open = false,
+        }
+
+        ui.memory().data.insert_temp(open_id, open);
+    });
+
+    let error = ui.memory().data.get_temp::<String>(id);
+    if let Some(error) = error {
+        ui.colored_label(egui::Color32::RED, error);
+    }
+}
+
+const PATTERN_KINDS: [&str; 7] = [
+    "perlin",
+    "simplex",
+    "worley",
+    "fbm",
+    "turbulence",
+    "marble",
+    "wood",
+];
+
+fn pattern_kind(pattern: &Pattern) -> &'static str {
+    match pattern {
+        Pattern::Perlin => "perlin",
+        Pattern::Simplex => "simplex",
+        Pattern::Worley => "worley",
+        Pattern::Fbm { .. } => "fbm",
+        Pattern::Turbulence { .. } => "turbulence",
+        Pattern::Marble { .. } => "marble",
+        Pattern::Wood { .. } => "wood",
+    }
+}
+
+fn default_pattern(kind: &str) -> Pattern {
+    match kind {
+        "perlin" => Pattern::Perlin,
+        "simplex" => Pattern::Simplex,
+        "worley" => Pattern::Worley,
+        "fbm" => Pattern::Fbm { octaves: 5 },
+        "turbulence" => Pattern::Turbulence { octaves: 5 },
+        "marble" => Pattern::Marble {
+            octaves: 5,
+            turbulence: 4.,
+        },
+        "wood" => Pattern::Wood { turbulence: 0.6 },
+        _ => panic!("Unknown pattern kind"),
+    }
+}
+
+fn default_solid_texture() -> SolidTexture {
+    SolidTexture {
+        pattern: Pattern::Perlin,
+        frequency: 1.,
+        seed: 0,
+    }
+}
+
+/// The textures over a material, with presets to start from
+fn material_textures_widget(ui: &mut egui::Ui, id: egui::Id, textures: &mut MaterialTextures) {
+    egui::CollapsingHeader::new("textures")
+        .id_source(id)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                for preset in MaterialTextures::PRESETS {
+                    if ui.button(preset).clicked() {
+                        *textures = MaterialTextures::preset(preset);
+                    }
+                }
+
+                if ui.button("clear").clicked() {
+                    *textures = MaterialTextures::default();
+                }
+            });
+
+            let mut enabled = textures.colour.is_some();
+            ui.checkbox(&mut enabled, "colour");
+            if enabled != textures.colour.is_some() {
+                textures.colour = enabled.then(|| ColourTexture {
+                    texture: default_solid_texture(),
+                    from: (0., 0., 0.),
+                    to: (1., 1., 1.),
+                });
+            }
+
+            if let Some(colour) = &mut textures.colour {
+                solid_texture_widget(ui, id.with("colour"), &mut colour.texture);
+
+                ui.horizontal(|ui| {
+                    for (label, value) in [("from", &mut colour.from), ("to", &mut colour.to)] {
+                        ui.label(label);
+
+                        let mut rgb = [value.0 as f32, value.1 as f32, value.2 as f32];
+                        ui.color_edit_button_rgb(&mut rgb);
+                        *value = (rgb[0] as f64, rgb[1] as f64, rgb[2] as f64);
+                    }
+                });
+            }
+
+            value_texture_widget(ui, id.with("specular"), "spec", &mut textures.specular);
+            value_texture_widget(ui, id.with("metallic"), "met", &mut textures.metallic);
+            value_texture_widget(ui, id.with("roughness"), "rough", &mut textures.roughness);
+        });
+}
+
+fn value_texture_widget(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    label: &str,
+    texture: &mut Option<ValueTexture>,
+) {
+    let mut enabled = texture.is_some();
+    ui.checkbox(&mut enabled, label);
+    if enabled != texture.is_some() {
+        *texture = enabled.then(|| ValueTexture {
+            texture: default_solid_texture(),
+            from: 0.,
+            to: 1.,
+        });
+    }
+
+    if let Some(texture) = texture {
+        solid_texture_widget(ui, id, &mut texture.texture);
+
+        ui.horizontal(|ui| {
+            ui.label("from");
+            ui.add(egui::DragValue::new(&mut texture.from).speed(0.05));
+            ui.label("to");
+            ui.add(egui::DragValue::new(&mut texture.to).speed(0.05));
+        });
+    }
+}
+
+fn solid_texture_widget(ui: &mut egui::Ui, id: egui::Id, texture: &mut SolidTexture) {
+    ui.horizontal(|ui| {
+        let mut kind = pattern_kind(&texture.pattern);
+        egui::ComboBox::from_id_source(id.with("pattern"))
+            .selected_text(kind)
+            .show_ui(ui, |ui| {
+                for option in PATTERN_KINDS {
+                    ui.selectable_value(&mut kind, option, option);
+                }
+            });
+
+        if kind != pattern_kind(&texture.pattern) {
+            texture.pattern = default_pattern(kind);
+        }
+
+        ui.label("freq");
+        ui.add(egui::DragValue::new(&mut texture.frequency).speed(0.1));
+        ui.label("seed");
+        ui.add(egui::DragValue::new(&mut texture.seed));
+    });
+
+    ui.horizontal(|ui| match &mut texture.pattern {
+        Pattern::Perlin | Pattern::Simplex | Pattern::Worley => (),
+        Pattern::Fbm { octaves } | Pattern::Turbulence { octaves } => {
+            ui.label("octaves");
+            ui.add(egui::DragValue::new(octaves).clamp_range(1..=10));
+        }
+        Pattern::Marble {
+            octaves,
+            turbulence,
+        } => {
+            ui.label("octaves");
+            ui.add(egui::DragValue::new(octaves).clamp_range(1..=10));
+            ui.label("turbulence");
+            ui.add(egui::DragValue::new(turbulence).speed(0.1));
+        }
+        Pattern::Wood { turbulence } => {
+            ui.label("turbulence");
+            ui.add(egui::DragValue::new(turbulence).speed(0.05));
+        }
+    });
+}
+
+const NODE_KINDS: [&str; 6] = ["uv", "position", "noise", "math", "mix", "fresnel"];
+
+fn default_node(kind: &str) -> NodeKind {
+    match kind {
+        "uv" => NodeKind::Uv,
+        "position" => NodeKind::Position,
+        "noise" => NodeKind::Noise {
+            texture: default_solid_texture(),
+        },
+        "math" => NodeKind::Math {
+            operation: MathOp::Multiply,
+            a: Input::Value(1.),
+            b: Input::Value(1.),
+        },
+        "mix" => NodeKind::Mix {
+            a: Input::Colour((0., 0., 0.)),
+            b: Input::Colour((1., 1., 1.)),
+            factor: Input::Value(0.5),
+        },
+        "fresnel" => NodeKind::Fresnel { ior: 1.5 },
+        _ => panic!("Unknown node kind"),
+    }
+}
+
+/// Something a wire can end at
+#[derive(Clone, Copy, PartialEq)]
+enum GraphSocket {
+    /// the nth input of a node
+    Node(usize, usize),
+    Output(BsdfInput),
+}
+
+/// A circle that a wire can be connected to
+fn socket_widget(ui: &mut egui::Ui, is_connected: bool) -> (egui::Pos2, egui::Response) {
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(12., 12.), egui::Sense::click());
+
+    let colour = if response.hovered() {
+        egui::Color32::WHITE
+    } else if is_connected {
+        egui::Color32::LIGHT_BLUE
+    } else {
+        egui::Color32::GRAY
+    };
+    ui.painter().circle_filled(rect.center(), 5., colour);
+
+    (rect.center(), response)
+}
+
+/// An input that's either a value, a colour or a wire from another node
+fn graph_input_widget(
+    ui: &mut egui::Ui,
+    graph: &MaterialGraph,
+    label: &str,
+    input: &mut Input,
+) -> (egui::Pos2, egui::Response) {
+    ui.horizontal(|ui| {
+        let socket = socket_widget(ui, matches!(input, Input::Node(_)));
+        ui.label(label);
+
+        match input {
+            Input::Value(value) => {
+                ui.add(egui::DragValue::new(value).speed(0.05));
+                if ui.small_button("c").on_hover_text("use a colour").clicked() {
+                    *input = Input::Colour((*value, *value, *value));
+                }
+            }
+            Input::Colour(colour) => {
+                let mut rgb = [colour.0 as f32, colour.1 as f32, colour.2 as f32];
+                ui.color_edit_button_rgb(&mut rgb);
+                *colour = (rgb[0] as f64, rgb[1] as f64, rgb[2] as f64);
+
+                if ui.small_button("v").on_hover_text("use a value").clicked() {
+                    *input = Input::Value((colour.0 + colour.1 + colour.2) / 3.);
+                }
+            }
+            Input::Node(index) => {
+                let name = graph.nodes.get(*index).map_or("?", |node| node.kind.name());
+                ui.label(format!("from {} {}", name, index));
+            }
+        }
+
+        socket
+    })
+    .inner
+}
+
+/// A canvas of nodes, connected by clicking an output and then an input.
+/// Clicking a connected input disconnects it.
+fn material_graph_editor(ui: &mut egui::Ui, id: egui::Id, graph: &mut MaterialGraph) {
+    const NODE_WIDTH: f32 = 190.;
+
+    let pending_id = id.with("pending");
+    let mut pending = ui.memory().data.get_temp::<usize>(pending_id);
+
+    ui.horizontal(|ui| {
+        ui.label("add");
+
+        for kind in NODE_KINDS {
+            if ui.button(kind).clicked() {
+                let offset = graph.nodes.len() as f32 * 20.;
+                graph.nodes.push(GraphNode {
+                    kind: default_node(kind),
+                    position: (10. + offset % 200., 10. + offset % 200.),
+                });
+            }
+        }
+    });
+
+    ui.horizontal(|ui| {
+        let path_id = id.with("texture_path");
+        let mut path = ui
+            .memory()
+            .data
+            .get_temp::<String>(path_id)
+            .unwrap_or_default();
+
+        ui.text_edit_singleline(&mut path);
+
+        if ui.button("add texture").clicked() {
+            match ImageTexture::load(path.clone()) {
+                Ok(texture) => {
+                    graph.nodes.push(GraphNode {
+                        kind: NodeKind::Texture { texture, scale: 1. },
+                        position: (10., 10.),
+                    });
+                    ui.memory().data.remove::<String>(id);
+                }
+                Err(error) => ui.memory().data.insert_temp(id, error.to_string()),
+            }
+        }
+
+        ui.memory().data.insert_temp(path_id, path);
+    });
+
+    let error = ui.memory().data.get_temp::<String>(id);
+    if let Some(error) = error {
+        ui.colored_label(egui::Color32::RED, error);
+    }
+
+    let (canvas, painter) = ui.allocate_painter(ui.available_size(), egui::Sense::click());
+    let origin = canvas.rect.min;
+
+    // wires go under the nodes, but can only be made once the sockets are known
+    let wires = painter.add(egui::Shape::Noop);
+
+    let mut outputs = vec![];
+    let mut inputs: Vec<(GraphSocket, egui::Pos2, Input)> = vec![];
+    let mut clicked: Option<GraphSocket> = None;
+    let mut removed: Option<usize> = None;
+
+    for index in 0..graph.nodes.len() {
+        let position = graph.nodes[index].position;
+        let rect = egui::Rect::from_min_size(
+            origin + egui::vec2(position.0, position.1),
+            egui::vec2(NODE_WIDTH, 0.),
+        );
+        let mut node_ui = ui.child_ui_with_id_source(
+            rect,
+            egui::Layout::top_down(egui::Align::Min),
+            ("node", index),
+        );
+
+        // the node is taken out so the inputs can still look at the others
+        let mut kind = graph.nodes[index].kind.clone();
+
+        egui::Frame::popup(node_ui.style()).show(&mut node_ui, |ui| {
+            ui.set_width(NODE_WIDTH);
+
+            ui.horizontal(|ui| {
+                let title = ui.add(
+                    egui::Label::new(format!("{} {}", kind.name(), index))
+                        .sense(egui::Sense::drag()),
+                );
+                let delta = title.drag_delta();
+                let node = &mut graph.nodes[index];
+                node.position = (
+                    (node.position.0 + delta.x).max(0.),
+                    (node.position.1 + delta.y).max(0.),
+                );
+
+                if ui.small_button("x").clicked() {
+                    removed = Some(index);
+                }
+
+                let (socket, response) = socket_widget(ui, pending == Some(index));
+                if response.clicked() {
+                    pending = Some(index);
+                }
+                outputs.push(socket);
+            });
+
+            match &mut kind {
+                NodeKind::Uv | NodeKind::Position => (),
+                NodeKind::Texture { texture, scale } => {
+                    ui.label(texture.path.clone());
+                    ui.horizontal(|ui| {
+                        ui.label("scale");
+                        ui.add(egui::DragValue::new(scale).speed(0.1));
+                    });
+                }
+                NodeKind::Noise { texture } => {
+                    solid_texture_widget(ui, ui.make_persistent_id(("noise", index)), texture)
+                }
+                NodeKind::Math {
+                    operation,
+                    a: _,
+                    b: _,
+                } => {
+                    egui::ComboBox::from_id_source(ui.make_persistent_id(("operation", index)))
+                        .selected_text(operation.name())
+                        .show_ui(ui, |ui| {
+                            for option in MathOp::ALL {
+                                ui.selectable_value(operation, option, option.name());
+                            }
+                        });
+                }
+                NodeKind::Mix { .. } => (),
+                NodeKind::Fresnel { ior } => {
+                    ui.horizontal(|ui| {
+                        ui.label("ior");
+                        ui.add(
+                            egui::DragValue::new(ior)
+                                .clamp_range::<f64>(1.0..=5.)
+                                .speed(0.01),
+                        );
+                    });
+                }
+            }
+
+            for (input_index, (label, input)) in kind.inputs_mut().into_iter().enumerate() {
+                let (socket, response) = graph_input_widget(ui, graph, label, input);
+                let target = GraphSocket::Node(index, input_index);
+
+                if response.clicked() {
+                    clicked = Some(target);
+                }
+                inputs.push((target, socket, input.clone()));
+            }
+        });
+
+        graph.nodes[index].kind = kind;
+    }
+
+    // the material's own inputs, down the right hand side
+    let mut output_ui = ui.child_ui_with_id_source(
+        egui::Rect::from_min_size(
+            egui::pos2(canvas.rect.right() - 150., origin.y + 10.),
+            egui::vec2(140., 0.),
+        ),
+        egui::Layout::top_down(egui::Align::Min),
+        "outputs",
+    );
+    egui::Frame::popup(output_ui.style()).show(&mut output_ui, |ui| {
+        ui.label("material");
+
+        for output in BsdfInput::ALL {
+            ui.horizontal(|ui| {
+                let input = graph.output(output).clone();
+                let (socket, response) = socket_widget(ui, input.is_some());
+                ui.label(output.name());
+
+                if response.clicked() {
+                    clicked = Some(GraphSocket::Output(output));
+                }
+                if let Some(input) = input {
+                    inputs.push((GraphSocket::Output(output), socket, input));
+                }
+            });
+        }
+    });
+
+    // connect the pending output, or disconnect if there isn't one
+    if let Some(target) = clicked {
+        let new_input = pending.take().map(Input::Node);
+
+        match (target, new_input) {
+            (GraphSocket::Node(node, input_index), Some(Input::Node(from))) => {
+                if !graph.depends_on(from, node) {
+                    *graph.nodes[node].kind.inputs_mut()[input_index].1 = Input::Node(from);
+                }
+            }
+            (GraphSocket::Node(node, input_index), _) => {
+                let input = graph.nodes[node].kind.inputs_mut().remove(input_index).1;
+                if matches!(input, Input::Node(_)) {
+                    *input = Input::Value(0.);
+                }
+            }
+            (GraphSocket::Output(output), new_input) => *graph.output_mut(output) = new_input,
+        }
+    }
+
+    if canvas.secondary_clicked() {
+        pending = None;
+    }
+
+    let wire = |from: egui::Pos2, to: egui::Pos2| {
+        let bend = ((to.x - from.x).abs() / 2.).max(30.);
+
+        egui::Shape::CubicBezier(egui::epaint::CubicBezierShape::from_points_stroke(
+            [
+                from,
+                from + egui::vec2(bend, 0.),
+                to - egui::vec2(bend, 0.),
+                to,
+            ],
+            false,
+            egui::Color32::TRANSPARENT,
+            egui::Stroke::new(2., egui::Color32::LIGHT_BLUE),
+        ))
+    };
+
+    let mut shapes: Vec<egui::Shape> = inputs
+        .iter()
+        .filter_map(|(_, to, input)| match input {
+            Input::Node(from) => outputs.get(*from).map(|from| wire(*from, *to)),
+            _ => None,
+        })
+        .collect();
+
+    // the wire being connected follows the mouse
+    if let (Some(from), Some(pointer)) = (
+        pending.and_then(|index| outputs.get(index)),
+        ui.input().pointer.hover_pos(),
+    ) {
+        shapes.push(wire(*from, pointer));
+    }
+
+    painter.set(wires, egui::Shape::Vec(shapes));
+
+    if let Some(index) = removed {
+        graph.remove_node(index);
+        pending = None;
+    }
+
+    match pending {
+        Some(pending) => ui.memory().data.insert_temp(pending_id, pending),
+        None => ui.memory().data.remove::<usize>(pending_id),
+    }
+}
+
+/// A thumbnail of a material, only rendered again when it changes
+fn material_preview_widget(ui: &mut egui::Ui, id: egui::Id, material: &Material) {
+    const SIZE: u32 = 64;
+
+    // comparing the json is simpler than keeping track of every edit
+    let json = serde_json::to_string(material).unwrap_or_default();
+    let preview = ui
+        .memory()
+        .data
+        .get_temp::<(String, eframe::epaint::TextureHandle)>(id);
+
+    let texture = match preview {
+        Some((old_json, texture)) if old_json == json => texture,
+        _ => {
+            let image = render_material_preview(material, SIZE);
+            let texture = ui.ctx().load_texture("material_preview", image);
+            ui.memory().data.insert_temp(id, (json, texture.clone()));
+            texture
+        }
+    };
+
+    ui.image(texture.id(), texture.size_vec2());
+}
+
+pub fn library_panel(ui: &mut egui::Ui, scene: &mut Scene) {
+    ui.heading("Materials");
+
+    if ui.add(egui::Button::new("➕ material")).clicked() {
+        scene.materials.push(LibraryMaterial {
+            name: scene.unique_material_name("material"),
+            material: default_material(),
+        });
+    }
+
+    ui.separator();
+
+    let targets: Vec<String> = scene
+        .objects
+        .iter()
+        .map(|object| object.name.clone())
+        .chain(
+            scene
+                .prototypes
+                .iter()
+                .map(|prototype| format!("prototype {}", prototype.name)),
+        )
+        .collect();
+
+    let users: Vec<usize> = scene
+        .materials
+        .iter()
+        .map(|shared| scene.material_users(&shared.name))
+        .collect();
+
+    let mut renamed = None;
+    let mut duplicated = None;
+    let mut removed = None;
+    let mut assigned = None;
+
+    for (index, shared) in scene.materials.iter_mut().enumerate() {
+        let id = ui.make_persistent_id(("library_material", index));
+
+        ui.horizontal(|ui| {
+            material_preview_widget(ui, id.with("preview"), &shared.material);
+
+            ui.vertical(|ui| {
+                // the name is only changed once it's finished, so every reference can follow it
+                let name_id = id.with("name");
+                let mut name = ui
+                    .memory()
+                    .data
+                    .get_temp::<String>(name_id)
+                    .unwrap_or_else(|| shared.name.clone());
+
+                let response = ui.text_edit_singleline(&mut name);
+                if response.lost_focus() {
+                    if name != shared.name && !name.is_empty() {
+                        renamed = Some((shared.name.clone(), name));
+                    }
+                    ui.memory().data.remove::<String>(name_id);
+                } else if response.has_focus() {
+                    ui.memory().data.insert_temp(name_id, name);
+                }
+
+                ui.label(format!("used by {}", users[index]));
+
+                ui.horizontal(|ui| {
+                    if ui.button("duplicate").clicked() {
+                        duplicated = Some(index);
+                    }
+
+                    if ui.add(egui::Button::new("❌")).clicked() {
+                        removed = Some(index);
+                    }
+                });
+
+                egui::ComboBox::from_id_source(id.with("assign"))
+                    .selected_text("assign to")
+                    .show_ui(ui, |ui| {
+                        for (target, label) in targets.iter().enumerate() {
+                            if ui.selectable_label(false, label).clicked() {
+                                assigned = Some((shared.name.clone(), target));
+                            }
+                        }
+                    });
+            });
+        });
+
+        egui::CollapsingHeader::new("edit")
+            .id_source(id.with("edit"))
+            .show(ui, |ui| material_widget(ui, id, &mut shared.material));
+
+        ui.separator();
+    }
+
+    if let Some((old_name, new_name)) = renamed {
+        let new_name = scene.unique_material_name(&new_name);
+        scene.rename_material(&old_name, &new_name);
+    }
+
+    if let Some(index) = duplicated {
+        let shared = &scene.materials[index];
+        let copy = LibraryMaterial {
+            name: scene.unique_material_name(&format!("{} copy", shared.name)),
+            material: shared.material.clone(),
+        };
+        scene.materials.insert(index + 1, copy);
+    }
+
+    if let Some(index) = removed {
+        scene.remove_material(index);
+    }
+
+    if let Some((name, target)) = assigned {
+        let object_count = scene.objects.len();
+
+        if target < object_count {
+            scene.objects[target].material = MaterialRef::Library(name);
+        } else {
+            scene.prototypes[target - object_count].material = MaterialRef::Library(name);
+        }
+    }
+}
+
+pub fn settings_panel(
+    ui: &mut egui::Ui,
+    fps: f32,
+    ray_tracer: &mut RayTracer,
+    has_size_changed: &mut bool,
+) {
+    ui.heading("Settings");
+
+    // this isn't perfect as I'm not using a fixed width font
+    // but it still looks better than nothing
+    ui.label(format!("fps: {: >4}", fps.round()));
+
+    ui.separator();
+
+    ui.horizontal(|ui| {
+        let mut new_width = ray_tracer.width;
+        let mut new_height = ray_tracer.height;
+
+        ui.label("width");
+        ui.add(egui::DragValue::new(&mut new_width).speed(20));
+        ui.label("height");
+        ui.add(egui::DragValue::new(&mut new_height).speed(20));
+
+        if new_width != ray_tracer.width || new_height != ray_tracer.height {
+            *has_size_changed = true;
+            ray_tracer.width = new_width;
+            ray_tracer.height = new_height;
+        }
+    });
+
+    ui.separator();
+
+    vec3_widget(ui, "pos", &mut ray_tracer.camera);
+    vec3_widget(ui, "rot", &mut ray_tracer.rotation);
+
+    ui.separator();
+
+    ui.horizontal(|ui| {
+        ui.label("bounces");
+        ui.add(
+            egui::DragValue::new(&mut ray_tracer.scene.reflection_limit).clamp_range::<u32>(0..=10),
+        );
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("reflection samples");
+        ui.add(
+            egui::DragValue::new(&mut ray_tracer.scene.reflection_samples)
+                .clamp_range::<u32>(1..=64),
+        );
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("emission samples");
+        ui.add(
+            egui::DragValue::new(&mut ray_tracer.scene.emission_samples).clamp_range::<u32>(1..=64),
+        );
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("pixel samples");
+        ui.add(
+            egui::DragValue::new(&mut ray_tracer.scene.pixel_samples).clamp_range::<u32>(1..=256),
+        );
+    });
+
+    ui.separator();
+
+    let blur = &mut ray_tracer.scene.motion_blur;
+    ui.horizontal(|ui| {
+        ui.label("shutter");
+        ui.add(
+            egui::DragValue::new(&mut blur.shutter_open)
+                .fixed_decimals(3)
+                .speed(0.001),
+        );
+        ui.label("to");
+        ui.add(
+            egui::DragValue::new(&mut blur.shutter_close)
+                .fixed_decimals(3)
+                .speed(0.001)
+                .clamp_range(blur.shutter_open..=f64::INFINITY),
+        );
+    });
+    let camera = Transform {
+        position: ray_tracer.camera,
+        rotation: ray_tracer.rotation,
+        ..Transform::default()
+    };
+    motion_widget(ui, "camera_motion", &camera, &mut blur.camera);
+
+    ui.separator();
+
+    let mut has_fog = ray_tracer.scene.fog.is_some();
+    ui.checkbox(&mut has_fog, "fog");
+
+    match (has_fog, &mut ray_tracer.scene.fog) {
+        (true, Some(fog)) => volume_widget(ui, &mut fog.colour, &mut fog.density, &mut fog.albedo),
+        (true, None) => {
+            ray_tracer.scene.fog = Some(Fog {
+                colour: (0.8, 0.8, 0.8),
+                density: 0.05,
+                albedo: 0.9,
+            })
+        }
+        (false, _) => ray_tracer.scene.fog = None,
+    }
+
+    ui.horizontal(|ui| {
+        ui.label("volume steps");
+        ui.add(
+            egui::DragValue::new(&mut ray_tracer.scene.volume_steps).clamp_range::<u32>(1..=256),
+        );
+    });
+
+    ui.separator();
+
+    generator_widget(ui, ray_tracer);
+    scene_file_widget(ui, &mut ray_tracer.scene);
+    import_widget(ui, ray_tracer);
+    export_widget(ui, ray_tracer);
+    sequence_widget(ui, ray_tracer);
+}
+
+fn count_widget(ui: &mut egui::Ui, label: &str, value: &mut u32) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(egui::DragValue::new(value).clamp_range::<u32>(1..=10000));
+    });
+}
+
+fn range_widget(ui: &mut egui::Ui, label: &str, range: &mut (f64, f64), max: f64) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(
+            egui::DragValue::new(&mut range.0)
+                .clamp_range(0.0..=max)
+                .speed(max / 100.),
+        );
+        ui.label("to");
+        ui.add(
+            egui::DragValue::new(&mut range.1)
+                .clamp_range(range.0..=max)
+                .speed(max / 100.),
+        );
+    });
+}
+
+fn generator_settings_widget(ui: &mut egui::Ui, id: egui::Id, settings: &mut GeneratorSettings) {
+    ui.horizontal(|ui| {
+        let mut kind = settings.generator.kind();
+        egui::ComboBox::from_id_source(id.with("kind"))
+            .selected_text(kind)
+            .show_ui(ui, |ui| {
+                for option in Generator::KINDS {
+                    ui.selectable_value(&mut kind, option, option);
+                }
+            });
+
+        if kind != settings.generator.kind() {
+            settings.generator = Generator::new(kind);
+        }
+
+        ui.label("seed");
+        ui.add(egui::DragValue::new(&mut settings.seed));
+        if ui.button("🎲").clicked() {
+            settings.seed = rand::random();
+        }
+    });
+
+    match &mut settings.generator {
+        Generator::RandomSpheres {
+            count,
+            min_radius,
+            max_radius,
+            placement_radius,
+        } => {
+            count_widget(ui, "count", count);
+            drag_widget(ui, "min radius", min_radius);
+            drag_widget(ui, "max radius", max_radius);
+            drag_widget(ui, "placement radius", placement_radius);
+            *max_radius = max_radius.max(*min_radius);
+        }
+        Generator::Grid {
+            columns,
+            rows,
+            spacing,
+            radius,
+        } => {
+            count_widget(ui, "columns", columns);
+            count_widget(ui, "rows", rows);
+            drag_widget(ui, "spacing", spacing);
+            drag_widget(ui, "radius", radius);
+        }
+        Generator::CornellBox { size } => drag_widget(ui, "size", size),
+        Generator::Spiral {
+            count,
+            turns,
+            radius,
+            height,
+            sphere_radius,
+        } => {
+            count_widget(ui, "count", count);
+            drag_widget(ui, "turns", turns);
+            drag_widget(ui, "radius", radius);
+            drag_widget(ui, "height", height);
+            drag_widget(ui, "sphere radius", sphere_radius);
+        }
+    }
+
+    // the cornell box has its own materials
+    if let Generator::CornellBox { .. } = settings.generator {
+        return;
+    }
+
+    ui.separator();
+
+    let materials = &mut settings.materials;
+    ui.horizontal(|ui| {
+        ui.label("model");
+        ui.selectable_value(&mut materials.model, MaterialModel::Phong, "phong");
+        ui.selectable_value(&mut materials.model, MaterialModel::Pbr, "pbr");
+    });
+    range_widget(ui, "specular", &mut materials.specular, 1000.);
+    range_widget(ui, "roughness", &mut materials.roughness, 1.);
+    ui.add(egui::Slider::new(&mut materials.metallic_chance, 0.0..=1.).text("metallic chance"));
+    ui.add(egui::Slider::new(&mut materials.emissive_chance, 0.0..=1.).text("emissive chance"));
+}
+
+/// A window for replacing the scene with a generated one
+fn generator_widget(ui: &mut egui::Ui, ray_tracer: &mut RayTracer) {
+    let id = ui.make_persistent_id("generator");
+    let open_id = id.with("open");
+
+    let mut open = ui.memory().data.get_temp::<bool>(open_id).unwrap_or(false);
+    let mut settings = ui
+        .memory()
+        .data
+        .get_temp::<GeneratorSettings>(id)
+        .unwrap_or_default();
+
+    if ui.button("new scene from generator").clicked() {
+        open = true;
+    }
+
+    let mut has_generated = false;
+    egui::Window::new("new scene from generator")
+        .id(id.with("window"))
+        .open(&mut open)
+        .show(ui.ctx(), |ui| {
+            generator_settings_widget(ui, id, &mut settings);
+
+            ui.separator();
+
+            if ui.button("generate").clicked() {
+                settings.apply(ray_tracer);
+                has_generated = true;
+            }
+        });
+
+    ui.memory()
+        .data
+        .insert_temp(open_id, open && !has_generated);
+    ui.memory().data.insert_temp(id, settings);
+}
+
+/// Loads or saves the whole scene, as .json, .ron, .toml or .yaml
+fn scene_file_widget(ui: &mut egui::Ui, scene: &mut Scene) {
+    let id = ui.make_persistent_id("scene_file");
+    let path_id = id.with("path");
+
+    ui.horizontal(|ui| {
+        let mut path = ui
+            .memory()
+            .data
+            .get_temp::<String>(path_id)
+            .unwrap_or_else(|| "scene.json".to_string());
+
+        ui.text_edit_singleline(&mut path);
+
+        let mut result = None;
+        if ui.button("load").clicked() {
+            result = Some(Scene::load(&path).map(|loaded| *scene = loaded));
+        }
+        if ui.button("save").clicked() {
+            result = Some(scene.save(&path));
+        }
+
+        match result {
+            Some(Ok(())) => ui.memory().data.remove::<String>(id),
+            Some(Err(error)) => ui.memory().data.insert_temp(id, error.to_string()),
+            None => (),
+        }
+
+        ui.memory().data.insert_temp(path_id, path);
+    });
+
+    let error = ui.memory().data.get_temp::<String>(id);
+    if let Some(error) = error {
+        ui.colored_label(egui::Color32::RED, error);
+    }
+}
+
+/// Writes the scene out as an OBJ or glTF file, picked by the extension
+fn export_widget(ui: &mut egui::Ui, ray_tracer: &RayTracer) {
+    let id = ui.make_persistent_id("export");
+    let path_id = id.with("path");
+    let segments_id = id.with("segments");
+
+    let mut segments = ui
+        .memory()
+        .data
+        .get_temp::<usize>(segments_id)
+        .unwrap_or(DEFAULT_SEGMENTS);
+
+    ui.horizontal(|ui| {
+        ui.label("segments");
+        ui.add(egui::DragValue::new(&mut segments).clamp_range(MIN_SEGMENTS..=256));
+    });
+
+    ui.horizontal(|ui| {
+        let mut path = ui
+            .memory()
+            .data
+            .get_temp::<String>(path_id)
+            .unwrap_or_default();
+
+        ui.text_edit_singleline(&mut path);
+
+        if ui.button("export").clicked() {
+            match export_scene(ray_tracer, &path, segments) {
+                Ok(()) => ui.memory().data.remove::<String>(id),
+                Err(error) => ui.memory().data.insert_temp(id, error.to_string()),
+            }
+        }
+
+        ui.memory().data.insert_temp(path_id, path);
+    });
+
+    ui.memory().data.insert_temp(segments_id, segments);
+
+    let error = ui.memory().data.get_temp::<String>(id);
+    if let Some(error) = error {
+        ui.colored_label(egui::Color32::RED, error);
+    }
+}
+
+/// Renders the animation out frame by frame, in the background
+fn sequence_widget(ui: &mut egui::Ui, ray_tracer: &RayTracer) {
+    let id = ui.make_persistent_id("sequence");
+    let settings_id = id.with("settings");
+    let job_id = id.with("job");
+
+    let mut settings = ui
+        .memory()
+        .data
+        .get_temp::<SequenceSettings>(settings_id)
+        .unwrap_or_else(|| SequenceSettings {
+            folder: "frames".to_string(),
+            format: FrameFormat::Png,
+            first_frame: 0,
+            last_frame: ((ray_tracer.scene.animation.length * 30.) as u32)
+                .min(SequenceSettings::MAX_FRAME),
+            frames_per_second: 30.,
+            resume: true,
+        });
+
+    ui.horizontal(|ui| {
+        ui.label("folder");
+        ui.text_edit_singleline(&mut settings.folder);
+    });
+
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut settings.format, FrameFormat::Png, "png");
+        ui.radio_value(&mut settings.format, FrameFormat::Exr, "exr");
+        ui.checkbox(&mut settings.resume, "resume");
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("frames");
+        ui.add(
+            egui::DragValue::new(&mut settings.first_frame)
+                .clamp_range(0..=SequenceSettings::MAX_FRAME),
+        );
+        ui.label("to");
+        ui.add(
+            egui::DragValue::new(&mut settings.last_frame)
+                .clamp_range(settings.first_frame..=SequenceSettings::MAX_FRAME),
+        );
+        ui.label("fps");
+        ui.add(egui::DragValue::new(&mut settings.frames_per_second).clamp_range(1..=240));
+    });
+
+    let job = ui.memory().data.get_temp::<SequenceJob>(job_id);
+    let progress = job.as_ref().map(|job| job.progress());
+    let is_running = matches!(&progress, Some(progress) if !progress.is_finished);
+
+    ui.horizontal(|ui| {
+        if is_running {
+            if ui.button("stop").clicked() {
+                if let Some(job) = &job {
+                    job.cancel();
+                }
+            }
+        } else if ui.button("render frames").clicked() {
+            let job = SequenceJob::start(ray_tracer.clone(), settings.clone());
+            ui.memory().data.insert_temp(job_id, job);
+        }
+
+        if let Some(progress) = &progress {
+            let fraction = progress.done as f32 / progress.total.max(1) as f32;
+            let text = if progress.is_finished {
+                format!("{} of {} frames", progress.done, progress.total)
+            } else {
+                format!(
+                    "frame {} ({} of {})",
+                    progress.frame,
+                    progress.done + 1,
+                    progress.total
+                )
+            };
+            ui.add(egui::ProgressBar::new(fraction).text(text));
+        }
+    });
+
+    if let Some(progress) = &progress {
+        if progress.skipped > 0 {
+            ui.label(format!("{} frames were already there", progress.skipped));
+        }
+        if let Some(error) = &progress.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    }
+
+    // keeps the progress bar moving
+    if is_running {
+        ui.ctx().request_repaint();
+    }
+
+    ui.memory().data.insert_temp(settings_id, settings);
+}
+
+/// A plain white object, so any vertex colours show as they are
+fn mesh_object(path: &str, mesh: Mesh) -> Object {
+    Object {
+        name: std::path::Path::new(path)
+            .file_stem()
+            .map_or("mesh".to_string(), |name| {
+                name.to_string_lossy().into_owned()
+            }),
+        material: MaterialRef::Own(Box::new(Material {
+            model: MaterialModel::Phong,
+            colour: (1., 1., 1.),
+            specular: 100.,
+            metallic: 0.,
+            roughness: 0.,
+            emission: (0., 0., 0.),
+            emission_strength: 0.,
+            normal_map: None,
+            textures: MaterialTextures::default(),
+            graph: None,
+        })),
+        geometry: Geometry::Mesh {
+            center: Vec3 {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            mesh,
+        },
+        transform: Transform::default(),
+        motion: None,
+    }
+}
+
+/// Adds the contents of a glTF file to the scene,
+/// or a PLY or STL file as one object
+fn import_widget(ui: &mut egui::Ui, ray_tracer: &mut RayTracer) {
+    let id = ui.make_persistent_id("gltf_import");
+    let path_id = id.with("path");
+    let light_scale_id = id.with("light_scale");
+
+    ui.horizontal(|ui| {
+        let mut path = ui
+            .memory()
+            .data
+            .get_temp::<String>(path_id)
+            .unwrap_or_default();
+
+        let mut light_scale = ui
+            .memory()
+            .data
+            .get_temp::<f64>(light_scale_id)
+            .unwrap_or(1.);
+
+        ui.text_edit_singleline(&mut path);
+
+        ui.label("lights ×");
+        ui.add(
+            egui::DragValue::new(&mut light_scale)
+                .clamp_range(0.0..=f64::MAX)
+                .speed(0.001),
+        )
+        .on_hover_text(
+            "glTF light intensities are multiplied by this, \
+             Blender's are in the hundreds so try 0.01",
+        );
+
+        if ui.button("import").clicked() {
+            let is_gltf = path.ends_with(".gltf") || path.ends_with(".glb");
+
+            let result = if is_gltf {
+                GltfImport::load(&path, light_scale)
+                    .map(|import| import.add_to(ray_tracer))
+                    .map_err(|error| error.to_string())
+            } else {
+                load_mesh_file(&path)
+                    .map(|mesh| ray_tracer.scene.objects.push(mesh_object(&path, mesh)))
+                    .map_err(|error| error.to_string())
+            };
+
+            match result {
+                Ok(()) => ui.memory().data.remove::<String>(id),
+                Err(error) => ui.memory().data.insert_temp(id, error),
+            }
+        }
+
+        ui.memory().data.insert_temp(path_id, path);
+        ui.memory().data.insert_temp(light_scale_id, light_scale);
+    });
+
+    let error = ui.memory().data.get_temp::<String>(id);
+    if let Some(error) = error {
+        ui.colored_label(egui::Color32::RED, error);
+    }
+}
+
+fn target_label(target: &Target) -> String {
+    let name = |property: &dyn std::fmt::Debug| format!("{:?}", property).to_lowercase();
+
+    match target {
+        Target::Camera(property) => format!("camera {}", name(property)),
+        Target::Object {
+            name: object,
+            property,
+        } => format!("{} {}", object, name(property)),
+        Target::Light { index, property } => format!("light {} {}", index, name(property)),
+        Target::Material {
+            name: material,
+            property,
+        } => format!("{} material {}", material, name(property)),
+    }
+}
+
+fn target_kind(target: &Target) -> &'static str {
+    match target {
+        Target::Camera(_) => "camera",
+        Target::Object { .. } => "object",
+        Target::Light { .. } => "light",
+        Target::Material { .. } => "material",
+    }
+}
+
+/// The names that object and material targets can use
+fn target_names(scene: &Scene, kind: &str) -> Vec<String> {
+    let objects = scene.objects.iter().map(|object| &object.name);
+    let instances = scene.instances.iter().map(|instance| &instance.name);
+
+    let mut names: Vec<String> = match kind {
+        "material" => scene
+            .materials
+            .iter()
+            .map(|material| &material.name)
+            .chain(objects)
+            .chain(instances)
+            .cloned()
+            .collect(),
+        _ => objects.chain(instances).cloned().collect(),
+    };
+    names.dedup();
+    names
+}
+
+fn default_target(scene: &Scene, kind: &str) -> Target {
+    let name = target_names(scene, kind)
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+
+    match kind {
+        "object" => Target::Object {
+            name,
+            property: ObjectProperty::Position,
+        },
+        "light" => Target::Light {
+            index: 0,
+            property: LightProperty::Intensity,
+        },
+        "material" => Target::Material {
+            name,
+            property: MaterialProperty::Colour,
+        },
+        _ => Target::Camera(CameraProperty::Position),
+    }
+}
+
+fn property_combo<T: PartialEq + std::fmt::Debug + Copy>(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    property: &mut T,
+    options: &[T],
+) {
+    egui::ComboBox::from_id_source(id.with("property"))
+        .selected_text(format!("{:?}", property).to_lowercase())
+        .show_ui(ui, |ui| {
+            for option in options {
+                ui.selectable_value(property, *option, format!("{:?}", option).to_lowercase());
+            }
+        });
+}
+
+/// Picks the property for a new track
+fn target_widget(ui: &mut egui::Ui, id: egui::Id, scene: &Scene, target: &mut Target) {
+    let mut kind = target_kind(target);
+    egui::ComboBox::from_id_source(id.with("kind"))
+        .selected_text(kind)
+        .show_ui(ui, |ui| {
+            for option in ["camera", "object", "light", "material"] {
+                ui.selectable_value(&mut kind, option, option);
+            }
+        });
+
+    if kind != target_kind(target) {
+        *target = default_target(scene, kind);
+    }
+
+    let name_combo = |ui: &mut egui::Ui, name: &mut String| {
+        egui::ComboBox::from_id_source(id.with("name"))
+            .selected_text(name.as_str())
+            .show_ui(ui, |ui| {
+                for option in target_names(scene, kind) {
+                    ui.selectable_value(name, option.clone(), option);
+                }
+            });
+    };
+
+    match target {
+        Target::Camera(property) => property_combo(
+            ui,
+            id,
+            property,
+            &[
+                CameraProperty::Position,
+                CameraProperty::Rotation,
+                CameraProperty::Fov,
+            ],
+        ),
+        Target::Object { name, property } => {
+            name_combo(ui, name);
+            property_combo(
+                ui,
+                id,
+                property,
+                &[
+                    ObjectProperty::Position,
+                    ObjectProperty::Rotation,
+                    ObjectProperty::Scale,
+                ],
+            );
+        }
+        Target::Light { index, property } => {
+            ui.add(egui::DragValue::new(index).clamp_range(0..=scene.lights.len().max(1) - 1));
+            property_combo(
+                ui,
+                id,
+                property,
+                &[
+                    LightProperty::Intensity,
+                    LightProperty::Position,
+                    LightProperty::Direction,
+                ],
+            );
+        }
+        Target::Material { name, property } => {
+            name_combo(ui, name);
+            property_combo(
+                ui,
+                id,
+                property,
+                &[
+                    MaterialProperty::Colour,
+                    MaterialProperty::Specular,
+                    MaterialProperty::Metallic,
+                    MaterialProperty::Roughness,
+                    MaterialProperty::Emission,
+                    MaterialProperty::EmissionStrength,
+                ],
+            );
+        }
+    }
+}
+
+/// Returns whether the value was changed
+fn keyframe_value_widget(ui: &mut egui::Ui, value: &mut Value) -> bool {
+    let old_value = *value;
+
+    match value {
+        Value::Number(number) => {
+            ui.add(egui::DragValue::new(number).speed(0.1));
+        }
+        Value::Vector(vector) => {
+            for component in [&mut vector.x, &mut vector.y, &mut vector.z] {
+                ui.add(egui::DragValue::new(component).fixed_decimals(1).speed(0.1));
+            }
+        }
+        Value::Colour(colour) => {
+            let mut rgb = [colour.0 as f32, colour.1 as f32, colour.2 as f32];
+            ui.color_edit_button_rgb(&mut rgb);
+            *colour = (rgb[0] as f64, rgb[1] as f64, rgb[2] as f64);
+        }
+    }
+
+    *value != old_value
+}
+
+/// The keyframes of a track as dots along a strip, clicking it moves the time there
+fn keyframe_strip(ui: &mut egui::Ui, track: &Track, length: f64, time: &mut f64) {
+    let size = egui::vec2(ui.available_width(), 12.);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+    let painter = ui.painter();
+
+    let x = |time: f64| rect.left() + rect.width() * (time / length) as f32;
+
+    painter.rect_filled(rect, 2., ui.visuals().extreme_bg_color);
+    for keyframe in &track.keyframes {
+        painter.circle_filled(
+            egui::pos2(x(keyframe.time), rect.center().y),
+            4.,
+            egui::Color32::GOLD,
+        );
+    }
+    painter.line_segment(
+        [
+            egui::pos2(x(*time), rect.top()),
+            egui::pos2(x(*time), rect.bottom()),
+        ],
+        (1., ui.visuals().text_color()),
+    );
+
+    if let Some(pointer) = response.interact_pointer_pos() {
+        *time = ((pointer.x - rect.left()) / rect.width()) as f64 * length;
+        *time = time.clamp(0., length);
+    }
+}
+
+/// Plays and scrubs through the scene's animation, and edits its tracks
+pub fn timeline_panel(ui: &mut egui::Ui, ray_tracer: &mut RayTracer) {
+    let id = ui.make_persistent_id("timeline");
+    let time_id = id.with("time");
+    let playing_id = id.with("playing");
+    let new_target_id = id.with("new_target");
+    let recorder_id = id.with("recorder");
+    let interval_id = id.with("interval");
+
+    let mut time = ui.memory().data.get_temp::<f64>(time_id).unwrap_or(0.);
+    let mut is_playing = ui
+        .memory()
+        .data
+        .get_temp::<bool>(playing_id)
+        .unwrap_or(false);
+    let mut recorder = ui.memory().data.get_temp::<CameraRecorder>(recorder_id);
+    let mut interval = ui
+        .memory()
+        .data
+        .get_temp::<f64>(interval_id)
+        .unwrap_or(0.25);
+    let old_time = time;
+    let mut has_changed = false;
+
+    // taken out, so the targets can read and write the rest of the ray tracer
+    let mut animation = std::mem::take(&mut ray_tracer.scene.animation);
+
+    ui.horizontal(|ui| {
+        if ui.button(if is_playing { "⏸" } else { "▶" }).clicked() {
+            is_playing = !is_playing;
+        }
+        if ui.button("⏮").clicked() {
+            time = 0.;
+        }
+
+        let record_text = if recorder.is_some() { "⏹" } else { "⏺" };
+        if ui
+            .button(record_text)
+            .on_hover_text("record the camera as it's flown around")
+            .clicked()
+        {
+            match recorder.take() {
+                Some(recorder) => {
+                    recorder.stop(&mut animation, time, ray_tracer.camera, ray_tracer.rotation);
+                    has_changed = true;
+                }
+                None => {
+                    recorder = Some(CameraRecorder::start(&mut animation, time, interval));
+                    is_playing = false;
+                }
+            }
+        }
+
+        ui.label("key every");
+        ui.add(
+            egui::DragValue::new(&mut interval)
+                .clamp_range(0.01..=10.)
+                .speed(0.01)
+                .suffix("s"),
+        );
+
+        ui.add(egui::Slider::new(&mut time, 0.0..=animation.length).suffix("s"));
+
+        ui.label("length");
+        ui.add(
+            egui::DragValue::new(&mut animation.length)
+                .clamp_range(Animation::MIN_LENGTH..=3600.)
+                .speed(0.1)
+                .suffix("s"),
+        );
+    });
+
+    if let Some(recorder) = &mut recorder {
+        // carries on past the end, which makes the animation longer
+        time += ui.input().unstable_dt as f64;
+        recorder.record(&mut animation, time, ray_tracer.camera, ray_tracer.rotation);
+    } else if is_playing {
+        time = (time + ui.input().unstable_dt as f64) % animation.length;
+    }
+    time = time.clamp(0., animation.length);
+
+    let mut removed_track = None;
+
+    for (index, track) in animation.tracks.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(target_label(&track.target));
+
+            if ui
+                .button("🔑")
+                .on_hover_text("keyframe the value it has now")
+                .clicked()
+            {
+                if let Some(value) = track.target.get(ray_tracer) {
+                    track.insert(Keyframe {
+                        time,
+                        value,
+                        curve: Curve::default(),
+                    });
+                    has_changed = true;
+                }
+            }
+
+            if ui.button("❌").clicked() {
+                removed_track = Some(index);
+            }
+        });
+
+        keyframe_strip(ui, track, animation.length, &mut time);
+
+        egui::CollapsingHeader::new("keyframes")
+            .id_source(id.with(("keyframes", index)))
+            .show(ui, |ui| {
+                let mut removed_keyframe = None;
+
+                for (key_index, keyframe) in track.keyframes.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        let old_keyframe_time = keyframe.time;
+                        ui.add(
+                            egui::DragValue::new(&mut keyframe.time)
+                                .clamp_range(0.0..=animation.length)
+                                .speed(0.05)
+                                .suffix("s"),
+                        );
+                        has_changed |= keyframe.time != old_keyframe_time;
+
+                        let old_curve = keyframe.curve;
+                        egui::ComboBox::from_id_source(id.with(("curve", index, key_index)))
+                            .selected_text(format!("{:?}", keyframe.curve))
+                            .show_ui(ui, |ui| {
+                                for curve in Curve::ALL {
+                                    ui.selectable_value(
+                                        &mut keyframe.curve,
+                                        curve,
+                                        format!("{:?}", curve),
+                                    );
+                                }
+                            });
+                        has_changed |= keyframe.curve != old_curve;
+
+                        has_changed |= keyframe_value_widget(ui, &mut keyframe.value);
+
+                        if ui.button("❌").clicked() {
+                            removed_keyframe = Some(key_index);
+                        }
+                    });
+                }
+
+                if let Some(key_index) = removed_keyframe {
+                    track.keyframes.remove(key_index);
+                    has_changed = true;
+                }
+            });
+
+        track.sort();
+    }
+
+    if let Some(index) = removed_track {
+        animation.tracks.remove(index);
+    }
+
+    ui.horizontal(|ui| {
+        let mut target = ui
+            .memory()
+            .data
+            .get_temp::<Target>(new_target_id)
+            .unwrap_or(Target::Camera(CameraProperty::Position));
+
+        target_widget(ui, new_target_id, &ray_tracer.scene, &mut target);
+
+        if ui.button("➕ track").clicked() {
+            animation.tracks.push(Track {
+                target: target.clone(),
+                keyframes: vec![],
+            });
+        }
+
+        ui.memory().data.insert_temp(new_target_id, target);
+    });
+
+    ray_tracer.scene.animation = animation;
+
+    if recorder.is_some() {
+        // everything else plays, but the camera is left to be flown
+        let (camera, rotation) = (ray_tracer.camera, ray_tracer.rotation);
+        ray_tracer.animate(time);
+        ray_tracer.camera = camera;
+        ray_tracer.rotation = rotation;
+    } else if has_changed || is_playing || time != old_time {
+        // only while it's moving, so properties can still be changed by hand when it's stopped
+        ray_tracer.animate(time);
+    }
+
+    ui.memory().data.insert_temp(time_id, time);
+    ui.memory().data.insert_temp(playing_id, is_playing);
+    ui.memory().data.insert_temp(interval_id, interval);
+    match recorder {
+        Some(recorder) => ui.memory().data.insert_temp(recorder_id, recorder),
+        None => ui.memory().data.remove::<CameraRecorder>(recorder_id),
+    }
 }
@@ -0,0 +1,462 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::ray_tracer::{deserialize_colour, Light, Material, MaterialRef, RayTracer, Vec3};
+
+/// What a keyframe sets a property to
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Vector(Vec3),
+    Colour(#[serde(deserialize_with = "deserialize_colour")] (f64, f64, f64)),
+}
+
+impl Value {
+    fn components(&self) -> [f64; 3] {
+        match *self {
+            Value::Number(number) => [number, 0., 0.],
+            Value::Vector(vector) => [vector.x, vector.y, vector.z],
+            Value::Colour((r, g, b)) => [r, g, b],
+        }
+    }
+
+    /// A value of the same kind as this one
+    fn with_components(&self, [x, y, z]: [f64; 3]) -> Value {
+        match self {
+            Value::Number(_) => Value::Number(x),
+            Value::Vector(_) => Value::Vector(Vec3 { x, y, z }),
+            Value::Colour(_) => Value::Colour((x, y, z)),
+        }
+    }
+}
+
+/// How a keyframe moves to the next one
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum Curve {
+    /// holds the value until the next keyframe
+    Step,
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    /// a catmull-rom spline through the keyframes around it, so there are no corners
+    Smooth,
+}
+
+impl Default for Curve {
+    fn default() -> Curve {
+        Curve::Linear
+    }
+}
+
+impl Curve {
+    pub const ALL: [Curve; 6] = [
+        Curve::Step,
+        Curve::Linear,
+        Curve::EaseIn,
+        Curve::EaseOut,
+        Curve::EaseInOut,
+        Curve::Smooth,
+    ];
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Keyframe {
+    /// in seconds
+    pub time: f64,
+    pub value: Value,
+    #[serde(default)]
+    pub curve: Curve,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum CameraProperty {
+    Position,
+    Rotation,
+    Fov,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum ObjectProperty {
+    Position,
+    Rotation,
+    Scale,
+}
+
+/// Position only works on point lights, and direction on directional ones
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum LightProperty {
+    Intensity,
+    Position,
+    Direction,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum MaterialProperty {
+    Colour,
+    Specular,
+    Metallic,
+    Roughness,
+    Emission,
+    EmissionStrength,
+}
+
+/// The property a track animates
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum Target {
+    Camera(CameraProperty),
+    /// the transform of every object and instance with the name
+    Object {
+        name: String,
+        property: ObjectProperty,
+    },
+    Light {
+        index: usize,
+        property: LightProperty,
+    },
+    /// a library material, or the materials of the objects and instances with the name
+    Material {
+        name: String,
+        property: MaterialProperty,
+    },
+}
+
+/// Every material that a material target could mean
+fn named_materials<'a>(ray_tracer: &'a mut RayTracer, name: &str) -> Vec<&'a mut Material> {
+    let scene = &mut ray_tracer.scene;
+
+    if let Some(library) = scene
+        .materials
+        .iter_mut()
+        .find(|material| material.name == name)
+    {
+        return vec![&mut library.material];
+    }
+
+    let objects = scene
+        .objects
+        .iter_mut()
+        .filter(|object| object.name == name)
+        .map(|object| &mut object.material);
+    let instances = scene
+        .instances
+        .iter_mut()
+        .filter(|instance| instance.name == name)
+        .filter_map(|instance| instance.material.as_mut());
+
+    objects
+        .chain(instances)
+        .filter_map(|material| match material {
+            MaterialRef::Own(material) => Some(material.as_mut()),
+            MaterialRef::Library(_) => None,
+        })
+        .collect()
+}
+
+impl Target {
+    /// The property's value now, or None if there's nothing with that name
+    pub fn get(&self, ray_tracer: &mut RayTracer) -> Option<Value> {
+        let mut value = None;
+        self.visit(ray_tracer, |property| {
+            if value.is_none() {
+                value = Some(*property);
+            }
+        });
+        value
+    }
+
+    pub fn set(&self, ray_tracer: &mut RayTracer, value: Value) {
+        self.visit(ray_tracer, |property| {
+            // a keyframe of the wrong kind is left alone
+            if std::mem::discriminant(property) == std::mem::discriminant(&value) {
+                *property = value;
+            }
+        });
+    }
+
+    /// Calls the function with every value the target means, then writes them back
+    fn visit(&self, ray_tracer: &mut RayTracer, mut function: impl FnMut(&mut Value)) {
+        match self {
+            Target::Camera(CameraProperty::Fov) => visit_number(&mut ray_tracer.fov, &mut function),
+            Target::Camera(property) => {
+                let vector = match property {
+                    CameraProperty::Position => &mut ray_tracer.camera,
+                    _ => &mut ray_tracer.rotation,
+                };
+                visit_vector(vector, &mut function);
+            }
+            Target::Object { name, property } => {
+                let scene = &mut ray_tracer.scene;
+                let objects = scene
+                    .objects
+                    .iter_mut()
+                    .filter(|object| object.name == *name)
+                    .map(|object| &mut object.transform);
+                let instances = scene
+                    .instances
+                    .iter_mut()
+                    .filter(|instance| instance.name == *name)
+                    .map(|instance| &mut instance.transform);
+
+                for transform in objects.chain(instances) {
+                    let vector = match property {
+                        ObjectProperty::Position => &mut transform.position,
+                        ObjectProperty::Rotation => &mut transform.rotation,
+                        ObjectProperty::Scale => &mut transform.scale,
+                    };
+                    visit_vector(vector, &mut function);
+                }
+            }
+            Target::Light { index, property } => {
+                match (ray_tracer.scene.lights.get_mut(*index), property) {
+                    (
+                        Some(Light::Direction { intensity, .. } | Light::Point { intensity, .. }),
+                        LightProperty::Intensity,
+                    ) => visit_colour(intensity, &mut function),
+                    (Some(Light::Point { position, .. }), LightProperty::Position) => {
+                        visit_vector(position, &mut function)
+                    }
+                    (Some(Light::Direction { direction, .. }), LightProperty::Direction) => {
+                        visit_vector(direction, &mut function);
+                        *direction = direction.normalize();
+                    }
+                    _ => (),
+                }
+            }
+            Target::Material { name, property } => {
+                for material in named_materials(ray_tracer, name) {
+                    match property {
+                        MaterialProperty::Colour => {
+                            visit_colour(&mut material.colour, &mut function)
+                        }
+                        MaterialProperty::Emission => {
+                            visit_colour(&mut material.emission, &mut function)
+                        }
+                        MaterialProperty::Specular => {
+                            visit_number(&mut material.specular, &mut function)
+                        }
+                        MaterialProperty::Metallic => {
+                            visit_number(&mut material.metallic, &mut function)
+                        }
+                        MaterialProperty::Roughness => {
+                            visit_number(&mut material.roughness, &mut function)
+                        }
+                        MaterialProperty::EmissionStrength => {
+                            visit_number(&mut material.emission_strength, &mut function)
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn visit_number(number: &mut f64, function: &mut impl FnMut(&mut Value)) {
+    let mut value = Value::Number(*number);
+    function(&mut value);
+    if let Value::Number(new) = value {
+        *number = new;
+    }
+}
+
+fn visit_vector(vector: &mut Vec3, function: &mut impl FnMut(&mut Value)) {
+    let mut value = Value::Vector(*vector);
+    function(&mut value);
+    if let Value::Vector(new) = value {
+        *vector = new;
+    }
+}
+
+fn visit_colour(colour: &mut (f64, f64, f64), function: &mut impl FnMut(&mut Value)) {
+    let mut value = Value::Colour(*colour);
+    function(&mut value);
+    if let Value::Colour(new) = value {
+        *colour = new;
+    }
+}
+
+/// Keyframes for one property, kept in order of time
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Track {
+    pub target: Target,
+    /// sorted when they're loaded, as they don't have to be in order in the file
+    #[serde(deserialize_with = "deserialize_keyframes")]
+    pub keyframes: Vec<Keyframe>,
+}
+
+fn deserialize_keyframes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Keyframe>, D::Error> {
+    let mut keyframes = Vec::deserialize(deserializer)?;
+    sort_keyframes(&mut keyframes);
+
+    Ok(keyframes)
+}
+
+fn sort_keyframes(keyframes: &mut [Keyframe]) {
+    keyframes.sort_by(|a, b| {
+        a.time
+            .partial_cmp(&b.time)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+impl Track {
+    /// Adds a keyframe, or replaces the one already at that time
+    pub fn insert(&mut self, keyframe: Keyframe) {
+        match self
+            .keyframes
+            .iter_mut()
+            .find(|other| (other.time - keyframe.time).abs() < 1e-6)
+        {
+            Some(other) => other.value = keyframe.value,
+            None => self.keyframes.push(keyframe),
+        }
+        self.sort();
+    }
+
+    pub fn sort(&mut self) {
+        sort_keyframes(&mut self.keyframes);
+    }
+
+    /// Before the first keyframe and after the last, the value is held
+    pub fn value_at(&self, time: f64) -> Option<Value> {
+        let keys = &self.keyframes;
+        let next = keys.iter().position(|keyframe| keyframe.time > time);
+
+        let index = match next {
+            None => return keys.last().map(|keyframe| keyframe.value),
+            Some(0) => return Some(keys[0].value),
+            Some(next) => next - 1,
+        };
+
+        let (from, to) = (&keys[index], &keys[index + 1]);
+        let t = (time - from.time) / (to.time - from.time);
+
+        let eased = match from.curve {
+            Curve::Step => 0.,
+            Curve::Linear | Curve::Smooth => t,
+            Curve::EaseIn => t * t,
+            Curve::EaseOut => 1. - (1. - t) * (1. - t),
+            Curve::EaseInOut => t * t * (3. - 2. * t),
+        };
+
+        let (a, b) = (from.value.components(), to.value.components());
+        let components = if from.curve == Curve::Smooth {
+            // the ends carry on in a straight line
+            let before = index
+                .checked_sub(1)
+                .map_or(a, |before| keys[before].value.components());
+            let after = keys
+                .get(index + 2)
+                .map_or(b, |after| after.value.components());
+
+            [0, 1, 2].map(|i| catmull_rom(before[i], a[i], b[i], after[i], t))
+        } else {
+            [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * eased)
+        };
+
+        Some(from.value.with_components(components))
+    }
+}
+
+/// The point a fraction t of the way from b to c, on a curve that also passes through a and d
+pub fn catmull_rom(a: f64, b: f64, c: f64, d: f64, t: f64) -> f64 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    0.5 * (2. * b
+        + (c - a) * t
+        + (2. * a - 5. * b + 4. * c - d) * t2
+        + (3. * b - a - 3. * c + d) * t3)
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Animation {
+    /// in seconds, at least MIN_LENGTH
+    #[serde(deserialize_with = "deserialize_length")]
+    pub length: f64,
+    pub tracks: Vec<Track>,
+}
+
+impl Animation {
+    /// Shorter than this, or negative, and there's nothing to play
+    pub const MIN_LENGTH: f64 = 0.1;
+}
+
+fn deserialize_length<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    // max also turns NaN into the minimum
+    Ok(f64::deserialize(deserializer)?.max(Animation::MIN_LENGTH))
+}
+
+impl Default for Animation {
+    fn default() -> Animation {
+        Animation {
+            length: 10.,
+            tracks: vec![],
+        }
+    }
+}
+
+impl RayTracer {
+    /// Sets everything the scene's animation moves to how it is at a time
+    pub fn animate(&mut self, time: f64) {
+        let tracks = std::mem::take(&mut self.scene.animation.tracks);
+
+        for track in &tracks {
+            if let Some(value) = track.value_at(time) {
+                track.target.set(self, value);
+            }
+        }
+
+        self.scene.animation.tracks = tracks;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_tracer::{Scene, SceneFormat};
+
+    fn animation(text: &str) -> Animation {
+        let text = format!(r#"{{ "animation": {} }}"#, text);
+        Scene::from_text(&text, SceneFormat::Json)
+            .unwrap()
+            .animation
+    }
+
+    #[test]
+    fn keyframes_are_sorted_when_loaded() {
+        let animation = animation(
+            r#"{
+                "length": 4,
+                "tracks": [{
+                    "target": { "Light": { "index": 0, "property": "Intensity" } },
+                    "keyframes": [
+                        { "time": 2, "value": { "Number": 2 }, "curve": "Linear" },
+                        { "time": 0, "value": { "Number": 0 }, "curve": "Linear" },
+                        { "time": 1, "value": { "Number": 1 }, "curve": "Linear" }
+                    ]
+                }]
+            }"#,
+        );
+        let track = &animation.tracks[0];
+
+        let times: Vec<f64> = track
+            .keyframes
+            .iter()
+            .map(|keyframe| keyframe.time)
+            .collect();
+        assert_eq!(times, [0., 1., 2.]);
+        assert_eq!(track.value_at(1.5), Some(Value::Number(1.5)));
+    }
+
+    #[test]
+    fn length_is_at_least_the_minimum() {
+        for length in ["-3", "0"] {
+            let animation = animation(&format!(r#"{{ "length": {}, "tracks": [] }}"#, length));
+            assert_eq!(animation.length, Animation::MIN_LENGTH);
+        }
+
+        assert_eq!(animation(r#"{ "length": 5, "tracks": [] }"#).length, 5.);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::ray_tracer::{Surface, Vec3};
+
+/// Below this, highlights from point lights get too small to ever be seen
+const MIN_ROUGHNESS: f64 = 0.03;
+
+/// The GGX / Trowbridge-Reitz normal distribution,
+/// how many microfacets face along the half vector
+pub fn ggx_distribution(n_dot_h: f64, roughness: f64) -> f64 {
+    let alpha_squared = roughness.max(MIN_ROUGHNESS).powi(4);
+    let denominator = n_dot_h.powi(2) * (alpha_squared - 1.) + 1.;
+
+    alpha_squared / (std::f64::consts::PI * denominator.powi(2))
+}
+
+/// Smith's masking-shadowing with the Schlick-GGX approximation,
+/// how many microfacets can be seen from both directions
+pub fn smith_geometry(n_dot_v: f64, n_dot_l: f64, roughness: f64) -> f64 {
+    // the remapping for direct lights from UE4
+    let k = (roughness.max(MIN_ROUGHNESS) + 1.).powi(2) / 8.;
+    let schlick_ggx = |n_dot_x: f64| n_dot_x / (n_dot_x * (1. - k) + k);
+
+    schlick_ggx(n_dot_v) * schlick_ggx(n_dot_l)
+}
+
+/// How much light is reflected at an angle, going to white at grazing angles
+pub fn schlick_fresnel(f0: (f64, f64, f64), cos_theta: f64) -> (f64, f64, f64) {
+    let weight = (1. - cos_theta.clamp(0., 1.)).powi(5);
+
+    (
+        f0.0 + (1. - f0.0) * weight,
+        f0.1 + (1. - f0.1) * weight,
+        f0.2 + (1. - f0.2) * weight,
+    )
+}
+
+/// The reflectance straight on, dielectrics are all about 4% and metals are their colour
+pub fn base_reflectance(surface: &Surface) -> (f64, f64, f64) {
+    let lerp = |a: f64, b: f64| a + (b - a) * surface.metallic;
+
+    (
+        lerp(0.04, surface.colour.0),
+        lerp(0.04, surface.colour.1),
+        lerp(0.04, surface.colour.2),
+    )
+}
+
+/// Lambert diffuse plus a Cook-Torrance specular lobe,
+/// already multiplied by n·l and scaled so a white diffuse surface matches the phong model.
+/// All the directions point away from the surface.
+pub fn cook_torrance(
+    surface: &Surface,
+    normal: Vec3,
+    to_view: Vec3,
+    to_light: Vec3,
+) -> (f64, f64, f64) {
+    let n_dot_l = normal.dot(to_light);
+    let n_dot_v = normal.dot(to_view);
+
+    if n_dot_l <= 0. || n_dot_v <= 0. {
+        return (0., 0., 0.);
+    }
+
+    let half = (to_view + to_light).normalize();
+    let n_dot_h = normal.dot(half).max(0.);
+
+    let fresnel = schlick_fresnel(base_reflectance(surface), to_view.dot(half));
+    let specular = ggx_distribution(n_dot_h, surface.roughness)
+        * smith_geometry(n_dot_v, n_dot_l, surface.roughness)
+        / (4. * n_dot_v * n_dot_l);
+
+    // whatever isn't reflected is diffused, apart from in metals which absorb it
+    let diffuse = |fresnel: f64, colour: f64| (1. - fresnel) * (1. - surface.metallic) * colour;
+
+    // the brdfs are per steradian, so pi brings them back to the same scale as the lights
+    (
+        (diffuse(fresnel.0, surface.colour.0) + std::f64::consts::PI * specular * fresnel.0)
+            * n_dot_l,
+        (diffuse(fresnel.1, surface.colour.1) + std::f64::consts::PI * specular * fresnel.1)
+            * n_dot_l,
+        (diffuse(fresnel.2, surface.colour.2) + std::f64::consts::PI * specular * fresnel.2)
+            * n_dot_l,
+    )
+}
+
+/// How much of the environment is reflected, like schlick_fresnel
+/// but rough surfaces don't go all the way to white
+pub fn environment_reflectance(surface: &Surface, n_dot_v: f64) -> (f64, f64, f64) {
+    let f0 = base_reflectance(surface);
+    let weight = (1. - n_dot_v.clamp(0., 1.)).powi(5);
+    let edge = |f0: f64| (1. - surface.roughness).max(f0);
+
+    (
+        f0.0 + (edge(f0.0) - f0.0) * weight,
+        f0.1 + (edge(f0.1) - f0.1) * weight,
+        f0.2 + (edge(f0.2) - f0.2) * weight,
+    )
+}
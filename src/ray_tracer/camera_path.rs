@@ -0,0 +1,178 @@
+use crate::ray_tracer::{Animation, CameraProperty, Curve, Keyframe, Target, Track, Value, Vec3};
+
+/// Records the camera as it's flown around, as smooth keyframes in the animation's camera tracks.
+/// They're played back like any other track, and the spline smooths out the flying.
+#[derive(Debug, Clone)]
+pub struct CameraRecorder {
+    /// seconds between keyframes, longer is smoother but follows the flight less closely
+    pub interval: f64,
+    /// when the next keyframe is due
+    next_time: f64,
+    last_rotation: Option<Vec3>,
+}
+
+impl CameraRecorder {
+    /// Starts recording over any camera keyframes from a time onwards
+    pub fn start(animation: &mut Animation, time: f64, interval: f64) -> CameraRecorder {
+        for property in [CameraProperty::Position, CameraProperty::Rotation] {
+            camera_track(animation, property)
+                .keyframes
+                .retain(|keyframe| keyframe.time < time);
+        }
+
+        CameraRecorder {
+            interval,
+            next_time: time,
+            last_rotation: None,
+        }
+    }
+
+    /// Keyframes the camera if it's been long enough since the last one,
+    /// making the animation longer if it has to
+    pub fn record(&mut self, animation: &mut Animation, time: f64, camera: Vec3, rotation: Vec3) {
+        // every frame, not just on keyframes, or the time is held back at the end
+        animation.length = animation.length.max(time);
+
+        if time >= self.next_time {
+            self.keyframe(animation, time, camera, rotation);
+            self.next_time = time + self.interval;
+        }
+    }
+
+    /// Keyframes where the camera ends up
+    pub fn stop(mut self, animation: &mut Animation, time: f64, camera: Vec3, rotation: Vec3) {
+        self.keyframe(animation, time, camera, rotation);
+    }
+
+    fn keyframe(&mut self, animation: &mut Animation, time: f64, camera: Vec3, rotation: Vec3) {
+        // turning is wrapped to a full turn, which would spin the long way round between keyframes
+        let mut rotation = rotation;
+        if let Some(last) = self.last_rotation {
+            let turn = 2. * std::f64::consts::PI;
+            rotation.y += turn * ((last.y - rotation.y) / turn).round();
+        }
+        self.last_rotation = Some(rotation);
+
+        animation.length = animation.length.max(time);
+
+        for (property, value) in [
+            (CameraProperty::Position, camera),
+            (CameraProperty::Rotation, rotation),
+        ] {
+            camera_track(animation, property).insert(Keyframe {
+                time,
+                value: Value::Vector(value),
+                curve: Curve::Smooth,
+            });
+        }
+    }
+}
+
+/// The first track for a camera property, added if there isn't one
+fn camera_track(animation: &mut Animation, property: CameraProperty) -> &mut Track {
+    let target = Target::Camera(property);
+
+    let index = match animation
+        .tracks
+        .iter()
+        .position(|track| track.target == target)
+    {
+        Some(index) => index,
+        None => {
+            animation.tracks.push(Track {
+                target,
+                keyframes: vec![],
+            });
+            animation.tracks.len() - 1
+        }
+    };
+
+    &mut animation.tracks[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec3(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn keyframes(animation: &Animation, property: CameraProperty) -> Vec<(f64, Vec3)> {
+        let target = Target::Camera(property);
+        let track = animation
+            .tracks
+            .iter()
+            .find(|track| track.target == target)
+            .unwrap();
+
+        track
+            .keyframes
+            .iter()
+            .map(|keyframe| match keyframe.value {
+                Value::Vector(value) => (keyframe.time, value),
+                ref value => panic!("expected a vector, got {:?}", value),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn starting_removes_later_keyframes() {
+        let mut animation = Animation::default();
+        let origin = vec3(0., 0., 0.);
+        let mut recorder = CameraRecorder::start(&mut animation, 0., 1.);
+        for time in [0., 1., 2., 3.] {
+            recorder.record(&mut animation, time, vec3(time, 0., 0.), origin);
+        }
+
+        CameraRecorder::start(&mut animation, 2., 1.);
+
+        for property in [CameraProperty::Position, CameraProperty::Rotation] {
+            let times: Vec<f64> = keyframes(&animation, property)
+                .iter()
+                .map(|(time, _)| *time)
+                .collect();
+            assert_eq!(times, vec![0., 1.]);
+        }
+    }
+
+    #[test]
+    fn turning_goes_the_short_way_round() {
+        let mut animation = Animation::default();
+        let origin = vec3(0., 0., 0.);
+        let turn = 2. * std::f64::consts::PI;
+        let mut recorder = CameraRecorder::start(&mut animation, 0., 1.);
+
+        // just short of a full turn, then just past it, wrapped back to near 0
+        recorder.record(&mut animation, 0., origin, vec3(0., turn - 0.1, 0.));
+        recorder.record(&mut animation, 1., origin, vec3(0., 0.1, 0.));
+        recorder.stop(&mut animation, 2., origin, vec3(0., turn - 0.1, 0.));
+
+        let turns: Vec<f64> = keyframes(&animation, CameraProperty::Rotation)
+            .iter()
+            .map(|(_, rotation)| rotation.y)
+            .collect();
+        assert!((turns[0] - (turn - 0.1)).abs() < 1e-9);
+        assert!((turns[1] - (turn + 0.1)).abs() < 1e-9);
+        assert!((turns[2] - (turn - 0.1)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn recording_past_the_end_makes_the_animation_longer() {
+        let mut animation = Animation::default();
+        let origin = vec3(0., 0., 0.);
+        let mut recorder = CameraRecorder::start(&mut animation, 9.9, 0.25);
+
+        // a frame at a time, clamped to the length afterwards like the timeline does
+        let mut time: f64 = 9.9;
+        for _ in 0..60 {
+            time += 1. / 60.;
+            recorder.record(&mut animation, time, origin, origin);
+            time = time.clamp(0., animation.length);
+        }
+
+        assert!(time > 10.8, "stuck at {}", time);
+        assert!(animation.length >= time);
+        assert!(keyframes(&animation, CameraProperty::Position).len() >= 4);
+    }
+}
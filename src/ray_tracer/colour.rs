@@ -0,0 +1,167 @@
+use std::fmt;
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::Deserializer;
+
+/// Reads a colour written as "#rrggbb", "#rgb" or "rgb(255, 128, 0)"
+pub fn parse_colour(text: &str) -> Result<(f64, f64, f64), String> {
+    let text = text.trim();
+    let error = || format!("\"{}\" isn't a colour", text);
+
+    if let Some(hex) = text.strip_prefix('#') {
+        let digits = hex
+            .chars()
+            .map(|digit| digit.to_digit(16).map(|digit| digit as f64))
+            .collect::<Option<Vec<f64>>>()
+            .ok_or_else(error)?;
+
+        return match digits[..] {
+            [r, g, b] => Ok((r / 15., g / 15., b / 15.)),
+            [r1, r2, g1, g2, b1, b2] => Ok((
+                (r1 * 16. + r2) / 255.,
+                (g1 * 16. + g2) / 255.,
+                (b1 * 16. + b2) / 255.,
+            )),
+            _ => Err(error()),
+        };
+    }
+
+    let values = text
+        .strip_prefix("rgb(")
+        .and_then(|text| text.strip_suffix(')'))
+        .ok_or_else(error)?
+        .split(',')
+        .map(|value| value.trim().parse::<f64>().map(|value| value / 255.))
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|_| error())?;
+
+    match values[..] {
+        [r, g, b] => Ok((r, g, b)),
+        _ => Err(error()),
+    }
+}
+
+struct ColourVisitor;
+
+impl<'de> Visitor<'de> for ColourVisitor {
+    type Value = (f64, f64, f64);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "3 numbers from 0 to 1, \"#rrggbb\" or \"rgb(r, g, b)\"")
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<Self::Value, E> {
+        parse_colour(text).map_err(E::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut next = |index| {
+            seq.next_element::<f64>()?
+                .ok_or_else(|| de::Error::invalid_length(index, &self))
+        };
+        let colour = (next(0)?, next(1)?, next(2)?);
+
+        if seq.next_element::<de::IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(4, &self));
+        }
+
+        // whole numbers are plain numbers too, so [2, 2, 2] is a light twice as bright
+        Ok(colour)
+    }
+}
+
+/// For colour fields in scene files, which can be 3 numbers like before or a string
+pub fn deserialize_colour<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<(f64, f64, f64), D::Error> {
+    deserializer.deserialize_any(ColourVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    use crate::ray_tracer::Light;
+
+    #[derive(Debug, Deserialize)]
+    struct Colour(#[serde(deserialize_with = "deserialize_colour")] (f64, f64, f64));
+
+    fn colour(json: &str) -> Result<(f64, f64, f64), String> {
+        serde_json::from_str::<Colour>(json)
+            .map(|colour| colour.0)
+            .map_err(|error| error.to_string())
+    }
+
+    #[test]
+    fn arrays_are_0_to_1() {
+        assert_eq!(colour("[1, 0.5, 0]"), Ok((1., 0.5, 0.)));
+        assert_eq!(colour("[1, 0, 1]"), Ok((1., 0., 1.)));
+        // brighter than 1 for lights
+        assert_eq!(colour("[2.0, 2.5, 3.0]"), Ok((2., 2.5, 3.)));
+    }
+
+    #[test]
+    fn whole_numbers_are_not_0_to_255() {
+        assert_eq!(colour("[2, 2, 2]"), Ok((2., 2., 2.)));
+        assert_eq!(colour("[255, 128, 0]"), Ok((255., 128., 0.)));
+    }
+
+    #[test]
+    fn whole_number_light_intensities_load_from_toml() {
+        let text = "[Point]\nintensity = [2, 2, 2]\nposition = { x = 0, y = 0, z = 0 }\n";
+        match toml::from_str(text).unwrap() {
+            Light::Point { intensity, .. } => assert_eq!(intensity, (2., 2., 2.)),
+            light => panic!("expected a point light, got {:?}", light),
+        }
+    }
+
+    #[test]
+    fn arrays_need_3_numbers() {
+        assert!(colour("[1, 0]").is_err());
+        assert!(colour("[1, 0, 0, 1]").is_err());
+        assert!(colour(r#"[1, "0", 0]"#).is_err());
+    }
+
+    #[test]
+    fn hex_strings() {
+        assert_eq!(parse_colour("#f00"), Ok((1., 0., 0.)));
+        assert_eq!(parse_colour("#ff8000"), Ok((1., 128. / 255., 0.)));
+        assert_eq!(parse_colour("#FFFFFF"), Ok((1., 1., 1.)));
+        assert_eq!(parse_colour("  #000 "), Ok((0., 0., 0.)));
+    }
+
+    #[test]
+    fn rgb_strings() {
+        assert_eq!(parse_colour("rgb(255, 128, 0)"), Ok((1., 128. / 255., 0.)));
+        assert_eq!(parse_colour(" rgb(0,0,255) "), Ok((0., 0., 1.)));
+        // brighter than 255 for lights
+        assert_eq!(parse_colour("rgb(510, 0, 0)"), Ok((2., 0., 0.)));
+    }
+
+    #[test]
+    fn malformed_strings_are_rejected() {
+        for text in [
+            "",
+            "#",
+            "#ff00",
+            "#ff00000",
+            "#gg0000",
+            "rgb(1, 2)",
+            "rgb(1, 2, 3, 4)",
+            "rgb(a, b, c)",
+            "rgb(1, 2, 3",
+            "rgb 1, 2, 3",
+            "red",
+        ] {
+            assert!(parse_colour(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn strings_are_parsed_when_deserializing() {
+        assert_eq!(colour(r##""#f00""##), Ok((1., 0., 0.)));
+        let error = colour(r#""red""#).unwrap_err();
+        assert!(error.contains("\"red\" isn't a colour"), "{}", error);
+    }
+}
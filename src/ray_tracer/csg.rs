@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+
+/// A stretch along a ray that's inside a solid, as (entry, exit) distances
+pub type Span = (f64, f64);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    /// the left geometry with the right one cut out of it
+    Difference,
+}
+
+impl CsgOperation {
+    pub fn contains(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+
+    /// Both lists of spans have to be sorted and not overlap themselves
+    pub fn combine(&self, left: &[Span], right: &[Span]) -> Vec<Span> {
+        // (distance, is from left, is entering)
+        let mut events: Vec<(f64, bool, bool)> = Vec::with_capacity((left.len() + right.len()) * 2);
+
+        for (spans, is_left) in [(left, true), (right, false)] {
+            for (entry, exit) in spans {
+                events.push((*entry, is_left, true));
+                events.push((*exit, is_left, false));
+            }
+        }
+
+        events.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut spans = vec![];
+        let mut in_left = false;
+        let mut in_right = false;
+        let mut entry = 0.;
+
+        for (distance, is_left, is_entering) in events {
+            let was_inside = self.contains(in_left, in_right);
+
+            if is_left {
+                in_left = is_entering;
+            } else {
+                in_right = is_entering;
+            }
+
+            match (was_inside, self.contains(in_left, in_right)) {
+                (false, true) => entry = distance,
+                (true, false) => spans.push((entry, distance)),
+                _ => (),
+            }
+        }
+
+        spans
+    }
+
+    /// The same operation on signed distances
+    pub fn combine_distances(&self, left: f64, right: f64) -> f64 {
+        match self {
+            CsgOperation::Union => left.min(right),
+            CsgOperation::Intersection => left.max(right),
+            CsgOperation::Difference => left.max(-right),
+        }
+    }
+}
+
+/// Turns every point a ray crosses a closed surface into spans
+pub fn pair_up(mut distances: Vec<f64>) -> Vec<Span> {
+    distances.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    // an odd one out is from a grazing hit, so it's safe to drop
+    distances
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two overlapping spans and one off on its own
+    const LEFT: [Span; 2] = [(1., 4.), (8., 9.)];
+    const RIGHT: [Span; 1] = [(3., 6.)];
+
+    #[test]
+    fn union() {
+        assert_eq!(
+            CsgOperation::Union.combine(&LEFT, &RIGHT),
+            [(1., 6.), (8., 9.)]
+        );
+    }
+
+    #[test]
+    fn intersection() {
+        assert_eq!(
+            CsgOperation::Intersection.combine(&LEFT, &RIGHT),
+            [(3., 4.)]
+        );
+    }
+
+    #[test]
+    fn difference() {
+        assert_eq!(
+            CsgOperation::Difference.combine(&LEFT, &RIGHT),
+            [(1., 3.), (8., 9.)]
+        );
+        // cutting out the whole of the left leaves nothing
+        assert!(CsgOperation::Difference
+            .combine(&[(2., 3.)], &[(1., 4.)])
+            .is_empty());
+    }
+
+    #[test]
+    fn empty_sides() {
+        assert_eq!(CsgOperation::Union.combine(&LEFT, &[]), LEFT);
+        assert!(CsgOperation::Intersection.combine(&LEFT, &[]).is_empty());
+        assert_eq!(CsgOperation::Difference.combine(&LEFT, &[]), LEFT);
+        assert!(CsgOperation::Difference.combine(&[], &RIGHT).is_empty());
+    }
+
+    #[test]
+    fn spans_that_only_touch() {
+        // the union of two spans meeting at a point is one span, or two with nothing between them
+        let spans = CsgOperation::Union.combine(&[(1., 2.)], &[(2., 3.)]);
+        assert_eq!(spans.first().map(|span| span.0), Some(1.));
+        assert_eq!(spans.last().map(|span| span.1), Some(3.));
+    }
+
+    #[test]
+    fn pairs_up_sorted_crossings() {
+        assert_eq!(pair_up(vec![5., 1., 3., 2.]), [(1., 2.), (3., 5.)]);
+    }
+
+    #[test]
+    fn drops_an_odd_crossing() {
+        assert_eq!(pair_up(vec![3., 1., 2.]), [(1., 2.)]);
+        assert!(pair_up(vec![1.]).is_empty());
+        assert!(pair_up(vec![]).is_empty());
+    }
+
+    #[test]
+    fn nan_crossings_dont_panic() {
+        assert_eq!(pair_up(vec![f64::NAN, 1.]).len(), 1);
+    }
+}
@@ -0,0 +1,656 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use serde_json::json;
+
+use crate::ray_tracer::{
+    Geometry, Light, Material, MaterialModel, MaterialRef, MeshData, RayTracer, Scene, Transform,
+    Vec3,
+};
+
+/// The formats a scene can be exported as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Obj,
+    Gltf,
+}
+
+impl ExportFormat {
+    /// The format to write a file in, from its extension
+    pub fn from_path(path: &Path) -> std::io::Result<ExportFormat> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match extension.as_str() {
+            "obj" => Ok(ExportFormat::Obj),
+            "gltf" => Ok(ExportFormat::Gltf),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("can't export as \"{}\", only .obj and .gltf", extension),
+            )),
+        }
+    }
+}
+
+/// Writes the scene in the format its extension asks for
+pub fn export_scene(ray_tracer: &RayTracer, path: &str, segments: usize) -> std::io::Result<()> {
+    match ExportFormat::from_path(Path::new(path))? {
+        ExportFormat::Obj => export_obj(&ray_tracer.scene, path, segments),
+        ExportFormat::Gltf => export_gltf(ray_tracer, path, segments),
+    }
+}
+
+/// An object or instance as triangles in world space, ready to be written out
+struct ExportMesh {
+    name: String,
+    data: MeshData,
+    /// an index into the materials
+    material: usize,
+}
+
+/// Everything in the scene that can be drawn, with the materials they use.
+/// Library materials keep their names, and the others are named after their objects.
+fn collect(scene: &Scene, segments: usize) -> (Vec<ExportMesh>, Vec<(String, Material)>) {
+    let mut meshes = vec![];
+    let mut materials: Vec<(String, Material)> = vec![];
+
+    let mut add =
+        |name: &str, geometry: &Geometry, transform: &Transform, material: &MaterialRef| {
+            let mut data = geometry.tessellate(segments);
+            if data.triangles.is_empty() {
+                return;
+            }
+
+            let matrix = transform.matrix();
+            for position in &mut data.positions {
+                *position = position.transform_point(matrix);
+            }
+            for normal in &mut data.normals {
+                *normal = transform.normal_to_world(*normal);
+            }
+            // a mirrored transform turns the triangles inside out
+            let scale = transform.scale;
+            if scale.x * scale.y * scale.z < 0. {
+                for triangle in &mut data.triangles {
+                    triangle.swap(1, 2);
+                }
+            }
+
+            let material_name = match material {
+                MaterialRef::Library(name) => name.clone(),
+                MaterialRef::Own(_) => format!("{} material", name),
+            };
+            let index = match materials
+                .iter()
+                .position(|(name, _)| *name == material_name)
+            {
+                Some(index) => index,
+                None => {
+                    materials.push((material_name, scene.material(material).clone()));
+                    materials.len() - 1
+                }
+            };
+
+            meshes.push(ExportMesh {
+                name: name.to_string(),
+                data,
+                material: index,
+            });
+        };
+
+    for object in &scene.objects {
+        add(
+            &object.name,
+            &object.geometry,
+            &object.transform,
+            &object.material,
+        );
+    }
+
+    for instance in &scene.instances {
+        if let Some(prototype) = scene
+            .prototypes
+            .iter()
+            .find(|prototype| prototype.name == instance.prototype)
+        {
+            add(
+                &instance.name,
+                &prototype.geometry,
+                &instance.transform,
+                instance.material(prototype),
+            );
+        }
+    }
+
+    (meshes, materials)
+}
+
+/// Names in OBJ and MTL files can't have spaces
+fn obj_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// The phong exponent that gives about the same highlight as a roughness
+fn roughness_to_exponent(roughness: f64) -> f64 {
+    let alpha = roughness.max(0.01).powi(2);
+    (2. / alpha.powi(2) - 2.).clamp(0., 1000.)
+}
+
+/// The opposite of roughness_to_exponent
+fn exponent_to_roughness(exponent: f64) -> f64 {
+    (2. / (exponent.max(0.) + 2.)).powf(0.25)
+}
+
+/// The emission as a colour from 0 to 1, as neither format can go brighter
+fn emission(material: &Material) -> (f64, f64, f64) {
+    let strength = material.emission_strength;
+    (
+        (material.emission.0 * strength).clamp(0., 1.),
+        (material.emission.1 * strength).clamp(0., 1.),
+        (material.emission.2 * strength).clamp(0., 1.),
+    )
+}
+
+/// Writes the scene as an .obj file, with its materials in an .mtl file next to it.
+/// Round shapes are split into this many segments.
+pub fn export_obj(scene: &Scene, path: &str, segments: usize) -> std::io::Result<()> {
+    let (meshes, materials) = collect(scene, segments);
+    let mtl_path = Path::new(path).with_extension("mtl");
+
+    // writing to a string can't fail
+    let mut obj = String::new();
+    writeln!(obj, "# exported from RSRayTracingV2").unwrap();
+    if let Some(file_name) = mtl_path.file_name() {
+        writeln!(obj, "mtllib {}", file_name.to_string_lossy()).unwrap();
+    }
+
+    // indices in obj files start at 1, and count across the whole file
+    let (mut positions_so_far, mut uvs_so_far) = (1, 1);
+
+    for mesh in &meshes {
+        let data = &mesh.data;
+
+        writeln!(obj, "o {}", obj_name(&mesh.name)).unwrap();
+        writeln!(obj, "usemtl {}", obj_name(&materials[mesh.material].0)).unwrap();
+
+        for position in &data.positions {
+            writeln!(obj, "v {} {} {}", position.x, position.y, position.z).unwrap();
+        }
+        for normal in &data.normals {
+            writeln!(obj, "vn {} {} {}", normal.x, normal.y, normal.z).unwrap();
+        }
+        for (u, v) in &data.uvs {
+            writeln!(obj, "vt {} {}", u, v).unwrap();
+        }
+
+        for triangle in &data.triangles {
+            let corners: Vec<String> = triangle
+                .iter()
+                .map(|index| {
+                    let position = positions_so_far + index;
+                    if data.uvs.is_empty() {
+                        format!("{}//{}", position, position)
+                    } else {
+                        format!("{}/{}/{}", position, uvs_so_far + index, position)
+                    }
+                })
+                .collect();
+            writeln!(obj, "f {}", corners.join(" ")).unwrap();
+        }
+
+        // every mesh has normals, so they're numbered the same as the positions
+        positions_so_far += data.positions.len();
+        uvs_so_far += data.uvs.len();
+    }
+
+    let mut mtl = String::new();
+    for (name, material) in &materials {
+        let (red, green, blue) = material.colour;
+        let metallic = material.metallic;
+
+        writeln!(mtl, "newmtl {}", obj_name(name)).unwrap();
+        match material.model {
+            // reflections are mixed in by metallic without being tinted
+            MaterialModel::Phong => {
+                writeln!(mtl, "Kd {} {} {}", red, green, blue).unwrap();
+                writeln!(mtl, "Ks {} {} {}", metallic, metallic, metallic).unwrap();
+                writeln!(mtl, "Ns {}", material.specular.clamp(0., 1000.)).unwrap();
+            }
+            // metals reflect in their own colour, and everything else a little in white
+            MaterialModel::Pbr => {
+                let specular = |colour: f64| 0.04 + (colour - 0.04) * metallic;
+                writeln!(mtl, "Kd {} {} {}", red, green, blue).unwrap();
+                writeln!(
+                    mtl,
+                    "Ks {} {} {}",
+                    specular(red),
+                    specular(green),
+                    specular(blue)
+                )
+                .unwrap();
+                writeln!(mtl, "Ns {}", roughness_to_exponent(material.roughness)).unwrap();
+            }
+        }
+
+        let (emission_red, emission_green, emission_blue) = emission(material);
+        writeln!(
+            mtl,
+            "Ke {} {} {}",
+            emission_red, emission_green, emission_blue
+        )
+        .unwrap();
+
+        // the pbr extension most tools read
+        let roughness = match material.model {
+            MaterialModel::Phong => exponent_to_roughness(material.specular),
+            MaterialModel::Pbr => material.roughness,
+        };
+        writeln!(mtl, "Pr {}", roughness).unwrap();
+        writeln!(mtl, "Pm {}", metallic).unwrap();
+        writeln!(mtl, "illum {}", if metallic > 0. { 3 } else { 2 }).unwrap();
+        writeln!(mtl).unwrap();
+    }
+
+    std::fs::write(path, obj)?;
+    std::fs::write(mtl_path, mtl)
+}
+
+/// The rotation that turns -z into a direction, as a quaternion
+fn rotation_from_forward(direction: Vec3) -> [f64; 4] {
+    let forward = Vec3 {
+        x: 0.,
+        y: 0.,
+        z: -1.,
+    };
+    let direction = direction.normalize();
+    let cos = forward.dot(direction);
+
+    // pointing straight back, so any half turn will do
+    if cos < -0.999_999 {
+        return [0., 1., 0., 0.];
+    }
+
+    let axis = forward * direction;
+    let length = (axis.dot(axis) + (1. + cos).powi(2)).sqrt();
+    [
+        axis.x / length,
+        axis.y / length,
+        axis.z / length,
+        (1. + cos) / length,
+    ]
+}
+
+/// The binary data of a glTF file, and the views and accessors that describe it.
+/// Every value is 4 bytes, so everything stays aligned.
+#[derive(Default)]
+struct GltfBuffer {
+    bytes: Vec<u8>,
+    views: Vec<serde_json::Value>,
+    accessors: Vec<serde_json::Value>,
+}
+
+impl GltfBuffer {
+    /// Returns the index of the new accessor
+    fn add(&mut self, bytes: Vec<u8>, target: u32, mut accessor: serde_json::Value) -> usize {
+        self.views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.bytes.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        }));
+        self.bytes.extend(bytes);
+
+        accessor["bufferView"] = json!(self.views.len() - 1);
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn floats(&mut self, values: Vec<f32>, kind: &str, components: usize, bounds: bool) -> usize {
+        let mut accessor = json!({
+            "componentType": 5126,
+            "count": values.len() / components,
+            "type": kind,
+        });
+
+        // positions have to say how far they reach
+        if bounds {
+            let (mut min, mut max) = (vec![f32::INFINITY; 3], vec![f32::NEG_INFINITY; 3]);
+            for vertex in values.chunks_exact(3) {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(vertex[axis]);
+                    max[axis] = max[axis].max(vertex[axis]);
+                }
+            }
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+
+        let bytes = values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        self.add(bytes, 34962, accessor)
+    }
+
+    fn indices(&mut self, triangles: &[[usize; 3]]) -> usize {
+        let bytes = triangles
+            .iter()
+            .flatten()
+            .flat_map(|index| (*index as u32).to_le_bytes())
+            .collect();
+
+        self.add(
+            bytes,
+            34963,
+            json!({
+                "componentType": 5125,
+                "count": triangles.len() * 3,
+                "type": "SCALAR",
+            }),
+        )
+    }
+}
+
+/// Writes the scene as a .gltf file, with its data in a .bin file next to it.
+/// Round shapes are split into this many segments,
+/// and the camera and lights are included.
+pub fn export_gltf(ray_tracer: &RayTracer, path: &str, segments: usize) -> std::io::Result<()> {
+    let scene = &ray_tracer.scene;
+    let (meshes, materials) = collect(scene, segments);
+    let bin_path = Path::new(path).with_extension("bin");
+
+    let mut buffer = GltfBuffer::default();
+
+    let mut gltf_meshes = vec![];
+    let mut nodes = vec![];
+
+    for mesh in &meshes {
+        let data = &mesh.data;
+        let vec3s = |values: &[Vec3]| {
+            values
+                .iter()
+                .flat_map(|value| [value.x as f32, value.y as f32, value.z as f32])
+                .collect()
+        };
+
+        let mut attributes = json!({
+            "POSITION": buffer.floats(vec3s(&data.positions), "VEC3", 3, true),
+            "NORMAL": buffer.floats(vec3s(&data.normals), "VEC3", 3, false),
+        });
+        // glTF's v goes down the image
+        if !data.uvs.is_empty() {
+            let uvs = data
+                .uvs
+                .iter()
+                .flat_map(|(u, v)| [*u as f32, 1. - *v as f32])
+                .collect();
+            attributes["TEXCOORD_0"] = json!(buffer.floats(uvs, "VEC2", 2, false));
+        }
+        if !data.colours.is_empty() {
+            let colours = data
+                .colours
+                .iter()
+                .flat_map(|(red, green, blue)| [*red as f32, *green as f32, *blue as f32])
+                .collect();
+            attributes["COLOR_0"] = json!(buffer.floats(colours, "VEC3", 3, false));
+        }
+
+        gltf_meshes.push(json!({
+            "name": mesh.name,
+            "primitives": [{
+                "attributes": attributes,
+                "indices": buffer.indices(&data.triangles),
+                "material": mesh.material,
+            }],
+        }));
+        nodes.push(json!({
+            "name": mesh.name,
+            "mesh": gltf_meshes.len() - 1,
+        }));
+    }
+
+    let gltf_materials: Vec<_> = materials
+        .iter()
+        .map(|(name, material)| {
+            let roughness = match material.model {
+                MaterialModel::Phong => exponent_to_roughness(material.specular),
+                MaterialModel::Pbr => material.roughness,
+            };
+            let (red, green, blue) = material.colour;
+            let (emission_red, emission_green, emission_blue) = emission(material);
+
+            json!({
+                "name": name,
+                "pbrMetallicRoughness": {
+                    "baseColorFactor": [red, green, blue, 1.],
+                    "metallicFactor": material.metallic.clamp(0., 1.),
+                    "roughnessFactor": roughness.clamp(0., 1.),
+                },
+                "emissiveFactor": [emission_red, emission_green, emission_blue],
+            })
+        })
+        .collect();
+
+    // the brightest channel becomes the intensity, so the colour stays between 0 and 1
+    let lights: Vec<_> = scene
+        .lights
+        .iter()
+        .map(|light| {
+            let (intensity, kind) = match light {
+                Light::Direction {
+                    intensity,
+                    direction: _,
+                } => (intensity, "directional"),
+                Light::Point {
+                    intensity,
+                    position: _,
+                } => (intensity, "point"),
+            };
+            let strength = intensity.0.max(intensity.1).max(intensity.2).max(1e-9);
+
+            json!({
+                "type": kind,
+                "color": [intensity.0 / strength, intensity.1 / strength, intensity.2 / strength],
+                "intensity": strength,
+            })
+        })
+        .collect();
+
+    for (index, light) in scene.lights.iter().enumerate() {
+        let mut node = json!({
+            "name": format!("light {}", index),
+            "extensions": { "KHR_lights_punctual": { "light": index } },
+        });
+        match light {
+            Light::Direction {
+                intensity: _,
+                direction,
+            } => node["rotation"] = json!(rotation_from_forward(*direction)),
+            Light::Point {
+                intensity: _,
+                position,
+            } => node["translation"] = json!([position.x, position.y, position.z]),
+        }
+        nodes.push(node);
+    }
+
+    // the camera looks down its -z, which is the ray tracer's -forward
+    let z_axis = ray_tracer.forward().normalize();
+    let x_axis = (ray_tracer.up() * z_axis).normalize();
+    let y_axis = z_axis * x_axis;
+    let position = ray_tracer.camera;
+    let aspect_ratio = ray_tracer.width as f64 / ray_tracer.height.max(1) as f64;
+    let horizontal_fov = ray_tracer.fov.to_radians();
+
+    nodes.push(json!({
+        "name": "camera",
+        "camera": 0,
+        "matrix": [
+            x_axis.x, x_axis.y, x_axis.z, 0.,
+            y_axis.x, y_axis.y, y_axis.z, 0.,
+            z_axis.x, z_axis.y, z_axis.z, 0.,
+            position.x, position.y, position.z, 1.,
+        ],
+    }));
+
+    let mut gltf = json!({
+        "asset": { "version": "2.0", "generator": "RSRayTracingV2" },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": gltf_meshes,
+        "materials": gltf_materials,
+        "cameras": [{
+            "type": "perspective",
+            "perspective": {
+                "yfov": 2. * ((horizontal_fov / 2.).tan() / aspect_ratio).atan(),
+                "aspectRatio": aspect_ratio,
+                "znear": 0.01,
+            },
+        }],
+        "buffers": [{
+            "uri": bin_path.file_name().map(|name| name.to_string_lossy().into_owned()),
+            "byteLength": buffer.bytes.len(),
+        }],
+        "bufferViews": buffer.views,
+        "accessors": buffer.accessors,
+    });
+
+    if !lights.is_empty() {
+        gltf["extensionsUsed"] = json!(["KHR_lights_punctual"]);
+        gltf["extensions"] = json!({ "KHR_lights_punctual": { "lights": lights } });
+    }
+
+    std::fs::write(&bin_path, &buffer.bytes)?;
+    std::fs::write(path, serde_json::to_string_pretty(&gltf)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_tracer::SceneFormat;
+    use std::path::PathBuf;
+
+    /// A folder for a test's files, removed when the test is over
+    struct TempFolder(PathBuf);
+
+    impl TempFolder {
+        fn new(name: &str) -> TempFolder {
+            let folder =
+                std::env::temp_dir().join(format!("export_test_{}_{}", std::process::id(), name));
+            std::fs::create_dir_all(&folder).unwrap();
+            TempFolder(folder)
+        }
+    }
+
+    impl Drop for TempFolder {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A box, a sphere and a mirrored sphere, so the meshes have different vertex counts
+    fn scene() -> Scene {
+        let text = r#"{
+            "objects": [{
+                "name": "box",
+                "geometry": { "Box": {
+                    "center": { "x": 0, "y": 0, "z": 0 },
+                    "half_extents": { "x": 1, "y": 1, "z": 1 },
+                    "rotation": { "x": 0, "y": 0, "z": 0 }
+                } },
+                "material": { "colour": [1, 1, 1], "specular": 10, "metallic": 0 }
+            }, {
+                "name": "sphere",
+                "geometry": { "Sphere": { "center": { "x": 4, "y": 0, "z": 0 }, "radius": 1 } },
+                "material": { "colour": [1, 0, 0], "specular": 10, "metallic": 0 }
+            }, {
+                "name": "mirrored",
+                "geometry": { "Sphere": { "center": { "x": 0, "y": 0, "z": 0 }, "radius": 1 } },
+                "material": { "colour": [0, 0, 1], "specular": 10, "metallic": 0 },
+                "transform": {
+                    "position": { "x": -4, "y": 0, "z": 0 },
+                    "rotation": { "x": 0, "y": 0, "z": 0 },
+                    "scale": { "x": -1, "y": 2, "z": 1 }
+                }
+            }]
+        }"#;
+
+        Scene::from_text(text, SceneFormat::Json).unwrap()
+    }
+
+    #[test]
+    fn export_formats_come_from_the_extension() {
+        let format = |path: &str| ExportFormat::from_path(Path::new(path)).ok();
+
+        assert_eq!(format("scene.obj"), Some(ExportFormat::Obj));
+        assert_eq!(format("SCENE.OBJ"), Some(ExportFormat::Obj));
+        assert_eq!(format("scene.gltf"), Some(ExportFormat::Gltf));
+        assert_eq!(format("scene.glb"), None);
+        assert_eq!(format("scene"), None);
+    }
+
+    #[test]
+    fn obj_faces_point_at_their_own_meshes_vertices() {
+        let folder = TempFolder::new("obj_faces");
+        let path = folder.0.join("scene.obj");
+        export_obj(&scene(), path.to_str().unwrap(), 8).unwrap();
+        let obj = std::fs::read_to_string(&path).unwrap();
+
+        // the range of position indices each object's vertices take up
+        let mut objects: Vec<(usize, usize)> = vec![];
+        let (mut positions, mut normals) = (0, 0);
+        let mut faces = 0;
+
+        for line in obj.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("o") => objects.push((positions + 1, positions)),
+                Some("v") => {
+                    positions += 1;
+                    objects.last_mut().unwrap().1 = positions;
+                }
+                Some("vn") => normals += 1,
+                Some("f") => {
+                    faces += 1;
+                    let (first, last) = *objects.last().unwrap();
+                    for corner in words {
+                        let indices: Vec<&str> = corner.split('/').collect();
+                        let position: usize = indices[0].parse().unwrap();
+                        assert!((first..=last).contains(&position), "{}", line);
+                        assert_eq!(indices[2], indices[0], "{}", line);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        assert_eq!(objects.len(), 3);
+        assert_eq!(normals, positions);
+        assert!(faces > 0);
+    }
+
+    #[test]
+    fn mirrored_meshes_are_still_wound_outwards() {
+        let (meshes, _) = collect(&scene(), 8);
+        let mirrored = meshes.iter().find(|mesh| mesh.name == "mirrored").unwrap();
+        let data = &mirrored.data;
+        let center = Vec3 {
+            x: -4.,
+            y: 0.,
+            z: 0.,
+        };
+
+        for [a, b, c] in &data.triangles {
+            let (a, b, c) = (data.positions[*a], data.positions[*b], data.positions[*c]);
+            let face_normal = (b - a) * (c - a);
+            assert!(face_normal.dot(a - center) > 0.);
+        }
+        for (position, normal) in data.positions.iter().zip(&data.normals) {
+            assert!(normal.dot(*position - center) > 0.);
+        }
+    }
+}
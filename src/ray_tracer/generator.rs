@@ -0,0 +1,553 @@
+use std::f64::consts::PI;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, UnitDisc};
+use serde::{Deserialize, Serialize};
+
+use crate::ray_tracer::{
+    Animation, Geometry, Instance, Light, Material, MaterialModel, MaterialRef, MaterialTextures,
+    MotionBlur, Object, Orientation, Prototype, RayTracer, Scene, Transform, Vec3,
+};
+
+/// The layout of a generated scene
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum Generator {
+    /// spheres of random sizes scattered on a disc, never overlapping
+    RandomSpheres {
+        count: u32,
+        min_radius: f64,
+        max_radius: f64,
+        placement_radius: f64,
+    },
+    /// spheres in rows and columns
+    Grid {
+        columns: u32,
+        rows: u32,
+        spacing: f64,
+        radius: f64,
+    },
+    /// the classic test scene, with a red and a green wall and a light in the ceiling
+    CornellBox { size: f64 },
+    /// spheres winding outwards and upwards from the middle
+    Spiral {
+        count: u32,
+        turns: f64,
+        radius: f64,
+        height: f64,
+        sphere_radius: f64,
+    },
+}
+
+impl Generator {
+    pub const KINDS: [&'static str; 4] = ["random spheres", "grid", "cornell box", "spiral"];
+
+    /// The generator of a kind, with its usual parameters
+    pub fn new(kind: &str) -> Generator {
+        match kind {
+            "grid" => Generator::Grid {
+                columns: 8,
+                rows: 8,
+                spacing: 3.,
+                radius: 1.,
+            },
+            "cornell box" => Generator::CornellBox { size: 10. },
+            "spiral" => Generator::Spiral {
+                count: 60,
+                turns: 3.,
+                radius: 20.,
+                height: 10.,
+                sphere_radius: 1.,
+            },
+            _ => Generator::RandomSpheres {
+                count: 100,
+                min_radius: 3.,
+                max_radius: 8.,
+                placement_radius: 50.,
+            },
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Generator::RandomSpheres { .. } => "random spheres",
+            Generator::Grid { .. } => "grid",
+            Generator::CornellBox { .. } => "cornell box",
+            Generator::Spiral { .. } => "spiral",
+        }
+    }
+}
+
+/// How the materials of generated spheres are picked
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct MaterialDistribution {
+    pub model: MaterialModel,
+    /// the range specular is picked from
+    pub specular: (f64, f64),
+    /// how likely a sphere is to be metallic at all, with a random amount if it is
+    pub metallic_chance: f64,
+    /// the range roughness is picked from
+    pub roughness: (f64, f64),
+    /// how likely a sphere is to glow in its own colour
+    pub emissive_chance: f64,
+}
+
+impl Default for MaterialDistribution {
+    fn default() -> MaterialDistribution {
+        MaterialDistribution {
+            model: MaterialModel::Phong,
+            specular: (0., 1000.),
+            metallic_chance: 0.7,
+            roughness: (0., 0.),
+            emissive_chance: 0.,
+        }
+    }
+}
+
+impl MaterialDistribution {
+    fn sample(&self, rng: &mut ChaCha8Rng) -> Material {
+        let colour = (rng.gen(), rng.gen(), rng.gen());
+        let between =
+            |rng: &mut ChaCha8Rng, (min, max): (f64, f64)| min + (max - min) * rng.gen::<f64>();
+        let is_metallic = rng.gen::<f64>() < self.metallic_chance;
+        let is_emissive = rng.gen::<f64>() < self.emissive_chance;
+
+        Material {
+            model: self.model,
+            colour,
+            specular: between(rng, self.specular),
+            metallic: if is_metallic { rng.gen() } else { 0. },
+            roughness: between(rng, self.roughness),
+            emission: if is_emissive { colour } else { (0., 0., 0.) },
+            emission_strength: if is_emissive { 2. } else { 0. },
+            normal_map: None,
+            textures: MaterialTextures::default(),
+            graph: None,
+        }
+    }
+}
+
+/// Everything needed to make the same scene again
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct GeneratorSettings {
+    pub seed: u64,
+    pub generator: Generator,
+    pub materials: MaterialDistribution,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> GeneratorSettings {
+        GeneratorSettings {
+            seed: 0,
+            generator: Generator::new("random spheres"),
+            materials: MaterialDistribution::default(),
+        }
+    }
+}
+
+fn vec3(x: f64, y: f64, z: f64) -> Vec3 {
+    Vec3 { x, y, z }
+}
+
+fn plain_material(colour: (f64, f64, f64), specular: f64, metallic: f64) -> Material {
+    Material {
+        model: MaterialModel::Phong,
+        colour,
+        specular,
+        metallic,
+        roughness: 0.,
+        emission: (0., 0., 0.),
+        emission_strength: 0.,
+        normal_map: None,
+        textures: MaterialTextures::default(),
+        graph: None,
+    }
+}
+
+fn object(name: &str, geometry: Geometry, material: impl Into<MaterialRef>) -> Object {
+    Object {
+        name: name.to_string(),
+        geometry,
+        material: material.into(),
+        transform: Transform::default(),
+        motion: None,
+    }
+}
+
+/// A scene with a ground plane, a sun and a fill light, but nothing on it yet
+fn outdoor_scene() -> Scene {
+    Scene {
+        includes: vec![],
+        objects: vec![object(
+            "plane",
+            Geometry::Plane {
+                center: vec3(0., 0., 0.),
+                normal: vec3(0., 1., 0.),
+                size: 100000.,
+            },
+            plain_material((0.5, 0.5, 0.5), 10., 0.2),
+        )],
+        materials: vec![],
+        // every sphere is an instance of this, scaled by its radius
+        prototypes: vec![Prototype {
+            name: "sphere".to_string(),
+            material: plain_material((1., 1., 1.), 500., 0.).into(),
+            geometry: Geometry::Sphere {
+                center: vec3(0., 0., 0.),
+                radius: 1.,
+            },
+        }],
+        instances: vec![],
+        lights: vec![
+            Light::Direction {
+                intensity: (0.4, 0.4, 0.4),
+                direction: vec3(-1., -1.5, -0.5).normalize(),
+            },
+            Light::Point {
+                intensity: (0.4, 0.4, 0.4),
+                position: vec3(0., 2., 0.),
+            },
+        ],
+        background_colour: (0.5, 0.8, 1.),
+        ambient_light: (0.2, 0.2, 0.2),
+        reflection_limit: 4,
+        reflection_samples: 8,
+        emission_samples: 4,
+        fog: None,
+        volumes: vec![],
+        volume_steps: 32,
+        pixel_samples: 1,
+        motion_blur: MotionBlur::default(),
+        do_objects_spin: false,
+        animation: Animation::default(),
+    }
+}
+
+fn sphere_instance(name: String, position: Vec3, radius: f64, material: Material) -> Instance {
+    Instance {
+        name,
+        prototype: "sphere".to_string(),
+        transform: Transform {
+            position,
+            scale: vec3(radius, radius, radius),
+            ..Transform::default()
+        },
+        material: Some(material.into()),
+        motion: None,
+    }
+}
+
+fn cornell_box(size: f64) -> Scene {
+    let half = size / 2.;
+    let white = plain_material((0.75, 0.75, 0.75), 10., 0.);
+    let wall = |name: &str, center: Vec3, normal: Vec3, colour: (f64, f64, f64)| {
+        object(
+            name,
+            Geometry::Plane {
+                center,
+                normal,
+                size,
+            },
+            plain_material(colour, 10., 0.),
+        )
+    };
+
+    let mut light_material = plain_material((1., 1., 1.), 0., 0.);
+    light_material.emission = (1., 0.85, 0.7);
+    light_material.emission_strength = 4.;
+
+    Scene {
+        includes: vec![],
+        objects: vec![
+            wall("floor", vec3(0., 0., 0.), vec3(0., 1., 0.), white.colour),
+            wall(
+                "ceiling",
+                vec3(0., size, 0.),
+                vec3(0., -1., 0.),
+                white.colour,
+            ),
+            wall(
+                "back wall",
+                vec3(0., half, -half),
+                vec3(0., 0., 1.),
+                white.colour,
+            ),
+            wall(
+                "left wall",
+                vec3(-half, half, 0.),
+                vec3(1., 0., 0.),
+                (0.65, 0.05, 0.05),
+            ),
+            wall(
+                "right wall",
+                vec3(half, half, 0.),
+                vec3(-1., 0., 0.),
+                (0.12, 0.45, 0.15),
+            ),
+            object(
+                "light",
+                Geometry::Disc {
+                    // just under the ceiling, so they don't fight
+                    center: vec3(0., size * 0.999, 0.),
+                    normal: vec3(0., -1., 0.),
+                    radius: size / 8.,
+                },
+                light_material,
+            ),
+            object(
+                "tall box",
+                Geometry::Box {
+                    center: vec3(-size * 0.17, size * 0.3, -size * 0.15),
+                    half_extents: vec3(size * 0.15, size * 0.3, size * 0.15),
+                    rotation: Orientation::new(vec3(0., 0.3, 0.)),
+                },
+                white.clone(),
+            ),
+            object(
+                "short box",
+                Geometry::Box {
+                    center: vec3(size * 0.18, size * 0.15, size * 0.15),
+                    half_extents: vec3(size * 0.15, size * 0.15, size * 0.15),
+                    rotation: Orientation::new(vec3(0., -0.3, 0.)),
+                },
+                white,
+            ),
+        ],
+        materials: vec![],
+        prototypes: vec![],
+        instances: vec![],
+        lights: vec![Light::Point {
+            intensity: (0.5, 0.45, 0.4),
+            position: vec3(0., size * 0.9, 0.),
+        }],
+        background_colour: (0., 0., 0.),
+        ambient_light: (0.05, 0.05, 0.05),
+        reflection_limit: 4,
+        reflection_samples: 8,
+        emission_samples: 8,
+        fog: None,
+        volumes: vec![],
+        volume_steps: 32,
+        pixel_samples: 1,
+        motion_blur: MotionBlur::default(),
+        do_objects_spin: false,
+        animation: Animation::default(),
+    }
+}
+
+impl GeneratorSettings {
+    /// The same settings always make the same scene
+    pub fn generate(&self) -> Scene {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+
+        match self.generator {
+            Generator::RandomSpheres {
+                count,
+                min_radius,
+                max_radius,
+                placement_radius,
+            } => {
+                let mut scene = outdoor_scene();
+
+                for i in 0..count {
+                    // if it failed 100 times, then there's probably no space left
+                    for _ in 0..100 {
+                        let radius = min_radius + (max_radius - min_radius) * rng.gen::<f64>();
+                        let [x, z]: [f64; 2] = UnitDisc.sample(&mut rng);
+                        let position = vec3(x * placement_radius, radius, z * placement_radius);
+
+                        // reject spheres that are intersecting others
+                        if scene.instances.iter().any(|instance| {
+                            let other_radius = instance.transform.scale.x;
+                            (instance.transform.position - position).length()
+                                < radius + other_radius
+                        }) {
+                            continue;
+                        }
+
+                        let material = self.materials.sample(&mut rng);
+                        scene.instances.push(sphere_instance(
+                            i.to_string(),
+                            position,
+                            radius,
+                            material,
+                        ));
+                        break;
+                    }
+                }
+
+                scene
+            }
+            Generator::Grid {
+                columns,
+                rows,
+                spacing,
+                radius,
+            } => {
+                let mut scene = outdoor_scene();
+
+                for row in 0..rows {
+                    for column in 0..columns {
+                        let position = vec3(
+                            (column as f64 - (columns as f64 - 1.) / 2.) * spacing,
+                            radius,
+                            (row as f64 - (rows as f64 - 1.) / 2.) * spacing,
+                        );
+                        let material = self.materials.sample(&mut rng);
+                        scene.instances.push(sphere_instance(
+                            format!("{} {}", column, row),
+                            position,
+                            radius,
+                            material,
+                        ));
+                    }
+                }
+
+                scene
+            }
+            Generator::CornellBox { size } => cornell_box(size),
+            Generator::Spiral {
+                count,
+                turns,
+                radius,
+                height,
+                sphere_radius,
+            } => {
+                let mut scene = outdoor_scene();
+
+                for i in 0..count {
+                    let t = i as f64 / (count.max(2) - 1) as f64;
+                    let angle = t * turns * 2. * PI;
+                    let position = vec3(
+                        angle.cos() * radius * t,
+                        sphere_radius + height * t,
+                        angle.sin() * radius * t,
+                    );
+                    let material = self.materials.sample(&mut rng);
+                    scene.instances.push(sphere_instance(
+                        i.to_string(),
+                        position,
+                        sphere_radius,
+                        material,
+                    ));
+                }
+
+                scene
+            }
+        }
+    }
+
+    /// Where the camera starts, and what it looks at
+    pub fn view(&self) -> (Vec3, Vec3) {
+        match self.generator {
+            // where the camera has always started
+            Generator::RandomSpheres { .. } => (vec3(5., 5., 5.), vec3(0., 0., 0.)),
+            Generator::Grid {
+                columns,
+                rows,
+                spacing,
+                ..
+            } => {
+                let extent = columns.max(rows) as f64 * spacing;
+                (vec3(extent, extent * 0.8, extent), vec3(0., 0., 0.))
+            }
+            Generator::CornellBox { size } => {
+                (vec3(0., size / 2., size * 1.4), vec3(0., size / 2., 0.))
+            }
+            Generator::Spiral { radius, height, .. } => (
+                vec3(radius * 1.5, height + radius, radius * 1.5),
+                vec3(0., height / 2., 0.),
+            ),
+        }
+    }
+
+    /// Replaces the scene, and moves the camera to see it
+    pub fn apply(&self, ray_tracer: &mut RayTracer) {
+        let (camera, target) = self.view();
+        ray_tracer.scene = self.generate();
+        ray_tracer.camera = camera;
+        ray_tracer.look_at(target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(seed: u64, kind: &str) -> GeneratorSettings {
+        GeneratorSettings {
+            seed,
+            generator: Generator::new(kind),
+            materials: MaterialDistribution {
+                emissive_chance: 0.2,
+                ..MaterialDistribution::default()
+            },
+        }
+    }
+
+    fn scene_json(settings: &GeneratorSettings) -> String {
+        serde_json::to_string(&settings.generate()).unwrap()
+    }
+
+    #[test]
+    fn the_same_settings_make_the_same_scene() {
+        for kind in Generator::KINDS {
+            assert_eq!(
+                scene_json(&settings(7, kind)),
+                scene_json(&settings(7, kind)),
+                "{}",
+                kind
+            );
+        }
+    }
+
+    #[test]
+    fn seeds_make_the_same_scene_everywhere() {
+        // ChaCha's numbers are fixed for a seed, so this only changes if the layout code does
+        let scene = settings(0, "random spheres").generate();
+        let first = &scene.instances[0].transform;
+
+        assert!((first.scale.x - 6.545377077132809).abs() < 1e-12);
+        assert!(
+            (first.position - vec3(-3.407827771038985, 6.545377077132809, 19.91432426747317))
+                .length()
+                < 1e-12
+        );
+    }
+
+    #[test]
+    fn different_seeds_make_different_scenes() {
+        // the cornell box doesn't use the seed
+        for kind in ["random spheres", "grid", "spiral"] {
+            assert_ne!(
+                scene_json(&settings(1, kind)),
+                scene_json(&settings(2, kind)),
+                "{}",
+                kind
+            );
+        }
+    }
+
+    #[test]
+    fn random_spheres_never_overlap() {
+        for seed in 0..4 {
+            let scene = settings(seed, "random spheres").generate();
+            let spheres: Vec<(Vec3, f64)> = scene
+                .instances
+                .iter()
+                .map(|instance| (instance.transform.position, instance.transform.scale.x))
+                .collect();
+            assert!(spheres.len() > 10);
+
+            for (index, (position, radius)) in spheres.iter().enumerate() {
+                // resting on the ground
+                assert!((position.y - radius).abs() < 1e-12);
+
+                for (other_position, other_radius) in &spheres[index + 1..] {
+                    let distance = (*position - *other_position).length();
+                    assert!(distance >= radius + other_radius, "seed {}", seed);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,720 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::ray_tracer::{
+    Geometry, GraphNode, ImageTexture, Input, LibraryMaterial, Light, Mat44, Material,
+    MaterialGraph, MaterialModel, MaterialRef, MaterialTextures, MathOp, Mesh, MeshData, MeshError,
+    NodeKind, NormalMap, Object, RayTracer, Transform, Vec3,
+};
+
+#[derive(Debug)]
+pub enum GltfError {
+    Gltf(gltf::Error),
+    /// a mesh in the file isn't usable
+    Mesh {
+        name: String,
+        error: MeshError,
+    },
+    Texture {
+        path: String,
+        error: image::ImageError,
+    },
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfError::Gltf(error) => write!(f, "{}", error),
+            GltfError::Mesh { name, error } => write!(f, "mesh {}: {}", name, error),
+            GltfError::Texture { path, error } => write!(f, "texture {}: {}", path, error),
+        }
+    }
+}
+
+impl From<gltf::Error> for GltfError {
+    fn from(error: gltf::Error) -> GltfError {
+        GltfError::Gltf(error)
+    }
+}
+
+/// A perspective camera from the file
+pub struct GltfCamera {
+    pub position: Vec3,
+    /// in the same terms as the ray tracer's rotation
+    pub rotation: Vec3,
+    /// in radians
+    pub vertical_fov: f64,
+    /// width over height, if the file gives one
+    pub aspect_ratio: Option<f64>,
+}
+
+impl GltfCamera {
+    /// The ray tracer's fov is horizontal and in degrees
+    pub fn fov(&self, width: u32, height: u32) -> f64 {
+        let aspect_ratio = self
+            .aspect_ratio
+            .unwrap_or(width as f64 / height.max(1) as f64);
+
+        2. * ((self.vertical_fov / 2.).tan() * aspect_ratio).atan() * 180. / std::f64::consts::PI
+    }
+}
+
+/// Everything read from a glTF file, ready to be added to a scene
+pub struct GltfImport {
+    pub objects: Vec<Object>,
+    pub materials: Vec<LibraryMaterial>,
+    pub lights: Vec<Light>,
+    pub cameras: Vec<GltfCamera>,
+}
+
+impl GltfImport {
+    /// Loads a .gltf or .glb file.
+    /// Textures are saved in scenes by their path,
+    /// so images inside the file are written out next to it as PNGs.
+    ///
+    /// glTF lights are in candela or lux and fall off with distance, which lights here don't,
+    /// so there's no one right conversion and their intensities are multiplied by light_scale.
+    /// 1 keeps them as they are, which is how export_gltf writes them.
+    /// Blender's come out in the hundreds or more.
+    pub fn load(path: &str, light_scale: f64) -> Result<GltfImport, GltfError> {
+        let (document, buffers, images) = gltf::import(path)?;
+        let textures = convert_images(&document, images, Path::new(path))?;
+
+        let mut import = GltfImport {
+            objects: vec![],
+            materials: vec![],
+            lights: vec![],
+            cameras: vec![],
+        };
+
+        // in the same order as the file, so they can be found by index
+        let mut material_names = vec![];
+        for material in document.materials() {
+            let mut name = material
+                .name()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("material {}", import.materials.len()));
+
+            // names in the file don't have to be unique, but they do in the library
+            if import.materials.iter().any(|other| other.name == name) {
+                name = format!("{} {}", name, import.materials.len());
+            }
+
+            material_names.push(name.clone());
+            import.materials.push(LibraryMaterial {
+                name,
+                material: convert_material(&material, &textures),
+            });
+        }
+
+        let mut context = Context {
+            buffers: &buffers,
+            material_names: &material_names,
+            light_scale,
+            meshes: HashMap::new(),
+        };
+
+        let scene = match document.default_scene() {
+            Some(scene) => Some(scene),
+            None => document.scenes().next(),
+        };
+
+        if let Some(scene) = scene {
+            for node in scene.nodes() {
+                import.add_node(&node, Mat44::identity(), &mut context)?;
+            }
+        }
+
+        Ok(import)
+    }
+
+    fn add_node(
+        &mut self,
+        node: &gltf::Node,
+        parent: Mat44,
+        context: &mut Context,
+    ) -> Result<(), GltfError> {
+        // glTF's column major matrices have the same layout as these row vector ones
+        let local = node.transform().matrix();
+        let matrix = Mat44::new(local.map(|row| row.map(|value| value as f64))) * parent;
+
+        let transform = Transform::from_matrix(matrix);
+        let name = node
+            .name()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("node {}", node.index()));
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    continue;
+                }
+
+                let material = match primitive.material().index() {
+                    Some(index) => MaterialRef::Library(context.material_names[index].clone()),
+                    None => MaterialRef::Own(Box::new(default_material())),
+                };
+
+                self.objects.push(Object {
+                    name: if mesh.primitives().len() > 1 {
+                        format!("{} {}", name, primitive.index())
+                    } else {
+                        name.clone()
+                    },
+                    material,
+                    geometry: Geometry::Mesh {
+                        center: Vec3 {
+                            x: 0.,
+                            y: 0.,
+                            z: 0.,
+                        },
+                        mesh: context.mesh(&mesh, &primitive)?,
+                    },
+                    transform,
+                    motion: None,
+                });
+            }
+        }
+
+        if let Some(camera) = node.camera() {
+            if let gltf::camera::Projection::Perspective(perspective) = camera.projection() {
+                // cameras look down their -z
+                let direction = -Vec3 {
+                    x: 0.,
+                    y: 0.,
+                    z: 1.,
+                }
+                .transform_direction(matrix)
+                .normalize();
+
+                self.cameras.push(GltfCamera {
+                    position: transform.position,
+                    rotation: Vec3 {
+                        x: (-direction.y).asin(),
+                        y: direction.x.atan2(-direction.z),
+                        z: 0.,
+                    },
+                    vertical_fov: perspective.yfov() as f64,
+                    aspect_ratio: perspective.aspect_ratio().map(|ratio| ratio as f64),
+                });
+            }
+        }
+
+        if let Some(light) = node.light() {
+            let [red, green, blue] = light.color();
+            let strength = light.intensity() as f64 * context.light_scale;
+            let intensity = (
+                red as f64 * strength,
+                green as f64 * strength,
+                blue as f64 * strength,
+            );
+
+            self.lights.push(match light.kind() {
+                gltf::khr_lights_punctual::Kind::Directional => Light::Direction {
+                    intensity,
+                    // lights point down their -z too
+                    direction: -Vec3 {
+                        x: 0.,
+                        y: 0.,
+                        z: 1.,
+                    }
+                    .transform_direction(matrix)
+                    .normalize(),
+                },
+                // spot lights don't have a cone here
+                _ => Light::Point {
+                    intensity,
+                    position: transform.position,
+                },
+            });
+        }
+
+        for child in node.children() {
+            self.add_node(&child, matrix, context)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds everything to the scene, and moves the camera to the file's first one.
+    /// Materials are renamed if the library already has one with the same name.
+    pub fn add_to(self, ray_tracer: &mut RayTracer) {
+        let scene = &mut ray_tracer.scene;
+
+        let mut renames = HashMap::new();
+        for mut material in self.materials {
+            let name = scene.unique_material_name(&material.name);
+            if name != material.name {
+                renames.insert(material.name.clone(), name.clone());
+                material.name = name;
+            }
+            scene.materials.push(material);
+        }
+
+        for mut object in self.objects {
+            if let MaterialRef::Library(name) = &mut object.material {
+                if let Some(new_name) = renames.get(name) {
+                    *name = new_name.clone();
+                }
+            }
+            scene.objects.push(object);
+        }
+
+        scene.lights.extend(self.lights);
+
+        if let Some(camera) = self.cameras.first() {
+            ray_tracer.camera = camera.position;
+            ray_tracer.rotation = camera.rotation;
+            ray_tracer.fov = camera.fov(ray_tracer.width, ray_tracer.height);
+        }
+    }
+}
+
+struct Context<'a> {
+    buffers: &'a [gltf::buffer::Data],
+    material_names: &'a [String],
+    light_scale: f64,
+    /// by mesh and primitive index, so nodes that use the same mesh share it
+    meshes: HashMap<(usize, usize), Mesh>,
+}
+
+impl<'a> Context<'a> {
+    fn mesh(&mut self, mesh: &gltf::Mesh, primitive: &gltf::Primitive) -> Result<Mesh, GltfError> {
+        let key = (mesh.index(), primitive.index());
+        if let Some(mesh) = self.meshes.get(&key) {
+            return Ok(mesh.clone());
+        }
+
+        let buffers = self.buffers;
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let vec3 = |[x, y, z]: [f32; 3]| Vec3 {
+            x: x as f64,
+            y: y as f64,
+            z: z as f64,
+        };
+
+        let positions: Vec<Vec3> = reader
+            .read_positions()
+            .map(|positions| positions.map(vec3).collect())
+            .unwrap_or_default();
+
+        let indices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|index| index as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+
+        let data = MeshData {
+            triangles: indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+            normals: reader
+                .read_normals()
+                .map(|normals| normals.map(vec3).collect())
+                .unwrap_or_default(),
+            // glTF's v goes down the image
+            uvs: reader
+                .read_tex_coords(0)
+                .map(|uvs| {
+                    uvs.into_f32()
+                        .map(|[u, v]| (u as f64, 1. - v as f64))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            colours: reader
+                .read_colors(0)
+                .map(|colours| {
+                    colours
+                        .into_rgb_f32()
+                        .map(|[r, g, b]| (r as f64, g as f64, b as f64))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            positions,
+        };
+
+        let new_mesh = Mesh::new(data).map_err(|error| GltfError::Mesh {
+            name: mesh
+                .name()
+                .map(str::to_string)
+                .unwrap_or_else(|| mesh.index().to_string()),
+            error,
+        })?;
+
+        self.meshes.insert(key, new_mesh.clone());
+        Ok(new_mesh)
+    }
+}
+
+/// What glTF uses when a primitive doesn't have a material
+fn default_material() -> Material {
+    Material {
+        model: MaterialModel::Pbr,
+        colour: (1., 1., 1.),
+        specular: 0.,
+        metallic: 1.,
+        roughness: 1.,
+        emission: (0., 0., 0.),
+        emission_strength: 0.,
+        normal_map: None,
+        textures: MaterialTextures::default(),
+        graph: None,
+    }
+}
+
+/// The images gltf has already decoded, as floats like ImageTexture::load gives
+fn image_data(data: gltf::image::Data) -> Option<image::Rgb32FImage> {
+    use gltf::image::Format;
+    use image::{DynamicImage, ImageBuffer};
+
+    let (width, height, pixels) = (data.width, data.height, data.pixels);
+    // wider channels are in native byte order
+    let u16s = |pixels: Vec<u8>| -> Vec<u16> {
+        pixels
+            .chunks_exact(2)
+            .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+            .collect()
+    };
+    let f32s = |pixels: Vec<u8>| -> Vec<f32> {
+        pixels
+            .chunks_exact(4)
+            .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect()
+    };
+
+    let image = match data.format {
+        Format::R8 => DynamicImage::ImageLuma8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8 => DynamicImage::ImageLumaA8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8B8 => DynamicImage::ImageRgb8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8B8A8 => DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R16 => {
+            DynamicImage::ImageLuma16(ImageBuffer::from_raw(width, height, u16s(pixels))?)
+        }
+        Format::R16G16 => {
+            DynamicImage::ImageLumaA16(ImageBuffer::from_raw(width, height, u16s(pixels))?)
+        }
+        Format::R16G16B16 => {
+            DynamicImage::ImageRgb16(ImageBuffer::from_raw(width, height, u16s(pixels))?)
+        }
+        Format::R16G16B16A16 => {
+            DynamicImage::ImageRgba16(ImageBuffer::from_raw(width, height, u16s(pixels))?)
+        }
+        Format::R32G32B32FLOAT => {
+            DynamicImage::ImageRgb32F(ImageBuffer::from_raw(width, height, f32s(pixels))?)
+        }
+        Format::R32G32B32A32FLOAT => {
+            DynamicImage::ImageRgba32F(ImageBuffer::from_raw(width, height, f32s(pixels))?)
+        }
+    };
+
+    Some(image.to_rgb32f())
+}
+
+/// A texture for each image in the file, in the same order.
+/// Images in files of their own keep their path,
+/// embedded ones are saved next to the glTF file, named after it.
+fn convert_images(
+    document: &gltf::Document,
+    images: Vec<gltf::image::Data>,
+    path: &Path,
+) -> Result<Vec<ImageTexture>, GltfError> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+    document
+        .images()
+        .zip(images)
+        .map(|(image, data)| {
+            let (texture_path, is_embedded) = match image.source() {
+                gltf::image::Source::Uri { uri, mime_type: _ } if !uri.starts_with("data:") => {
+                    (path.with_file_name(uri), false)
+                }
+                _ => (
+                    path.with_file_name(format!("{}_image_{}.png", stem, image.index())),
+                    true,
+                ),
+            };
+            let texture_path = texture_path.to_string_lossy().into_owned();
+            let error = |error| GltfError::Texture {
+                path: texture_path.clone(),
+                error,
+            };
+
+            let pixels = image_data(data).ok_or_else(|| {
+                error(image::ImageError::Parameter(
+                    image::error::ParameterError::from_kind(
+                        image::error::ParameterErrorKind::DimensionMismatch,
+                    ),
+                ))
+            })?;
+
+            if is_embedded {
+                image::DynamicImage::ImageRgb32F(pixels.clone())
+                    .into_rgb8()
+                    .save(&texture_path)
+                    .map_err(error)?;
+            }
+
+            Ok(ImageTexture::new(texture_path, pixels))
+        })
+        .collect()
+}
+
+/// Textures become nodes in a material graph, multiplied by the plain values like glTF does
+fn convert_material(material: &gltf::Material, textures: &[ImageTexture]) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let [red, green, blue, _] = pbr.base_color_factor();
+    let [emission_red, emission_green, emission_blue] = material.emissive_factor();
+
+    let texture = |texture: gltf::Texture| textures[texture.source().index()].clone();
+
+    let mut graph = MaterialGraph::default();
+    let mut add_node = |kind: NodeKind| {
+        graph.nodes.push(GraphNode {
+            kind,
+            position: (10. + graph.nodes.len() as f32 * 160., 10.),
+        });
+        Input::Node(graph.nodes.len() - 1)
+    };
+
+    let colour = (red as f64, green as f64, blue as f64);
+    let mut colour_input = None;
+    if let Some(info) = pbr.base_color_texture() {
+        let texture = texture(info.texture());
+        let texture = add_node(NodeKind::Texture { texture, scale: 1. });
+        colour_input = Some(add_node(NodeKind::Math {
+            operation: MathOp::Multiply,
+            a: texture,
+            b: Input::Colour(colour),
+        }));
+    }
+
+    let mut metallic_input = None;
+    let mut roughness_input = None;
+    if let Some(info) = pbr.metallic_roughness_texture() {
+        let texture = texture(info.texture());
+        let texture = add_node(NodeKind::Texture { texture, scale: 1. });
+
+        // roughness is in green and metallic in blue,
+        // and the graph averages the channels for single values, hence the 3s
+        roughness_input = Some(add_node(NodeKind::Math {
+            operation: MathOp::Multiply,
+            a: texture.clone(),
+            b: Input::Colour((0., 3. * pbr.roughness_factor() as f64, 0.)),
+        }));
+        metallic_input = Some(add_node(NodeKind::Math {
+            operation: MathOp::Multiply,
+            a: texture,
+            b: Input::Colour((0., 0., 3. * pbr.metallic_factor() as f64)),
+        }));
+    }
+
+    graph.colour = colour_input;
+    graph.metallic = metallic_input;
+    graph.roughness = roughness_input;
+
+    let normal_map = material.normal_texture().map(|normal| NormalMap::Image {
+        texture: texture(normal.texture()),
+        strength: normal.scale() as f64,
+    });
+
+    let has_emission = emission_red > 0. || emission_green > 0. || emission_blue > 0.;
+
+    Material {
+        model: MaterialModel::Pbr,
+        colour,
+        specular: 0.,
+        metallic: pbr.metallic_factor() as f64,
+        roughness: pbr.roughness_factor() as f64,
+        emission: (
+            emission_red as f64,
+            emission_green as f64,
+            emission_blue as f64,
+        ),
+        emission_strength: if has_emission { 1. } else { 0. },
+        normal_map,
+        textures: MaterialTextures::default(),
+        graph: if graph.nodes.is_empty() {
+            None
+        } else {
+            Some(graph)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_tracer::{export_gltf, Scene, SceneFormat};
+    use std::path::PathBuf;
+
+    /// A folder of its own for a test, removed when it's done
+    struct TempFolder(PathBuf);
+
+    impl TempFolder {
+        fn new(name: &str) -> TempFolder {
+            let folder = std::env::temp_dir().join(format!(
+                "gltf_import_test_{}_{}",
+                std::process::id(),
+                name
+            ));
+            std::fs::create_dir_all(&folder).unwrap();
+            TempFolder(folder)
+        }
+    }
+
+    impl Drop for TempFolder {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn embedded_images_are_used_and_saved_beside_the_file() {
+        let folder = TempFolder::new("embedded");
+
+        let mut png = std::io::Cursor::new(vec![]);
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(2, 2, image::Rgb([255, 0, 0])))
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+        let png = png.into_inner();
+        std::fs::write(folder.0.join("scene.bin"), &png).unwrap();
+
+        // the image is in a buffer view like in a .glb, rather than a file of its own
+        let gltf = serde_json::json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "scene.bin", "byteLength": png.len() }],
+            "bufferViews": [{ "buffer": 0, "byteLength": png.len() }],
+            "images": [{ "bufferView": 0, "mimeType": "image/png" }],
+            "textures": [{ "source": 0 }],
+            "materials": [{
+                "name": "painted",
+                "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } }
+            }],
+        });
+        let path = folder.0.join("scene.gltf");
+        std::fs::write(&path, gltf.to_string()).unwrap();
+
+        let import = GltfImport::load(path.to_str().unwrap(), 1.).unwrap();
+
+        let graph = import.materials[0].material.graph.as_ref().unwrap();
+        let texture = graph
+            .nodes
+            .iter()
+            .find_map(|node| match &node.kind {
+                NodeKind::Texture { texture, .. } => Some(texture),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(texture.sample(0.5, 0.5), (1., 0., 0.));
+        let saved = folder.0.join("scene_image_0.png");
+        assert_eq!(texture.path, saved.to_string_lossy());
+        assert!(ImageTexture::load(texture.path.clone()).is_ok());
+    }
+
+    fn vec3(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn assert_close(a: Vec3, b: Vec3, tolerance: f64) {
+        assert!((a - b).length() < tolerance, "{:?} isn't {:?}", a, b);
+    }
+
+    /// A moved and scaled sphere, two lights and a camera, exported and loaded back in
+    fn round_trip(name: &str, light_scale: f64) -> (RayTracer, GltfImport) {
+        let text = r#"{
+            "objects": [{
+                "name": "ball",
+                "geometry": { "Sphere": { "center": { "x": 0, "y": 0, "z": 0 }, "radius": 1 } },
+                "material": { "colour": [1, 0, 0], "specular": 10, "metallic": 0 },
+                "transform": {
+                    "position": { "x": 1, "y": 2, "z": 3 },
+                    "rotation": { "x": 0, "y": 1, "z": 0 },
+                    "scale": { "x": 2, "y": 2, "z": 2 }
+                }
+            }],
+            "lights": [
+                { "Point": { "intensity": [2, 1, 0.5], "position": { "x": 0, "y": 5, "z": 0 } } },
+                { "Direction": { "intensity": [0.5, 0.5, 0.5], "direction": { "x": 0, "y": -1, "z": 0 } } }
+            ]
+        }"#;
+        let ray_tracer = RayTracer {
+            camera: vec3(0., 1., -5.),
+            rotation: vec3(0.1, 0.3, 0.),
+            fov: 60.,
+            width: 400,
+            height: 300,
+            scene: Scene::from_text(text, SceneFormat::Json).unwrap(),
+        };
+
+        let folder = TempFolder::new(name);
+        let path = folder.0.join("scene.gltf");
+        let path = path.to_str().unwrap();
+        export_gltf(&ray_tracer, path, 32).unwrap();
+        let import = GltfImport::load(path, light_scale).unwrap();
+
+        (ray_tracer, import)
+    }
+
+    #[test]
+    fn exported_scenes_load_back_in() {
+        let (ray_tracer, import) = round_trip("round_trip", 1.);
+
+        // the export is in world space, so the sphere's transform is in its triangles
+        assert_eq!(import.objects.len(), 1);
+        let object = &import.objects[0];
+        assert_eq!(object.name, "ball");
+        let mesh = match &object.geometry {
+            Geometry::Mesh { mesh, .. } => mesh,
+            geometry => panic!("expected a mesh, got {:?}", geometry),
+        };
+        let matrix = object.transform.matrix();
+        for position in &mesh.data().positions {
+            let distance = (position.transform_point(matrix) - vec3(1., 2., 3.)).length();
+            assert!((distance - 2.).abs() < 1e-5, "{} from the center", distance);
+        }
+
+        assert_eq!(import.materials.len(), 1);
+        assert_eq!(import.materials[0].material.colour, (1., 0., 0.));
+
+        let camera = &import.cameras[0];
+        assert_close(camera.position, ray_tracer.camera, 1e-5);
+        assert_close(camera.rotation, ray_tracer.rotation, 1e-5);
+        assert!((camera.fov(ray_tracer.width, ray_tracer.height) - ray_tracer.fov).abs() < 1e-3);
+
+        assert_eq!(import.lights.len(), 2);
+        match import.lights[0] {
+            Light::Point {
+                intensity,
+                position,
+            } => {
+                assert!((intensity.0 - 2.).abs() < 1e-5);
+                assert!((intensity.1 - 1.).abs() < 1e-5);
+                assert!((intensity.2 - 0.5).abs() < 1e-5);
+                assert_close(position, vec3(0., 5., 0.), 1e-5);
+            }
+            ref light => panic!("expected a point light, got {:?}", light),
+        }
+        match import.lights[1] {
+            Light::Direction {
+                intensity,
+                direction,
+            } => {
+                assert!((intensity.0 - 0.5).abs() < 1e-5);
+                assert_close(direction, vec3(0., -1., 0.), 1e-5);
+            }
+            ref light => panic!("expected a directional light, got {:?}", light),
+        }
+    }
+
+    #[test]
+    fn lights_are_scaled() {
+        let (_, import) = round_trip("light_scale", 0.01);
+
+        match import.lights[0] {
+            Light::Point { intensity, .. } => assert!((intensity.0 - 0.02).abs() < 1e-6),
+            ref light => panic!("expected a point light, got {:?}", light),
+        }
+    }
+}
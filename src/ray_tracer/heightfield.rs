@@ -0,0 +1,509 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ray_tracer::{fbm, solve_quadratic, Vec3};
+
+/// Where the heights of a heightfield come from
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum HeightSource {
+    /// a greyscale image, black is the bottom and white the top
+    Image { path: String },
+    /// fractal noise over a resolution by resolution grid
+    Noise {
+        seed: u32,
+        resolution: usize,
+        /// how many hills there are across the grid
+        frequency: f64,
+        octaves: u32,
+    },
+}
+
+#[derive(Debug)]
+pub enum HeightmapError {
+    Image(image::ImageError),
+    /// there has to be at least 2 by 2 heights to make a cell
+    TooSmall,
+    /// more than Heightmap::MAX_SIZE heights across, or Heightmap::MAX_OCTAVES of noise
+    TooLarge,
+}
+
+impl std::fmt::Display for HeightmapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeightmapError::Image(error) => write!(f, "couldn't load heightmap: {}", error),
+            HeightmapError::TooSmall => write!(f, "heightmap has to be at least 2x2"),
+            HeightmapError::TooLarge => write!(
+                f,
+                "heightmap can't be more than {0}x{0}, or have more than {1} octaves",
+                Heightmap::MAX_SIZE,
+                Heightmap::MAX_OCTAVES
+            ),
+        }
+    }
+}
+
+impl From<image::ImageError> for HeightmapError {
+    fn from(error: image::ImageError) -> HeightmapError {
+        HeightmapError::Image(error)
+    }
+}
+
+/// A grid of heights between 0 and 1.
+/// Only the source is saved, the heights are made again when it's loaded.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(try_from = "HeightSource", into = "HeightSource")]
+pub struct Heightmap {
+    pub source: HeightSource,
+    width: usize,
+    depth: usize,
+    /// shared, so cloning the scene doesn't copy them
+    heights: Arc<Vec<f64>>,
+}
+
+impl TryFrom<HeightSource> for Heightmap {
+    type Error = HeightmapError;
+
+    fn try_from(source: HeightSource) -> Result<Heightmap, HeightmapError> {
+        Heightmap::new(source)
+    }
+}
+
+impl From<Heightmap> for HeightSource {
+    fn from(heightmap: Heightmap) -> HeightSource {
+        heightmap.source
+    }
+}
+
+impl Heightmap {
+    /// The most heights along each side, which is checked before anything's made,
+    /// as a scene file can ask for anything
+    pub const MAX_SIZE: usize = 8192;
+    /// More than this and the extra detail is too small to see anyway
+    pub const MAX_OCTAVES: u32 = 16;
+
+    pub fn new(source: HeightSource) -> Result<Heightmap, HeightmapError> {
+        let (width, depth) = match &source {
+            HeightSource::Image { path } => {
+                let (width, depth) = image::image_dimensions(path)?;
+                (width as usize, depth as usize)
+            }
+            HeightSource::Noise {
+                resolution,
+                octaves,
+                ..
+            } => {
+                if *octaves > Heightmap::MAX_OCTAVES {
+                    return Err(HeightmapError::TooLarge);
+                }
+                (*resolution, *resolution)
+            }
+        };
+
+        if width < 2 || depth < 2 {
+            return Err(HeightmapError::TooSmall);
+        }
+        if width > Heightmap::MAX_SIZE || depth > Heightmap::MAX_SIZE {
+            return Err(HeightmapError::TooLarge);
+        }
+
+        let heights = match &source {
+            HeightSource::Image { path } => image::open(path)?
+                .to_luma16()
+                .pixels()
+                .map(|pixel| pixel.0[0] as f64 / u16::MAX as f64)
+                .collect(),
+            HeightSource::Noise {
+                seed,
+                resolution,
+                frequency,
+                octaves,
+            } => {
+                let resolution = *resolution;
+
+                let mut heights = Vec::with_capacity(resolution * resolution);
+                for j in 0..resolution {
+                    for i in 0..resolution {
+                        let scale = *frequency / (resolution.max(2) - 1) as f64;
+                        heights.push(fbm(
+                            Vec3 {
+                                x: i as f64 * scale,
+                                // the noise is always 0 on whole numbers
+                                y: 0.5,
+                                z: j as f64 * scale,
+                            },
+                            *seed,
+                            *octaves,
+                        ));
+                    }
+                }
+
+                // stretch it to use the full height
+                let min = heights.iter().cloned().fold(f64::INFINITY, f64::min);
+                let max = heights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                if max > min {
+                    for height in heights.iter_mut() {
+                        *height = (*height - min) / (max - min);
+                    }
+                }
+
+                heights
+            }
+        };
+
+        Ok(Heightmap {
+            source,
+            width,
+            depth,
+            heights: Arc::new(heights),
+        })
+    }
+
+    /// The height at a grid point, clamped to the edges
+    fn height(&self, i: isize, j: isize) -> f64 {
+        let i = i.clamp(0, self.width as isize - 1) as usize;
+        let j = j.clamp(0, self.depth as isize - 1) as usize;
+        self.heights[j * self.width + i]
+    }
+
+    /// The cell a point in grid space is in, and how far across it it is
+    fn cell(&self, x: f64, z: f64) -> (isize, isize, f64, f64) {
+        let i = (x.floor() as isize).clamp(0, self.width as isize - 2);
+        let j = (z.floor() as isize).clamp(0, self.depth as isize - 2);
+        (
+            i,
+            j,
+            (x - i as f64).clamp(0., 1.),
+            (z - j as f64).clamp(0., 1.),
+        )
+    }
+
+    /// Bilinearly interpolated between the grid points
+    fn height_bilinear(&self, x: f64, z: f64) -> f64 {
+        let (i, j, u, v) = self.cell(x, z);
+
+        self.height(i, j) * (1. - u) * (1. - v)
+            + self.height(i + 1, j) * u * (1. - v)
+            + self.height(i, j + 1) * (1. - u) * v
+            + self.height(i + 1, j + 1) * u * v
+    }
+
+    /// From the heightfield's space, where the base is centered on the origin,
+    /// to grid space, where each cell is 1 by 1 and the heights go from 0 to 1.
+    /// It's only a scale, so distances along a ray are the same in both.
+    fn to_grid(&self, size: Vec3, local: Vec3) -> Vec3 {
+        Vec3 {
+            x: (local.x / size.x + 0.5) * (self.width - 1) as f64,
+            y: local.y / size.y,
+            z: (local.z / size.z + 0.5) * (self.depth - 1) as f64,
+        }
+    }
+
+    fn direction_to_grid(&self, size: Vec3, direction: Vec3) -> Vec3 {
+        Vec3 {
+            x: direction.x / size.x * (self.width - 1) as f64,
+            y: direction.y / size.y,
+            z: direction.z / size.z * (self.depth - 1) as f64,
+        }
+    }
+
+    /// Where a ray in grid space is inside the heightfield's bounding box
+    fn clip(&self, origin: Vec3, direction: Vec3) -> Option<(f64, f64)> {
+        let slab = |origin: f64, direction: f64, max: f64| {
+            if direction == 0. {
+                if (0. ..=max).contains(&origin) {
+                    Some((f64::NEG_INFINITY, f64::INFINITY))
+                } else {
+                    None
+                }
+            } else {
+                let t1 = -origin / direction;
+                let t2 = (max - origin) / direction;
+                Some((t1.min(t2), t1.max(t2)))
+            }
+        };
+
+        let x = slab(origin.x, direction.x, (self.width - 1) as f64)?;
+        let y = slab(origin.y, direction.y, 1.)?;
+        let z = slab(origin.z, direction.z, (self.depth - 1) as f64)?;
+
+        let t_near = x.0.max(y.0).max(z.0);
+        let t_far = x.1.min(y.1).min(z.1);
+
+        if t_near > t_far {
+            return None;
+        }
+
+        Some((t_near, t_far))
+    }
+
+    /// Where a ray crosses the bilinear surface of one cell, between start and end
+    fn cell_crossings(
+        &self,
+        i: isize,
+        j: isize,
+        origin: Vec3,
+        direction: Vec3,
+        start: f64,
+        end: f64,
+    ) -> Vec<f64> {
+        let h00 = self.height(i, j);
+        let h10 = self.height(i + 1, j);
+        let h01 = self.height(i, j + 1);
+        let h11 = self.height(i + 1, j + 1);
+
+        // the surface is never outside the heights at the corners,
+        // so most cells can be skipped without solving anything
+        let start_y = origin.y + direction.y * start;
+        let end_y = origin.y + direction.y * end;
+        if start_y.min(end_y) > h00.max(h10).max(h01).max(h11)
+            || start_y.max(end_y) < h00.min(h10).min(h01).min(h11)
+        {
+            return vec![];
+        }
+
+        // h(u, v) = h00 + a u + b v + c u v
+        let a = h10 - h00;
+        let b = h01 - h00;
+        let c = h00 - h10 - h01 + h11;
+        let u = origin.x - i as f64;
+        let v = origin.z - j as f64;
+
+        // the ray's height minus the surface's height, as a quadratic in t
+        let constant = origin.y - h00 - a * u - b * v - c * u * v;
+        let linear = direction.y
+            - a * direction.x
+            - b * direction.z
+            - c * (u * direction.z + v * direction.x);
+        let quadratic = -c * direction.x * direction.z;
+
+        let mut roots = if quadratic.abs() < 1e-12 {
+            if linear == 0. {
+                vec![]
+            } else {
+                vec![-constant / linear]
+            }
+        } else {
+            match solve_quadratic(quadratic, linear, constant) {
+                Some((plus, minus)) => vec![plus.min(minus), plus.max(minus)],
+                None => vec![],
+            }
+        };
+
+        // half open, so a crossing on the border of two cells only counts once
+        roots.retain(|t| (start..end).contains(t));
+        roots
+    }
+
+    /// Every distance along the ray after start where it goes in or out of the solid under the surface,
+    /// stepping through the grid one cell at a time.
+    pub fn crossings(
+        &self,
+        size: Vec3,
+        local_origin: Vec3,
+        local_direction: Vec3,
+        start: f64,
+        first_only: bool,
+    ) -> Vec<f64> {
+        let origin = self.to_grid(size, local_origin);
+        let direction = self.direction_to_grid(size, local_direction);
+
+        let (t_enter, t_exit) = match self.clip(origin, direction) {
+            Some(span) => span,
+            None => return vec![],
+        };
+
+        let is_below = |t: f64| {
+            let point = origin + direction * t;
+            point.y < self.height_bilinear(point.x, point.z)
+        };
+
+        let mut crossings = vec![];
+
+        // going through the sides or bottom
+        if t_enter > start && is_below(t_enter) {
+            crossings.push(t_enter);
+            if first_only {
+                return crossings;
+            }
+        }
+
+        let mut t = t_enter.max(start);
+        if t >= t_exit {
+            return crossings;
+        }
+
+        let point = origin + direction * t;
+        let (mut i, mut j, _, _) = self.cell(point.x, point.z);
+
+        // how far along the ray the next cell border in each direction is
+        let border = |cell: isize, origin: f64, direction: f64| {
+            if direction > 0. {
+                ((cell + 1) as f64 - origin) / direction
+            } else if direction < 0. {
+                (cell as f64 - origin) / direction
+            } else {
+                f64::INFINITY
+            }
+        };
+        let step_i = if direction.x > 0. { 1 } else { -1 };
+        let step_j = if direction.z > 0. { 1 } else { -1 };
+        let delta_x = 1. / direction.x.abs();
+        let delta_z = 1. / direction.z.abs();
+        let mut next_x = border(i, origin.x, direction.x);
+        let mut next_z = border(j, origin.z, direction.z);
+
+        loop {
+            let cell_end = next_x.min(next_z).min(t_exit);
+
+            crossings.append(&mut self.cell_crossings(i, j, origin, direction, t, cell_end));
+            if first_only && !crossings.is_empty() {
+                return crossings;
+            }
+
+            if cell_end >= t_exit {
+                break;
+            }
+
+            if next_x < next_z {
+                i += step_i;
+                t = next_x;
+                next_x += delta_x;
+            } else {
+                j += step_j;
+                t = next_z;
+                next_z += delta_z;
+            }
+
+            if i < 0 || j < 0 || i > self.width as isize - 2 || j > self.depth as isize - 2 {
+                break;
+            }
+        }
+
+        if t_exit > start && is_below(t_exit) {
+            crossings.push(t_exit);
+        }
+
+        crossings
+    }
+
+    /// The surface's height above the base at a point in the heightfield's space
+    pub fn surface_height(&self, size: Vec3, local: Vec3) -> f64 {
+        let grid = self.to_grid(size, local);
+        self.height_bilinear(grid.x, grid.z) * size.y
+    }
+
+    /// A smooth normal, interpolated from the normals at the grid points around it
+    pub fn normal_at_point(&self, size: Vec3, local: Vec3) -> Vec3 {
+        let grid = self.to_grid(size, local);
+        let epsilon = 1e-6;
+
+        // the sides and bottom of the block under the surface
+        if grid.y < self.height_bilinear(grid.x, grid.z) - epsilon {
+            let wall = if grid.y < epsilon {
+                Some((0., -1., 0.))
+            } else if grid.x < epsilon {
+                Some((-1., 0., 0.))
+            } else if grid.x > (self.width - 1) as f64 - epsilon {
+                Some((1., 0., 0.))
+            } else if grid.z < epsilon {
+                Some((0., 0., -1.))
+            } else if grid.z > (self.depth - 1) as f64 - epsilon {
+                Some((0., 0., 1.))
+            } else {
+                None
+            };
+
+            if let Some((x, y, z)) = wall {
+                return Vec3 { x, y, z };
+            }
+        }
+
+        let cell_width = size.x / (self.width - 1) as f64;
+        let cell_depth = size.z / (self.depth - 1) as f64;
+
+        // central differences, or one sided at the edges
+        let grid_normal = |i: isize, j: isize| {
+            let left = (i - 1).max(0);
+            let right = (i + 1).min(self.width as isize - 1);
+            let back = (j - 1).max(0);
+            let front = (j + 1).min(self.depth as isize - 1);
+
+            let slope_x = (self.height(right, j) - self.height(left, j)) * size.y
+                / ((right - left) as f64 * cell_width);
+            let slope_z = (self.height(i, front) - self.height(i, back)) * size.y
+                / ((front - back) as f64 * cell_depth);
+
+            Vec3 {
+                x: -slope_x,
+                y: 1.,
+                z: -slope_z,
+            }
+            .normalize()
+        };
+
+        let (i, j, u, v) = self.cell(grid.x, grid.z);
+
+        (grid_normal(i, j) * ((1. - u) * (1. - v))
+            + grid_normal(i + 1, j) * (u * (1. - v))
+            + grid_normal(i, j + 1) * ((1. - u) * v)
+            + grid_normal(i + 1, j + 1) * (u * v))
+            .normalize()
+    }
+
+    /// How far across the grid the point is, from 0 to 1 in each direction
+    pub fn uv_at_point(&self, size: Vec3, local: Vec3) -> (f64, f64) {
+        let grid = self.to_grid(size, local);
+
+        (
+            (grid.x / (self.width - 1) as f64).clamp(0., 1.),
+            (grid.z / (self.depth - 1) as f64).clamp(0., 1.),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(resolution: usize, octaves: u32) -> Result<Heightmap, HeightmapError> {
+        Heightmap::new(HeightSource::Noise {
+            seed: 1,
+            resolution,
+            frequency: 2.,
+            octaves,
+        })
+    }
+
+    #[test]
+    fn noise_sizes_are_checked_before_making_it() {
+        assert!(noise(16, 4).is_ok());
+        assert!(matches!(noise(1, 4), Err(HeightmapError::TooSmall)));
+        assert!(matches!(
+            noise(usize::MAX, 4),
+            Err(HeightmapError::TooLarge)
+        ));
+        assert!(matches!(
+            noise(Heightmap::MAX_SIZE + 1, 4),
+            Err(HeightmapError::TooLarge)
+        ));
+        assert!(matches!(noise(16, u32::MAX), Err(HeightmapError::TooLarge)));
+    }
+
+    #[test]
+    fn noise_uses_the_full_height() {
+        let heightmap = noise(16, 4).unwrap();
+        let min = heightmap
+            .heights
+            .iter()
+            .cloned()
+            .fold(f64::INFINITY, f64::min);
+        let max = heightmap
+            .heights
+            .iter()
+            .cloned()
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        assert_eq!((min, max), (0., 1.));
+    }
+}
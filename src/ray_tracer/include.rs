@@ -0,0 +1,480 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ray_tracer::{
+    Light, LightProperty, MaterialRef, Motion, Scene, SceneFileError, Target, Track, Transform,
+    Value, Vec3,
+};
+
+/// Changes to one object or instance from an included file
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct IncludeOverride {
+    /// the name it has in the included file
+    pub name: String,
+    #[serde(default)]
+    pub rename: Option<String>,
+    #[serde(default)]
+    pub material: Option<MaterialRef>,
+    /// replaces its own transform, the include's transform still goes on top
+    #[serde(default)]
+    pub transform: Option<Transform>,
+}
+
+/// Another scene file, added to the scene when it's loaded.
+/// Its library materials and prototypes are added too.
+/// If the scene already has one with the same name it has to be the same,
+/// like when two includes share a file of materials, otherwise it's an error.
+/// So is its animation, apart from the camera's, which belongs to the scene including it.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Include {
+    /// relative to the file that includes it
+    pub path: String,
+    /// moves everything in the file, on top of their own transforms
+    #[serde(default)]
+    pub transform: Transform,
+    /// the objects and instances to take by name, or all of them if this is missing
+    #[serde(default)]
+    pub only: Option<Vec<String>>,
+    #[serde(default = "default_lights")]
+    pub lights: bool,
+    #[serde(default)]
+    pub overrides: Vec<IncludeOverride>,
+}
+
+fn default_lights() -> bool {
+    true
+}
+
+impl Include {
+    fn wants(&self, name: &str) -> bool {
+        match &self.only {
+            Some(only) => only.iter().any(|wanted| wanted == name),
+            None => true,
+        }
+    }
+
+    /// The name an object or instance has once it's included
+    fn renamed(&self, name: &str) -> String {
+        self.overrides
+            .iter()
+            .find(|overrides| overrides.name == name)
+            .and_then(|overrides| overrides.rename.clone())
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// Applies the override for an object or instance and the include's transform,
+    /// giving back the material it should use instead
+    fn place(
+        &self,
+        name: &mut String,
+        transform: &mut Transform,
+        motion: &mut Option<Motion>,
+    ) -> Option<MaterialRef> {
+        let overrides = self
+            .overrides
+            .iter()
+            .find(|overrides| overrides.name == *name);
+
+        if let Some(new_transform) = overrides.and_then(|overrides| overrides.transform) {
+            *transform = new_transform;
+        }
+        if let Some(new_name) = overrides.and_then(|overrides| overrides.rename.clone()) {
+            *name = new_name;
+        }
+
+        // from_matrix rounds a little, so it's skipped when nothing moves
+        if !self.transform.is_identity() {
+            let matrix = self.transform.matrix();
+            let place = |transform: &mut Transform| {
+                *transform = Transform::from_matrix(transform.matrix() * matrix);
+            };
+
+            place(transform);
+            match motion {
+                Some(Motion::Velocity(velocity)) => {
+                    *velocity = velocity.transform_direction(matrix)
+                }
+                Some(Motion::Transforms { open, close }) => {
+                    place(open);
+                    place(close);
+                }
+                None => (),
+            }
+        }
+
+        overrides.and_then(|overrides| overrides.material.clone())
+    }
+}
+
+/// Whether two things would be saved the same, as materials and prototypes can't be compared
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    match (serde_json::to_value(a), serde_json::to_value(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+impl Scene {
+    /// Adds everything the includes point to, and what they include in turn,
+    /// then removes them so the scene is flat.
+    /// Include paths are relative to the path given, which is where the scene came from.
+    pub fn resolve_includes(&mut self, path: &str) -> Result<(), SceneFileError> {
+        self.resolve_includes_in(Path::new(path), &mut vec![])
+    }
+
+    /// The chain is the files being loaded that led here, to catch cycles
+    fn resolve_includes_in(
+        &mut self,
+        path: &Path,
+        chain: &mut Vec<(PathBuf, String)>,
+    ) -> Result<(), SceneFileError> {
+        let full_path = path.canonicalize().map_err(SceneFileError::Io)?;
+        chain.push((full_path, path.display().to_string()));
+
+        for include in std::mem::take(&mut self.includes) {
+            let include_path = path
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(&include.path);
+
+            self.add_include(&include, &include_path, chain)
+                .map_err(|error| SceneFileError::Include {
+                    path: include.path.clone(),
+                    error: Box::new(error),
+                })?;
+        }
+
+        chain.pop();
+        Ok(())
+    }
+
+    fn add_include(
+        &mut self,
+        include: &Include,
+        path: &Path,
+        chain: &mut Vec<(PathBuf, String)>,
+    ) -> Result<(), SceneFileError> {
+        let full_path = path.canonicalize().map_err(SceneFileError::Io)?;
+        if let Some(start) = chain.iter().position(|(other, _)| *other == full_path) {
+            let mut paths: Vec<String> = chain[start..]
+                .iter()
+                .map(|(_, path)| path.clone())
+                .collect();
+            paths.push(path.display().to_string());
+            return Err(SceneFileError::IncludeCycle(paths));
+        }
+
+        let mut scene = Scene::load_unresolved(path)?;
+        scene.resolve_includes_in(path, chain)?;
+
+        let names: Vec<String> = scene
+            .objects
+            .iter()
+            .map(|object| object.name.clone())
+            .chain(scene.instances.iter().map(|instance| instance.name.clone()))
+            .collect();
+
+        let named = include.only.iter().flatten();
+        let overridden = include.overrides.iter().map(|overrides| &overrides.name);
+        if let Some(missing) = named.chain(overridden).find(|name| !names.contains(name)) {
+            return Err(SceneFileError::UnknownObject(missing.clone()));
+        }
+
+        for mut object in scene.objects {
+            if include.wants(&object.name) {
+                if let Some(material) =
+                    include.place(&mut object.name, &mut object.transform, &mut object.motion)
+                {
+                    object.material = material;
+                }
+                self.objects.push(object);
+            }
+        }
+
+        for mut instance in scene.instances {
+            if include.wants(&instance.name) {
+                if let Some(material) = include.place(
+                    &mut instance.name,
+                    &mut instance.transform,
+                    &mut instance.motion,
+                ) {
+                    instance.material = Some(material);
+                }
+                self.instances.push(instance);
+            }
+        }
+
+        for material in scene.materials {
+            match self.library_material(&material.name) {
+                None => self.materials.push(material),
+                Some(existing) if same(existing, &material.material) => (),
+                Some(_) => return Err(SceneFileError::NameCollision("material", material.name)),
+            }
+        }
+
+        for prototype in scene.prototypes {
+            match self.prototype(&prototype.name) {
+                None => self.prototypes.push(prototype),
+                Some(existing) if same(existing, &prototype) => (),
+                Some(_) => return Err(SceneFileError::NameCollision("prototype", prototype.name)),
+            }
+        }
+
+        let matrix = include.transform.matrix();
+
+        self.add_included_tracks(include, scene.animation.tracks, &names)?;
+        self.animation.length = self.animation.length.max(scene.animation.length);
+
+        if include.lights {
+            self.lights
+                .extend(scene.lights.into_iter().map(|light| match light {
+                    Light::Direction {
+                        intensity,
+                        direction,
+                    } => Light::Direction {
+                        intensity,
+                        direction: direction.transform_direction(matrix).normalize(),
+                    },
+                    Light::Point {
+                        intensity,
+                        position,
+                    } => Light::Point {
+                        intensity,
+                        position: position.transform_point(matrix),
+                    },
+                }));
+        }
+
+        // volumes are boxes lined up with the axes, so they're only moved and scaled
+        let scale = include.transform.scale;
+        for mut volume in scene.volumes {
+            volume.center = volume.center.transform_point(matrix);
+            volume.half_extents = Vec3 {
+                x: volume.half_extents.x * scale.x.abs(),
+                y: volume.half_extents.y * scale.y.abs(),
+                z: volume.half_extents.z * scale.z.abs(),
+            };
+            self.volumes.push(volume);
+        }
+
+        Ok(())
+    }
+
+    /// Adds an included file's tracks, pointed at what they animate once it's included
+    fn add_included_tracks(
+        &mut self,
+        include: &Include,
+        tracks: Vec<Track>,
+        names: &[String],
+    ) -> Result<(), SceneFileError> {
+        let matrix = include.transform.matrix();
+        // the included lights go after the ones already here
+        let light_count = self.lights.len();
+
+        for mut track in tracks {
+            match &mut track.target {
+                Target::Camera(_) => continue,
+                Target::Object { name, .. } => {
+                    if !include.wants(name) {
+                        continue;
+                    }
+                    // the keyframes set the whole transform, which would undo the include's
+                    if !include.transform.is_identity() {
+                        return Err(SceneFileError::AnimatedInclude(name.clone()));
+                    }
+                    *name = include.renamed(name);
+                }
+                Target::Light { index, property } => {
+                    if !include.lights {
+                        continue;
+                    }
+                    *index += light_count;
+
+                    for keyframe in &mut track.keyframes {
+                        if let Value::Vector(vector) = &mut keyframe.value {
+                            *vector = match property {
+                                LightProperty::Position => vector.transform_point(matrix),
+                                LightProperty::Direction => {
+                                    vector.transform_direction(matrix).normalize()
+                                }
+                                LightProperty::Intensity => *vector,
+                            };
+                        }
+                    }
+                }
+                // either a library material, or the material of an object or instance
+                Target::Material { name, .. } => {
+                    if names.contains(name) {
+                        if !include.wants(name) {
+                            continue;
+                        }
+                        *name = include.renamed(name);
+                    }
+                }
+            }
+
+            self.animation.tracks.push(track);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A folder for a test's files, removed when the test is over
+    struct TempFolder(PathBuf);
+
+    impl TempFolder {
+        fn new(name: &str) -> TempFolder {
+            let folder =
+                std::env::temp_dir().join(format!("include_test_{}_{}", std::process::id(), name));
+            std::fs::create_dir_all(&folder).unwrap();
+            TempFolder(folder)
+        }
+    }
+
+    impl Drop for TempFolder {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// An included file with a light and an animated sphere called ball
+    fn included(name: &str) -> TempFolder {
+        let folder = TempFolder::new(name);
+
+        std::fs::write(
+            folder.0.join("ball.json"),
+            r#"{
+                "objects": [{
+                    "name": "ball",
+                    "geometry": { "Sphere": { "center": { "x": 0, "y": 0, "z": 0 }, "radius": 1 } },
+                    "material": "white"
+                }],
+                "lights": [{ "Point": { "intensity": [1, 1, 1], "position": { "x": 0, "y": 1, "z": 0 } } }],
+                "animation": {
+                    "length": 20,
+                    "tracks": [{
+                        "target": { "Object": { "name": "ball", "property": "Position" } },
+                        "keyframes": [{ "time": 0, "value": { "Vector": { "x": 0, "y": 0, "z": 0 } } }]
+                    }, {
+                        "target": { "Light": { "index": 0, "property": "Position" } },
+                        "keyframes": [{ "time": 0, "value": { "Vector": { "x": 0, "y": 1, "z": 0 } } }]
+                    }, {
+                        "target": { "Camera": "Position" },
+                        "keyframes": [{ "time": 0, "value": { "Vector": { "x": 0, "y": 0, "z": 0 } } }]
+                    }]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        folder
+    }
+
+    fn load(folder: &Path, include: &str) -> Result<Scene, SceneFileError> {
+        let path = folder.join("scene.json");
+        std::fs::write(
+            &path,
+            format!(
+                r#"{{
+                    "lights": [{{ "Point": {{ "intensity": [1, 1, 1], "position": {{ "x": 0, "y": 0, "z": 0 }} }} }}],
+                    "includes": [{}]
+                }}"#,
+                include
+            ),
+        )
+        .unwrap();
+
+        Scene::load(path.to_str().unwrap())
+    }
+
+    #[test]
+    fn included_animation_follows_what_it_animates() {
+        let folder = included("follows");
+        let scene = load(
+            &folder.0,
+            r#"{ "path": "ball.json", "overrides": [{ "name": "ball", "rename": "moon" }] }"#,
+        )
+        .unwrap();
+
+        assert_eq!(scene.animation.length, 20.);
+
+        // the camera is left to the scene including it
+        let targets: Vec<&Target> = scene
+            .animation
+            .tracks
+            .iter()
+            .map(|track| &track.target)
+            .collect();
+        assert_eq!(targets.len(), 2);
+        assert!(matches!(targets[0], Target::Object { name, .. } if name == "moon"));
+        assert!(matches!(targets[1], Target::Light { index: 1, .. }));
+    }
+
+    #[test]
+    fn moving_an_animated_include_is_an_error() {
+        let folder = included("moved");
+        let error = load(
+            &folder.0,
+            r#"{ "path": "ball.json", "transform": {
+                "position": { "x": 5, "y": 0, "z": 0 },
+                "rotation": { "x": 0, "y": 0, "z": 0 },
+                "scale": { "x": 1, "y": 1, "z": 1 }
+            } }"#,
+        )
+        .err()
+        .unwrap();
+
+        match error {
+            SceneFileError::Include { error, .. } => {
+                assert!(matches!(*error, SceneFileError::AnimatedInclude(name) if name == "ball"))
+            }
+            error => panic!("expected an include error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn library_names_have_to_mean_the_same_thing() {
+        let temp_folder = TempFolder::new("collision");
+        let folder = &temp_folder.0;
+
+        let library = |colour: &str| {
+            format!(
+                r#"{{ "materials": [{{
+                    "name": "paint",
+                    "material": {{ "colour": {}, "specular": 10, "metallic": 0 }}
+                }}] }}"#,
+                colour
+            )
+        };
+        std::fs::write(folder.join("red.json"), library("[1, 0, 0]")).unwrap();
+        std::fs::write(folder.join("also_red.json"), library("[1, 0, 0]")).unwrap();
+        std::fs::write(folder.join("blue.json"), library("[0, 0, 1]")).unwrap();
+
+        // the same material twice is fine
+        let scene = load(
+            folder,
+            r#"{ "path": "red.json" }, { "path": "also_red.json" }"#,
+        )
+        .unwrap();
+        assert_eq!(scene.materials.len(), 1);
+
+        let error = load(folder, r#"{ "path": "red.json" }, { "path": "blue.json" }"#)
+            .err()
+            .unwrap();
+        match error {
+            SceneFileError::Include { path, error } => {
+                assert_eq!(path, "blue.json");
+                assert!(
+                    matches!(*error, SceneFileError::NameCollision("material", name) if name == "paint")
+                );
+            }
+            error => panic!("expected an include error, got {}", error),
+        }
+    }
+}
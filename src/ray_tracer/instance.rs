@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ray_tracer::{Geometry, MaterialRef, Motion, Transform};
+
+/// Geometry that's stored once and drawn by any number of instances.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Prototype {
+    pub name: String,
+    pub material: MaterialRef,
+    pub geometry: Geometry,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Instance {
+    pub name: String,
+    /// the name of the prototype this draws
+    pub prototype: String,
+    #[serde(default)]
+    pub transform: Transform,
+    /// uses the prototype's material if this is None
+    #[serde(default)]
+    pub material: Option<MaterialRef>,
+    /// how it moves while the shutter is open
+    #[serde(default)]
+    pub motion: Option<Motion>,
+}
+
+impl Instance {
+    pub fn material<'a>(&'a self, prototype: &'a Prototype) -> &'a MaterialRef {
+        self.material.as_ref().unwrap_or(&prototype.material)
+    }
+}
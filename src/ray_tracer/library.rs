@@ -0,0 +1,269 @@
+use std::fmt;
+
+use once_cell::sync::Lazy;
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::ray_tracer::{
+    Animation, Geometry, Light, Material, MaterialModel, MaterialTextures, MotionBlur, Object,
+    RayTracer, Scene, Transform, Vec3,
+};
+
+/// A material in the scene's library, shared by everything that names it
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LibraryMaterial {
+    pub name: String,
+    pub material: Material,
+}
+
+/// Either the name of a material in the library, or a material of its own.
+/// In the scene file this is just a string or a material.
+#[derive(Debug, Serialize, Clone)]
+#[serde(untagged)]
+pub enum MaterialRef {
+    Library(String),
+    Own(Box<Material>),
+}
+
+struct MaterialRefVisitor;
+
+impl<'de> Visitor<'de> for MaterialRefVisitor {
+    type Value = MaterialRef;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the name of a library material, or a material")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<MaterialRef, E> {
+        Ok(MaterialRef::Library(name.to_string()))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<MaterialRef, A::Error> {
+        Material::deserialize(de::value::MapAccessDeserializer::new(map)).map(MaterialRef::from)
+    }
+}
+
+// not untagged, as that loses the names of enum variants in formats like ron
+impl<'de> Deserialize<'de> for MaterialRef {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MaterialRef, D::Error> {
+        deserializer.deserialize_any(MaterialRefVisitor)
+    }
+}
+
+impl From<Material> for MaterialRef {
+    fn from(material: Material) -> MaterialRef {
+        MaterialRef::Own(Box::new(material))
+    }
+}
+
+/// Bright magenta, so a missing library material is obvious
+static MISSING_MATERIAL: Lazy<Material> = Lazy::new(|| Material {
+    model: MaterialModel::Phong,
+    colour: (1., 0., 1.),
+    specular: 0.,
+    metallic: 0.,
+    roughness: 1.,
+    emission: (0., 0., 0.),
+    emission_strength: 0.,
+    normal_map: None,
+    textures: MaterialTextures::default(),
+    graph: None,
+});
+
+impl Scene {
+    pub fn library_material(&self, name: &str) -> Option<&Material> {
+        self.materials
+            .iter()
+            .find(|material| material.name == name)
+            .map(|material| &material.material)
+    }
+
+    /// The material something is drawn with
+    pub fn material<'a>(&'a self, material: &'a MaterialRef) -> &'a Material {
+        match material {
+            MaterialRef::Library(name) => self.library_material(name).unwrap_or(&MISSING_MATERIAL),
+            MaterialRef::Own(material) => material,
+        }
+    }
+
+    /// A name that isn't in the library yet, based on the one given
+    pub fn unique_material_name(&self, name: &str) -> String {
+        if self.library_material(name).is_none() {
+            return name.to_string();
+        }
+
+        (2..)
+            .map(|number| format!("{} {}", name, number))
+            .find(|name| self.library_material(name).is_none())
+            .unwrap()
+    }
+
+    /// Renames a library material, and everything that uses it
+    pub fn rename_material(&mut self, old_name: &str, new_name: &str) {
+        let rename = |material: &mut MaterialRef| {
+            if let MaterialRef::Library(name) = material {
+                if name == old_name {
+                    *name = new_name.to_string();
+                }
+            }
+        };
+
+        self.objects
+            .iter_mut()
+            .for_each(|object| rename(&mut object.material));
+        self.prototypes
+            .iter_mut()
+            .for_each(|prototype| rename(&mut prototype.material));
+        self.instances
+            .iter_mut()
+            .filter_map(|instance| instance.material.as_mut())
+            .for_each(rename);
+
+        if let Some(material) = self
+            .materials
+            .iter_mut()
+            .find(|material| material.name == old_name)
+        {
+            material.name = new_name.to_string();
+        }
+    }
+
+    /// Removes a library material,
+    /// giving everything that used it a copy of its own so they still look the same
+    pub fn remove_material(&mut self, index: usize) {
+        let removed = self.materials.remove(index);
+
+        let inline = |material: &mut MaterialRef| {
+            if matches!(material, MaterialRef::Library(name) if *name == removed.name) {
+                *material = MaterialRef::Own(Box::new(removed.material.clone()));
+            }
+        };
+
+        self.objects
+            .iter_mut()
+            .for_each(|object| inline(&mut object.material));
+        self.prototypes
+            .iter_mut()
+            .for_each(|prototype| inline(&mut prototype.material));
+        self.instances
+            .iter_mut()
+            .filter_map(|instance| instance.material.as_mut())
+            .for_each(inline);
+    }
+
+    /// How many things use a library material
+    pub fn material_users(&self, name: &str) -> usize {
+        let uses = |material: &MaterialRef| matches!(material, MaterialRef::Library(other) if other == name);
+
+        self.objects
+            .iter()
+            .filter(|object| uses(&object.material))
+            .count()
+            + self
+                .prototypes
+                .iter()
+                .filter(|prototype| uses(&prototype.material))
+                .count()
+            + self
+                .instances
+                .iter()
+                .filter(|instance| instance.material.as_ref().map_or(false, uses))
+                .count()
+    }
+}
+
+/// Renders a sphere with the material on a grey floor, lit from the top left
+pub fn render_material_preview(material: &Material, size: u32) -> eframe::epaint::ColorImage {
+    let ray_tracer = RayTracer {
+        camera: Vec3 {
+            x: 0.,
+            y: 0.,
+            z: 3.2,
+        },
+        rotation: Vec3 {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        },
+        fov: 45.,
+        width: size,
+        height: size,
+        scene: Scene {
+            includes: vec![],
+            objects: vec![
+                Object {
+                    name: "preview".to_string(),
+                    material: MaterialRef::Own(Box::new(material.clone())),
+                    geometry: Geometry::Sphere {
+                        center: Vec3 {
+                            x: 0.,
+                            y: 0.,
+                            z: 0.,
+                        },
+                        radius: 1.,
+                    },
+                    transform: Transform::default(),
+                    motion: None,
+                },
+                // something for reflective materials to reflect
+                Object {
+                    name: "floor".to_string(),
+                    material: MaterialRef::Own(Box::new(Material {
+                        model: MaterialModel::Phong,
+                        colour: (0.6, 0.6, 0.6),
+                        specular: 10.,
+                        metallic: 0.,
+                        roughness: 0.,
+                        emission: (0., 0., 0.),
+                        emission_strength: 0.,
+                        normal_map: None,
+                        textures: MaterialTextures::default(),
+                        graph: None,
+                    })),
+                    geometry: Geometry::Plane {
+                        center: Vec3 {
+                            x: 0.,
+                            y: -1.,
+                            z: 0.,
+                        },
+                        normal: Vec3 {
+                            x: 0.,
+                            y: 1.,
+                            z: 0.,
+                        },
+                        size: 100.,
+                    },
+                    transform: Transform::default(),
+                    motion: None,
+                },
+            ],
+            materials: vec![],
+            prototypes: vec![],
+            instances: vec![],
+            lights: vec![Light::Point {
+                intensity: (0.9, 0.9, 0.9),
+                position: Vec3 {
+                    x: -3.,
+                    y: 3.,
+                    z: 3.,
+                },
+            }],
+            background_colour: (0.25, 0.25, 0.3),
+            ambient_light: (0.15, 0.15, 0.15),
+            reflection_limit: 2,
+            reflection_samples: 4,
+            emission_samples: 1,
+            fog: None,
+            volumes: vec![],
+            volume_steps: 1,
+            pixel_samples: 1,
+            motion_blur: MotionBlur::default(),
+            do_objects_spin: false,
+            animation: Animation::default(),
+        },
+    };
+
+    let mut image = eframe::epaint::ColorImage::new([0, 0], eframe::epaint::Color32::BLACK);
+    ray_tracer.rs_render(&mut image);
+    image
+}
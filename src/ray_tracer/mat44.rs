@@ -1,11 +1,18 @@
+use serde::{Deserialize, Serialize};
 use std::ops;
 
+use crate::ray_tracer::Vec3;
+
 pub enum Axis {
     X,
     Y,
     Z,
 }
 
+/// A 4x4 matrix used with row vectors,
+/// so `a * b` applies `a` first, then `b`,
+/// and the translation lives in the bottom row.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Mat44 {
     data: [[f64; 4]; 4],
 }
@@ -15,6 +22,15 @@ impl Mat44 {
         Mat44 { data }
     }
 
+    pub fn identity() -> Mat44 {
+        Mat44::new([
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
     pub fn create_rotation(axis: Axis, radians: f64) -> Mat44 {
         match axis {
             Axis::X => Mat44::new([
@@ -37,6 +53,96 @@ impl Mat44 {
             ]),
         }
     }
+
+    pub fn create_translation(translation: Vec3) -> Mat44 {
+        Mat44::new([
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [translation.x, translation.y, translation.z, 1.],
+        ])
+    }
+
+    pub fn create_scale(scale: Vec3) -> Mat44 {
+        Mat44::new([
+            [scale.x, 0., 0., 0.],
+            [0., scale.y, 0., 0.],
+            [0., 0., scale.z, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Places something at `eye` with its +z axis pointing at `target`.
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Mat44 {
+        let forward = (target - eye).normalize();
+        let right = (up * forward).normalize();
+        let up = forward * right;
+
+        Mat44::new([
+            [right.x, right.y, right.z, 0.],
+            [up.x, up.y, up.z, 0.],
+            [forward.x, forward.y, forward.z, 0.],
+            [eye.x, eye.y, eye.z, 1.],
+        ])
+    }
+
+    pub fn transpose(&self) -> Mat44 {
+        let mut data = [[0.; 4]; 4];
+
+        for (i, row) in data.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.data[j][i];
+            }
+        }
+
+        Mat44::new(data)
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting,
+    /// returns None if the matrix is singular (e.g. a scale of 0).
+    pub fn inverse(&self) -> Option<Mat44> {
+        let mut left = self.data;
+        let mut right = Mat44::identity().data;
+
+        for column in 0..4 {
+            // use the biggest value in this column to keep things stable
+            let pivot = (column..4)
+                .max_by(|a, b| {
+                    left[*a][column]
+                        .abs()
+                        .partial_cmp(&left[*b][column].abs())
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .unwrap();
+
+            if left[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+
+            left.swap(column, pivot);
+            right.swap(column, pivot);
+
+            let divisor = left[column][column];
+            for j in 0..4 {
+                left[column][j] /= divisor;
+                right[column][j] /= divisor;
+            }
+
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+
+                let factor = left[row][column];
+                for j in 0..4 {
+                    left[row][j] -= factor * left[column][j];
+                    right[row][j] -= factor * right[column][j];
+                }
+            }
+        }
+
+        Some(Mat44::new(right))
+    }
 }
 
 impl ops::Index<usize> for Mat44 {
@@ -45,3 +151,30 @@ impl ops::Index<usize> for Mat44 {
         &self.data[index]
     }
 }
+
+impl ops::IndexMut<usize> for Mat44 {
+    fn index_mut(&mut self, index: usize) -> &mut [f64; 4] {
+        &mut self.data[index]
+    }
+}
+
+impl ops::Mul for Mat44 {
+    type Output = Mat44;
+    fn mul(self, rhs: Mat44) -> Mat44 {
+        let mut data = [[0.; 4]; 4];
+
+        for (i, row) in data.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.data[i][k] * rhs.data[k][j]).sum();
+            }
+        }
+
+        Mat44::new(data)
+    }
+}
+
+impl ops::MulAssign for Mat44 {
+    fn mul_assign(&mut self, rhs: Mat44) {
+        *self = *self * rhs;
+    }
+}
@@ -0,0 +1,342 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ray_tracer::{deserialize_colour, ImageTexture, SolidTexture, Vec3};
+
+/// Everything a material graph can know about the point being shaded
+pub struct ShadingPoint {
+    /// in the object's own space, so textures move with it
+    pub local_point: Vec3,
+    pub uv: (f64, f64),
+    pub normal: Vec3,
+    /// from the point back towards the camera, or the last bounce
+    pub to_view: Vec3,
+    /// from meshes that have colours, which tint the material's colour
+    pub vertex_colour: Option<(f64, f64, f64)>,
+}
+
+/// Where an input of a node gets its value from
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum Input {
+    Value(f64),
+    Colour(#[serde(deserialize_with = "deserialize_colour")] (f64, f64, f64)),
+    /// the output of the node at this index
+    Node(usize),
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum MathOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    Minimum,
+    Maximum,
+}
+
+impl MathOp {
+    pub const ALL: [MathOp; 7] = [
+        MathOp::Add,
+        MathOp::Subtract,
+        MathOp::Multiply,
+        MathOp::Divide,
+        MathOp::Power,
+        MathOp::Minimum,
+        MathOp::Maximum,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MathOp::Add => "add",
+            MathOp::Subtract => "subtract",
+            MathOp::Multiply => "multiply",
+            MathOp::Divide => "divide",
+            MathOp::Power => "power",
+            MathOp::Minimum => "min",
+            MathOp::Maximum => "max",
+        }
+    }
+
+    fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            MathOp::Add => a + b,
+            MathOp::Subtract => a - b,
+            MathOp::Multiply => a * b,
+            MathOp::Divide => {
+                if b == 0. {
+                    0.
+                } else {
+                    a / b
+                }
+            }
+            MathOp::Power => a.max(0.).powf(b),
+            MathOp::Minimum => a.min(b),
+            MathOp::Maximum => a.max(b),
+        }
+    }
+}
+
+/// Every node outputs a colour, and single values are the same in all 3 channels
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub enum NodeKind {
+    /// u and v as red and green
+    Uv,
+    /// the point in the object's own space as red, green and blue
+    Position,
+    /// an image wrapped around the object with its uvs
+    Texture { texture: ImageTexture, scale: f64 },
+    /// a solid noise texture, from 0 to 1
+    Noise { texture: SolidTexture },
+    Math {
+        operation: MathOp,
+        a: Input,
+        b: Input,
+    },
+    /// a when the factor is 0, b when it's 1
+    Mix { a: Input, b: Input, factor: Input },
+    /// how much is reflected at the current angle, going to 1 at the edges
+    Fresnel { ior: f64 },
+}
+
+impl NodeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NodeKind::Uv => "uv",
+            NodeKind::Position => "position",
+            NodeKind::Texture { .. } => "texture",
+            NodeKind::Noise { .. } => "noise",
+            NodeKind::Math { .. } => "math",
+            NodeKind::Mix { .. } => "mix",
+            NodeKind::Fresnel { .. } => "fresnel",
+        }
+    }
+
+    /// The inputs that can be connected to other nodes, with their names
+    pub fn inputs(&self) -> Vec<(&'static str, &Input)> {
+        match self {
+            NodeKind::Math { operation: _, a, b } => vec![("a", a), ("b", b)],
+            NodeKind::Mix { a, b, factor } => vec![("a", a), ("b", b), ("factor", factor)],
+            _ => vec![],
+        }
+    }
+
+    pub fn inputs_mut(&mut self) -> Vec<(&'static str, &mut Input)> {
+        match self {
+            NodeKind::Math { operation: _, a, b } => vec![("a", a), ("b", b)],
+            NodeKind::Mix { a, b, factor } => vec![("a", a), ("b", b), ("factor", factor)],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct GraphNode {
+    pub kind: NodeKind,
+    /// where it is in the editor
+    pub position: (f32, f32),
+}
+
+/// The material inputs a graph can drive
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum BsdfInput {
+    Colour,
+    Specular,
+    Metallic,
+    Roughness,
+    Emission,
+}
+
+impl BsdfInput {
+    pub const ALL: [BsdfInput; 5] = [
+        BsdfInput::Colour,
+        BsdfInput::Specular,
+        BsdfInput::Metallic,
+        BsdfInput::Roughness,
+        BsdfInput::Emission,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BsdfInput::Colour => "colour",
+            BsdfInput::Specular => "specular",
+            BsdfInput::Metallic => "metallic",
+            BsdfInput::Roughness => "roughness",
+            BsdfInput::Emission => "emission",
+        }
+    }
+}
+
+/// Nodes wired together to calculate the inputs of a material.
+/// Inputs that aren't connected keep the material's own values.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct MaterialGraph {
+    pub nodes: Vec<GraphNode>,
+    #[serde(default)]
+    pub colour: Option<Input>,
+    #[serde(default)]
+    pub specular: Option<Input>,
+    #[serde(default)]
+    pub metallic: Option<Input>,
+    #[serde(default)]
+    pub roughness: Option<Input>,
+    #[serde(default)]
+    pub emission: Option<Input>,
+}
+
+impl MaterialGraph {
+    pub fn output(&self, input: BsdfInput) -> &Option<Input> {
+        match input {
+            BsdfInput::Colour => &self.colour,
+            BsdfInput::Specular => &self.specular,
+            BsdfInput::Metallic => &self.metallic,
+            BsdfInput::Roughness => &self.roughness,
+            BsdfInput::Emission => &self.emission,
+        }
+    }
+
+    pub fn output_mut(&mut self, input: BsdfInput) -> &mut Option<Input> {
+        match input {
+            BsdfInput::Colour => &mut self.colour,
+            BsdfInput::Specular => &mut self.specular,
+            BsdfInput::Metallic => &mut self.metallic,
+            BsdfInput::Roughness => &mut self.roughness,
+            BsdfInput::Emission => &mut self.emission,
+        }
+    }
+
+    /// If the node at from is already fed by the node at to,
+    /// so connecting from into to would make a loop
+    pub fn depends_on(&self, from: usize, to: usize) -> bool {
+        if from == to {
+            return true;
+        }
+
+        match self.nodes.get(from) {
+            Some(node) => node.kind.inputs().iter().any(|(_, input)| match input {
+                Input::Node(index) => self.depends_on(*index, to),
+                _ => false,
+            }),
+            None => false,
+        }
+    }
+
+    /// Removes a node, disconnecting anything that used it
+    pub fn remove_node(&mut self, index: usize) {
+        self.nodes.remove(index);
+
+        let fix = |input: &mut Input| {
+            if let Input::Node(other) = input {
+                if *other == index {
+                    *input = Input::Value(0.);
+                } else if *other > index {
+                    *other -= 1;
+                }
+            }
+        };
+
+        for node in &mut self.nodes {
+            for (_, input) in node.kind.inputs_mut() {
+                fix(input);
+            }
+        }
+
+        // outputs go back to the material's own value instead
+        for output in BsdfInput::ALL {
+            let output = self.output_mut(output);
+            if *output == Some(Input::Node(index)) {
+                *output = None;
+            } else if let Some(input) = output {
+                fix(input);
+            }
+        }
+    }
+
+    /// The value of an input, with each node calculated at most once
+    pub fn evaluate(
+        &self,
+        input: &Input,
+        shading: &ShadingPoint,
+        cache: &mut Vec<Option<(f64, f64, f64)>>,
+    ) -> (f64, f64, f64) {
+        self.evaluate_input(input, shading, cache, 0)
+    }
+
+    fn evaluate_input(
+        &self,
+        input: &Input,
+        shading: &ShadingPoint,
+        cache: &mut Vec<Option<(f64, f64, f64)>>,
+        depth: usize,
+    ) -> (f64, f64, f64) {
+        match input {
+            Input::Value(value) => (*value, *value, *value),
+            Input::Colour(colour) => *colour,
+            Input::Node(index) => self.evaluate_node(*index, shading, cache, depth),
+        }
+    }
+
+    fn evaluate_node(
+        &self,
+        index: usize,
+        shading: &ShadingPoint,
+        cache: &mut Vec<Option<(f64, f64, f64)>>,
+        depth: usize,
+    ) -> (f64, f64, f64) {
+        // a path longer than the number of nodes has to go round a loop,
+        // which the editor stops but a hand written scene might not
+        let node = match self.nodes.get(index) {
+            Some(node) if depth <= self.nodes.len() => node,
+            _ => return (0., 0., 0.),
+        };
+
+        if cache.len() < self.nodes.len() {
+            cache.resize(self.nodes.len(), None);
+        }
+        if let Some(value) = cache[index] {
+            return value;
+        }
+
+        let mut input = |input: &Input| self.evaluate_input(input, shading, cache, depth + 1);
+
+        let value = match &node.kind {
+            NodeKind::Uv => (shading.uv.0, shading.uv.1, 0.),
+            NodeKind::Position => (
+                shading.local_point.x,
+                shading.local_point.y,
+                shading.local_point.z,
+            ),
+            NodeKind::Texture { texture, scale } => {
+                texture.sample(shading.uv.0 * scale, shading.uv.1 * scale)
+            }
+            NodeKind::Noise { texture } => {
+                let value = texture.value(shading.local_point);
+                (value, value, value)
+            }
+            NodeKind::Math { operation, a, b } => {
+                let (a, b) = (input(a), input(b));
+                (
+                    operation.apply(a.0, b.0),
+                    operation.apply(a.1, b.1),
+                    operation.apply(a.2, b.2),
+                )
+            }
+            NodeKind::Mix { a, b, factor } => {
+                let (a, b, factor) = (input(a), input(b), input(factor));
+                (
+                    a.0 + (b.0 - a.0) * factor.0,
+                    a.1 + (b.1 - a.1) * factor.1,
+                    a.2 + (b.2 - a.2) * factor.2,
+                )
+            }
+            NodeKind::Fresnel { ior } => {
+                let f0 = ((ior - 1.) / (ior + 1.)).powi(2);
+                let cos_theta = shading.normal.dot(shading.to_view).clamp(0., 1.);
+                let value = f0 + (1. - f0) * (1. - cos_theta).powi(5);
+                (value, value, value)
+            }
+        };
+
+        cache[index] = Some(value);
+        value
+    }
+}
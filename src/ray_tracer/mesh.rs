@@ -0,0 +1,806 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ray_tracer::Vec3;
+
+/// The triangles of a mesh, with optional per vertex attributes.
+/// Attributes are either missing or have one value per position.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct MeshData {
+    pub positions: Vec<Vec3>,
+    pub triangles: Vec<[usize; 3]>,
+    /// worked out from the triangles if they're missing
+    #[serde(default)]
+    pub normals: Vec<Vec3>,
+    #[serde(default)]
+    pub uvs: Vec<(f64, f64)>,
+    #[serde(default)]
+    pub colours: Vec<(f64, f64, f64)>,
+}
+
+#[derive(Debug)]
+pub enum MeshError {
+    Empty,
+    /// a triangle uses a vertex that doesn't exist
+    BadIndex {
+        triangle: usize,
+        index: usize,
+    },
+    /// an attribute doesn't have one value per vertex
+    BadAttributeCount(&'static str),
+    NotFinite,
+}
+
+impl std::fmt::Display for MeshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MeshError::Empty => write!(f, "the mesh has no triangles"),
+            MeshError::BadIndex { triangle, index } => {
+                write!(
+                    f,
+                    "triangle {} uses vertex {} which doesn't exist",
+                    triangle, index
+                )
+            }
+            MeshError::BadAttributeCount(attribute) => {
+                write!(f, "there isn't one {} per vertex", attribute)
+            }
+            MeshError::NotFinite => write!(f, "the mesh has an infinite or NaN position"),
+        }
+    }
+}
+
+impl MeshData {
+    pub fn validate(&self) -> Result<(), MeshError> {
+        if self.triangles.is_empty() {
+            return Err(MeshError::Empty);
+        }
+
+        for (triangle, indices) in self.triangles.iter().enumerate() {
+            if let Some(index) = indices.iter().find(|&&index| index >= self.positions.len()) {
+                return Err(MeshError::BadIndex {
+                    triangle,
+                    index: *index,
+                });
+            }
+        }
+
+        let vertex_count = self.positions.len();
+        let counts = [
+            ("normal", self.normals.len()),
+            ("uv", self.uvs.len()),
+            ("colour", self.colours.len()),
+        ];
+        if let Some((attribute, _)) = counts
+            .iter()
+            .find(|(_, count)| *count != 0 && *count != vertex_count)
+        {
+            return Err(MeshError::BadAttributeCount(attribute));
+        }
+
+        if self.positions.iter().any(|position| {
+            !(position.x.is_finite() && position.y.is_finite() && position.z.is_finite())
+        }) {
+            return Err(MeshError::NotFinite);
+        }
+
+        Ok(())
+    }
+
+    /// The normal of each triangle, with a length of twice its area
+    fn face_normal(&self, triangle: usize) -> Vec3 {
+        let [a, b, c] = self.triangles[triangle];
+        let (a, b, c) = (self.positions[a], self.positions[b], self.positions[c]);
+
+        (b - a) * (c - a)
+    }
+
+    /// Smooth normals, where each triangle counts more the bigger it is
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![
+            Vec3 {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            };
+            self.positions.len()
+        ];
+
+        for triangle in 0..self.triangles.len() {
+            let normal = self.face_normal(triangle);
+
+            for index in self.triangles[triangle] {
+                normals[index] += normal;
+            }
+        }
+
+        self.normals = normals
+            .into_iter()
+            .map(|normal| normal.normalize())
+            .collect();
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Bounds {
+    fn empty() -> Bounds {
+        Bounds {
+            min: Vec3 {
+                x: f64::INFINITY,
+                y: f64::INFINITY,
+                z: f64::INFINITY,
+            },
+            max: Vec3 {
+                x: f64::NEG_INFINITY,
+                y: f64::NEG_INFINITY,
+                z: f64::NEG_INFINITY,
+            },
+        }
+    }
+
+    fn grow(&mut self, point: Vec3) {
+        self.min = Vec3 {
+            x: self.min.x.min(point.x),
+            y: self.min.y.min(point.y),
+            z: self.min.z.min(point.z),
+        };
+        self.max = Vec3 {
+            x: self.max.x.max(point.x),
+            y: self.max.y.max(point.y),
+            z: self.max.z.max(point.z),
+        };
+    }
+
+    /// Where the ray enters and leaves the box, if it does before max_t
+    fn slabs(&self, origin: Vec3, inverse_direction: Vec3, max_t: f64) -> Option<f64> {
+        let slab = |min: f64, max: f64, origin: f64, inverse: f64| {
+            // parallel to the slab, so it's either always or never inside it
+            if inverse.is_infinite() {
+                return if (min..=max).contains(&origin) {
+                    (f64::NEG_INFINITY, f64::INFINITY)
+                } else {
+                    (f64::INFINITY, f64::NEG_INFINITY)
+                };
+            }
+
+            let t1 = (min - origin) * inverse;
+            let t2 = (max - origin) * inverse;
+            (t1.min(t2), t1.max(t2))
+        };
+
+        let x = slab(self.min.x, self.max.x, origin.x, inverse_direction.x);
+        let y = slab(self.min.y, self.max.y, origin.y, inverse_direction.y);
+        let z = slab(self.min.z, self.max.z, origin.z, inverse_direction.z);
+
+        let t_near = x.0.max(y.0).max(z.0);
+        let t_far = x.1.min(y.1).min(z.1).min(max_t);
+
+        if t_near <= t_far {
+            Some(t_near)
+        } else {
+            None
+        }
+    }
+
+    /// 0 inside the box
+    fn distance_squared(&self, point: Vec3) -> f64 {
+        let axis = |value: f64, min: f64, max: f64| (min - value).max(value - max).max(0.);
+
+        axis(point.x, self.min.x, self.max.x).powi(2)
+            + axis(point.y, self.min.y, self.max.y).powi(2)
+            + axis(point.z, self.min.z, self.max.z).powi(2)
+    }
+}
+
+#[derive(Debug)]
+enum BvhNode {
+    /// triangles start..start + count of the bvh order
+    Leaf {
+        bounds: Bounds,
+        start: usize,
+        count: usize,
+    },
+    Branch {
+        bounds: Bounds,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &Bounds {
+        match self {
+            BvhNode::Leaf {
+                bounds,
+                start: _,
+                count: _,
+            } => bounds,
+            BvhNode::Branch {
+                bounds,
+                left: _,
+                right: _,
+            } => bounds,
+        }
+    }
+}
+
+/// A tree of boxes around the triangles, so a ray only tests the ones near it
+#[derive(Debug)]
+struct Bvh {
+    nodes: Vec<BvhNode>,
+    /// triangle indices, in the order the leaves use them
+    order: Vec<usize>,
+}
+
+impl Bvh {
+    const MAX_LEAF_SIZE: usize = 4;
+
+    fn new(data: &MeshData) -> Bvh {
+        let triangle_bounds = |triangle: usize| {
+            let mut bounds = Bounds::empty();
+            for index in data.triangles[triangle] {
+                bounds.grow(data.positions[index]);
+            }
+            bounds
+        };
+        let centroid = |triangle: usize| {
+            let [a, b, c] = data.triangles[triangle];
+            (data.positions[a] + data.positions[b] + data.positions[c]) / 3.
+        };
+
+        let mut bvh = Bvh {
+            nodes: vec![],
+            order: (0..data.triangles.len()).collect(),
+        };
+        let centroids: Vec<Vec3> = bvh
+            .order
+            .iter()
+            .map(|&triangle| centroid(triangle))
+            .collect();
+
+        bvh.build(0, data.triangles.len(), &triangle_bounds, &centroids);
+        bvh
+    }
+
+    /// Adds the node for order[start..end] and everything below it, returning its index
+    fn build(
+        &mut self,
+        start: usize,
+        end: usize,
+        triangle_bounds: &impl Fn(usize) -> Bounds,
+        centroids: &[Vec3],
+    ) -> usize {
+        let mut bounds = Bounds::empty();
+        let mut centroid_bounds = Bounds::empty();
+        for &triangle in &self.order[start..end] {
+            let triangle_bounds = triangle_bounds(triangle);
+            bounds.grow(triangle_bounds.min);
+            bounds.grow(triangle_bounds.max);
+            centroid_bounds.grow(centroids[triangle]);
+        }
+
+        let index = self.nodes.len();
+
+        if end - start <= Bvh::MAX_LEAF_SIZE {
+            self.nodes.push(BvhNode::Leaf {
+                bounds,
+                start,
+                count: end - start,
+            });
+            return index;
+        }
+
+        // split at the median along the longest axis
+        let extent = centroid_bounds.max - centroid_bounds.min;
+        let axis = |point: Vec3| {
+            if extent.x >= extent.y && extent.x >= extent.z {
+                point.x
+            } else if extent.y >= extent.z {
+                point.y
+            } else {
+                point.z
+            }
+        };
+
+        let middle = (start + end) / 2;
+        self.order[start..end].select_nth_unstable_by(middle - start, |a, b| {
+            axis(centroids[*a])
+                .partial_cmp(&axis(centroids[*b]))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        // filled in once the children exist
+        self.nodes.push(BvhNode::Leaf {
+            bounds,
+            start,
+            count: 0,
+        });
+
+        let left = self.build(start, middle, triangle_bounds, centroids);
+        let right = self.build(middle, end, triangle_bounds, centroids);
+        self.nodes[index] = BvhNode::Branch {
+            bounds,
+            left,
+            right,
+        };
+
+        index
+    }
+}
+
+/// The distance along the ray to the triangle and the (u, v) barycentrics of b and c,
+/// from the Möller–Trumbore algorithm
+fn intersect_triangle(
+    origin: Vec3,
+    direction: Vec3,
+    a: Vec3,
+    b: Vec3,
+    c: Vec3,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = direction * edge2;
+    let determinant = edge1.dot(p);
+
+    // parallel to the triangle
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+
+    let inverse = 1. / determinant;
+    let to_origin = origin - a;
+
+    // a little slack, so rays exactly along a shared edge don't miss both triangles
+    const EDGE_TOLERANCE: f64 = 1e-9;
+
+    let u = to_origin.dot(p) * inverse;
+    if !(-EDGE_TOLERANCE..=1. + EDGE_TOLERANCE).contains(&u) {
+        return None;
+    }
+
+    let q = to_origin * edge1;
+    let v = direction.dot(q) * inverse;
+    if v < -EDGE_TOLERANCE || u + v > 1. + EDGE_TOLERANCE {
+        return None;
+    }
+
+    Some((edge2.dot(q) * inverse, u, v))
+}
+
+/// The closest point on a triangle as barycentric weights of a, b and c,
+/// from Real-Time Collision Detection
+fn closest_on_triangle(point: Vec3, a: Vec3, b: Vec3, c: Vec3) -> (f64, f64, f64) {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = point - a;
+
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0. && d2 <= 0. {
+        return (1., 0., 0.);
+    }
+
+    let bp = point - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0. && d4 <= d3 {
+        return (0., 1., 0.);
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0. && d1 >= 0. && d3 <= 0. {
+        let v = d1 / (d1 - d3);
+        return (1. - v, v, 0.);
+    }
+
+    let cp = point - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0. && d5 <= d6 {
+        return (0., 0., 1.);
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0. && d2 >= 0. && d6 <= 0. {
+        let w = d2 / (d2 - d6);
+        return (1. - w, 0., w);
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0. && (d4 - d3) >= 0. && (d5 - d6) >= 0. {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return (0., 1. - w, w);
+    }
+
+    let denominator = 1. / (va + vb + vc);
+    let v = vb * denominator;
+    let w = vc * denominator;
+    (1. - v - w, v, w)
+}
+
+/// A point on one triangle of a mesh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriangleHit {
+    pub triangle: usize,
+    /// the barycentric weights of the triangle's vertices, in order
+    pub weights: (f64, f64, f64),
+}
+
+/// A triangle mesh that can be shared between objects.
+/// The vertices are relative to the geometry's center.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(try_from = "MeshData", into = "MeshData")]
+pub struct Mesh {
+    data: Arc<MeshData>,
+    bvh: Arc<Bvh>,
+}
+
+impl TryFrom<MeshData> for Mesh {
+    type Error = MeshError;
+
+    fn try_from(data: MeshData) -> Result<Mesh, MeshError> {
+        Mesh::new(data)
+    }
+}
+
+impl From<Mesh> for MeshData {
+    fn from(mesh: Mesh) -> MeshData {
+        (*mesh.data).clone()
+    }
+}
+
+impl Mesh {
+    pub fn new(mut data: MeshData) -> Result<Mesh, MeshError> {
+        data.validate()?;
+
+        if data.normals.is_empty() {
+            data.compute_normals();
+        }
+
+        let bvh = Bvh::new(&data);
+
+        Ok(Mesh {
+            data: Arc::new(data),
+            bvh: Arc::new(bvh),
+        })
+    }
+
+    pub fn data(&self) -> &MeshData {
+        &self.data
+    }
+
+    fn vertices(&self, triangle: usize) -> (Vec3, Vec3, Vec3) {
+        let [a, b, c] = self.data.triangles[triangle];
+        let positions = &self.data.positions;
+
+        (positions[a], positions[b], positions[c])
+    }
+
+    /// Every distance where the ray crosses a triangle after start, sorted.
+    /// If first_only, it's just the closest.
+    pub fn crossings(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        start: f64,
+        first_only: bool,
+    ) -> Vec<f64> {
+        self.hits(origin, direction, start, first_only)
+            .into_iter()
+            .map(|(t, _)| t)
+            .collect()
+    }
+
+    /// The closest triangle the ray hits after start
+    pub fn intersect(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        start: f64,
+    ) -> Option<(f64, TriangleHit)> {
+        self.hits(origin, direction, start, true).pop()
+    }
+
+    /// Like crossings, along with where on the triangles they are
+    fn hits(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        start: f64,
+        first_only: bool,
+    ) -> Vec<(f64, TriangleHit)> {
+        let inverse_direction = Vec3 {
+            x: 1. / direction.x,
+            y: 1. / direction.y,
+            z: 1. / direction.z,
+        };
+
+        let mut closest: Option<(f64, TriangleHit)> = None;
+        let mut hits = vec![];
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let max_t = match closest {
+                Some((t, _)) if first_only => t,
+                _ => f64::INFINITY,
+            };
+
+            match &self.bvh.nodes[index] {
+                node if node
+                    .bounds()
+                    .slabs(origin, inverse_direction, max_t)
+                    .is_none() => {}
+                BvhNode::Leaf {
+                    bounds: _,
+                    start: first,
+                    count,
+                } => {
+                    for &triangle in &self.bvh.order[*first..first + count] {
+                        let (a, b, c) = self.vertices(triangle);
+
+                        match intersect_triangle(origin, direction, a, b, c) {
+                            Some((t, u, v)) if t > start => {
+                                let hit = TriangleHit {
+                                    triangle,
+                                    weights: (1. - u - v, u, v),
+                                };
+
+                                if closest.map_or(true, |(closest, _)| t < closest) {
+                                    closest = Some((t, hit));
+                                }
+                                if !first_only {
+                                    hits.push((t, hit));
+                                }
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                BvhNode::Branch {
+                    bounds: _,
+                    left,
+                    right,
+                } => {
+                    stack.push(*left);
+                    stack.push(*right);
+                }
+            }
+        }
+
+        if first_only {
+            return closest.into_iter().collect();
+        }
+
+        hits.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        hits
+    }
+
+    /// The closest point on any triangle and its position, searching the nearest boxes first
+    pub fn closest_point(&self, point: Vec3) -> (TriangleHit, Vec3) {
+        let mut best = (
+            TriangleHit {
+                triangle: 0,
+                weights: (1., 0., 0.),
+            },
+            self.data.positions[self.data.triangles[0][0]],
+        );
+        let mut best_distance = f64::INFINITY;
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.bvh.nodes[index];
+            if node.bounds().distance_squared(point) >= best_distance {
+                continue;
+            }
+
+            match node {
+                BvhNode::Leaf {
+                    bounds: _,
+                    start,
+                    count,
+                } => {
+                    for &triangle in &self.bvh.order[*start..start + count] {
+                        let (a, b, c) = self.vertices(triangle);
+                        let weights = closest_on_triangle(point, a, b, c);
+                        let position = a * weights.0 + b * weights.1 + c * weights.2;
+                        let distance = (position - point).dot(position - point);
+
+                        if distance < best_distance {
+                            best_distance = distance;
+                            best = (TriangleHit { triangle, weights }, position);
+                        }
+                    }
+                }
+                BvhNode::Branch {
+                    bounds: _,
+                    left,
+                    right,
+                } => {
+                    // the closer child goes on top, so it's searched first
+                    let left_distance = self.bvh.nodes[*left].bounds().distance_squared(point);
+                    let right_distance = self.bvh.nodes[*right].bounds().distance_squared(point);
+
+                    if left_distance < right_distance {
+                        stack.push(*right);
+                        stack.push(*left);
+                    } else {
+                        stack.push(*left);
+                        stack.push(*right);
+                    }
+                }
+            }
+        }
+
+        best
+    }
+
+    /// Blends a per vertex attribute over a triangle
+    fn interpolate<T: Copy>(
+        &self,
+        hit: &TriangleHit,
+        values: &[T],
+        blend: impl Fn(T, T, T, (f64, f64, f64)) -> T,
+    ) -> T {
+        let [a, b, c] = self.data.triangles[hit.triangle];
+
+        blend(values[a], values[b], values[c], hit.weights)
+    }
+
+    pub fn normal_at(&self, hit: &TriangleHit) -> Vec3 {
+        self.interpolate(hit, &self.data.normals, |a, b, c, (u, v, w)| {
+            a * u + b * v + c * w
+        })
+        .normalize()
+    }
+
+    /// The normal of the closest triangle,
+    /// for when the ray's hit isn't known, like in csg
+    pub fn normal_at_point(&self, point: Vec3) -> Vec3 {
+        self.normal_at(&self.closest_point(point).0)
+    }
+
+    /// Negative inside, assuming the mesh is closed and the triangles wind outwards
+    pub fn signed_distance(&self, point: Vec3) -> f64 {
+        let (hit, position) = self.closest_point(point);
+        let offset = point - position;
+
+        // near edges this can pick the wrong side, but it's only used as a bound
+        if offset.dot(self.data.face_normal(hit.triangle)) < 0. {
+            -offset.length()
+        } else {
+            offset.length()
+        }
+    }
+
+    pub fn uv_at(&self, hit: &TriangleHit) -> Option<(f64, f64)> {
+        if self.data.uvs.is_empty() {
+            return None;
+        }
+
+        Some(self.interpolate(hit, &self.data.uvs, |a, b, c, (u, v, w)| {
+            (a.0 * u + b.0 * v + c.0 * w, a.1 * u + b.1 * v + c.1 * w)
+        }))
+    }
+
+    pub fn uv_at_point(&self, point: Vec3) -> Option<(f64, f64)> {
+        self.uv_at(&self.closest_point(point).0)
+    }
+
+    /// The direction of increasing u across the triangle
+    pub fn tangent_at(&self, hit: &TriangleHit) -> Option<Vec3> {
+        if self.data.uvs.is_empty() {
+            return None;
+        }
+
+        let [a, b, c] = self.data.triangles[hit.triangle];
+        let uvs = &self.data.uvs;
+        let (edge1, edge2) = (
+            self.data.positions[b] - self.data.positions[a],
+            self.data.positions[c] - self.data.positions[a],
+        );
+        let (du1, dv1) = (uvs[b].0 - uvs[a].0, uvs[b].1 - uvs[a].1);
+        let (du2, dv2) = (uvs[c].0 - uvs[a].0, uvs[c].1 - uvs[a].1);
+
+        let determinant = du1 * dv2 - du2 * dv1;
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+
+        Some(((edge1 * dv2 - edge2 * dv1) / determinant).normalize())
+    }
+
+    pub fn tangent_at_point(&self, point: Vec3) -> Option<Vec3> {
+        self.tangent_at(&self.closest_point(point).0)
+    }
+
+    pub fn colour_at(&self, hit: &TriangleHit) -> Option<(f64, f64, f64)> {
+        if self.data.colours.is_empty() {
+            return None;
+        }
+
+        Some(
+            self.interpolate(hit, &self.data.colours, |a, b, c, (u, v, w)| {
+                (
+                    a.0 * u + b.0 * v + c.0 * w,
+                    a.1 * u + b.1 * v + c.1 * w,
+                    a.2 * u + b.2 * v + c.2 * w,
+                )
+            }),
+        )
+    }
+
+    /// The middle and radius of a sphere around every vertex
+    pub fn bounding_sphere(&self) -> (Vec3, f64) {
+        let bounds = self.bvh.nodes[0].bounds();
+
+        (
+            (bounds.min + bounds.max) / 2.,
+            (bounds.max - bounds.min).length() / 2.,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec3(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    /// Two triangles making a square facing -z, with red, green and blue corners
+    fn square() -> Mesh {
+        Mesh::new(MeshData {
+            positions: vec![
+                vec3(-1., -1., 0.),
+                vec3(1., -1., 0.),
+                vec3(1., 1., 0.),
+                vec3(-1., 1., 0.),
+            ],
+            triangles: vec![[0, 2, 1], [0, 3, 2]],
+            colours: vec![(1., 0., 0.), (0., 1., 0.), (0., 0., 1.), (0., 1., 0.)],
+            ..MeshData::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn hits_carry_the_triangle_and_weights() {
+        let mesh = square();
+        let (distance, hit) = mesh
+            .intersect(vec3(0.5, -0.5, -2.), vec3(0., 0., 1.), 1e-6)
+            .unwrap();
+
+        assert!((distance - 2.).abs() < 1e-9);
+        assert_eq!(hit.triangle, 0);
+        let (u, v, w) = hit.weights;
+        assert!((u + v + w - 1.).abs() < 1e-9);
+
+        // halfway between the first and last vertex, a quarter of the way to the top right
+        let (r, g, b) = mesh.colour_at(&hit).unwrap();
+        assert!((r - 0.25).abs() < 1e-9 && (g - 0.5).abs() < 1e-9 && (b - 0.25).abs() < 1e-9);
+
+        // the same as searching for the closest triangle
+        let (closest, _) = mesh.closest_point(vec3(0.5, -0.5, 0.));
+        assert_eq!(closest.triangle, hit.triangle);
+        assert_eq!(mesh.normal_at(&hit), mesh.normal_at(&closest));
+    }
+
+    #[test]
+    fn misses_outside_the_triangles() {
+        let mesh = square();
+
+        assert!(mesh
+            .intersect(vec3(2., 0., -2.), vec3(0., 0., 1.), 1e-6)
+            .is_none());
+        assert!(mesh
+            .intersect(vec3(0., 0., -2.), vec3(0., 0., -1.), 1e-6)
+            .is_none());
+    }
+}
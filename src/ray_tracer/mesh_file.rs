@@ -0,0 +1,692 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::ray_tracer::{Mesh, MeshData, MeshError, Vec3};
+
+/// Files bigger than this are refused rather than read into memory
+pub const MAX_FILE_SIZE: u64 = 1 << 30;
+/// The most vertices, faces or triangles a file can have
+pub const MAX_ELEMENTS: usize = 1 << 26;
+
+#[derive(Debug)]
+pub enum MeshFileError {
+    Io(std::io::Error),
+    TooLarge {
+        what: &'static str,
+        size: u64,
+        limit: u64,
+    },
+    /// the file doesn't follow its format, usually because it's corrupt
+    Invalid(String),
+    UnknownFormat(String),
+    Mesh(MeshError),
+}
+
+impl fmt::Display for MeshFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshFileError::Io(error) => write!(f, "{}", error),
+            MeshFileError::TooLarge { what, size, limit } => {
+                write!(
+                    f,
+                    "the {} is {}, more than the limit of {}",
+                    what, size, limit
+                )
+            }
+            MeshFileError::Invalid(message) => write!(f, "invalid file: {}", message),
+            MeshFileError::UnknownFormat(extension) => {
+                write!(f, "can't load .{} files, only .ply and .stl", extension)
+            }
+            MeshFileError::Mesh(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> MeshFileError {
+    MeshFileError::Invalid(message.into())
+}
+
+fn check_count(what: &'static str, count: usize) -> Result<(), MeshFileError> {
+    if count > MAX_ELEMENTS {
+        Err(MeshFileError::TooLarge {
+            what,
+            size: count as u64,
+            limit: MAX_ELEMENTS as u64,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Loads a .ply or .stl file, picked by the extension
+pub fn load_mesh_file(path: &str) -> Result<Mesh, MeshFileError> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let parse = match extension.as_str() {
+        "ply" => parse_ply,
+        "stl" => parse_stl,
+        _ => return Err(MeshFileError::UnknownFormat(extension)),
+    };
+
+    let size = std::fs::metadata(path).map_err(MeshFileError::Io)?.len();
+    if size > MAX_FILE_SIZE {
+        return Err(MeshFileError::TooLarge {
+            what: "file size",
+            size,
+            limit: MAX_FILE_SIZE,
+        });
+    }
+
+    let bytes = std::fs::read(path).map_err(MeshFileError::Io)?;
+    Mesh::new(parse(&bytes)?).map_err(MeshFileError::Mesh)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Scalar, MeshFileError> {
+        match name {
+            "char" | "int8" => Ok(Scalar::I8),
+            "uchar" | "uint8" => Ok(Scalar::U8),
+            "short" | "int16" => Ok(Scalar::I16),
+            "ushort" | "uint16" => Ok(Scalar::U16),
+            "int" | "int32" => Ok(Scalar::I32),
+            "uint" | "uint32" => Ok(Scalar::U32),
+            "float" | "float32" => Ok(Scalar::F32),
+            "double" | "float64" => Ok(Scalar::F64),
+            _ => Err(invalid(format!("unknown property type {}", name))),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, Scalar::F32 | Scalar::F64)
+    }
+
+    /// The bytes have to be the right size
+    fn decode(self, bytes: &[u8], big_endian: bool) -> f64 {
+        let push = |bits: u64, byte: &u8| bits << 8 | *byte as u64;
+        let bits = if big_endian {
+            bytes.iter().fold(0, push)
+        } else {
+            bytes.iter().rev().fold(0, push)
+        };
+
+        match self {
+            Scalar::I8 => bits as u8 as i8 as f64,
+            Scalar::I16 => bits as u16 as i16 as f64,
+            Scalar::I32 => bits as u32 as i32 as f64,
+            Scalar::U8 | Scalar::U16 | Scalar::U32 => bits as f64,
+            Scalar::F32 => f32::from_bits(bits as u32) as f64,
+            Scalar::F64 => f64::from_bits(bits),
+        }
+    }
+}
+
+enum PropertyKind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// The data after the header, read one value at a time
+enum Body<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary {
+        bytes: &'a [u8],
+        position: usize,
+        big_endian: bool,
+    },
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, MeshFileError> {
+        match self {
+            Body::Ascii(tokens) => {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| invalid("the file ends before all the elements"))?;
+                token
+                    .parse()
+                    .map_err(|_| invalid(format!("{} isn't a number", token)))
+            }
+            Body::Binary {
+                bytes,
+                position,
+                big_endian,
+            } => {
+                let value = bytes
+                    .get(*position..*position + scalar.size())
+                    .ok_or_else(|| invalid("the file ends before all the elements"))?;
+                *position += scalar.size();
+                Ok(scalar.decode(value, *big_endian))
+            }
+        }
+    }
+
+    fn read_count(&mut self, scalar: Scalar) -> Result<usize, MeshFileError> {
+        let count = self.read(scalar)?;
+        if count < 0. || count.fract() != 0. {
+            return Err(invalid(format!("{} can't be a list length", count)));
+        }
+        Ok(count as usize)
+    }
+}
+
+/// Reads a PLY file, in ascii or either binary format.
+/// Vertex colours and uvs are kept, and other elements are skipped.
+pub fn parse_ply(bytes: &[u8]) -> Result<MeshData, MeshFileError> {
+    if !bytes.starts_with(b"ply") {
+        return Err(invalid("a ply file has to start with ply"));
+    }
+
+    let header_end = bytes
+        .windows(b"end_header".len())
+        .position(|window| window == b"end_header")
+        .ok_or_else(|| invalid("the header never ends"))?;
+    let body_start = bytes[header_end..]
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .map_or(bytes.len(), |offset| header_end + offset + 1);
+    let header = String::from_utf8_lossy(&bytes[..header_end]);
+
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+
+    for line in header.lines().skip(1) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["format", name, _version] => format = Some(*name),
+            ["element", name, count] => {
+                let count = count
+                    .parse()
+                    .map_err(|_| invalid(format!("{} isn't an element count", count)))?;
+                check_count("element count", count)?;
+
+                elements.push(Element {
+                    name: name.to_string(),
+                    count,
+                    properties: vec![],
+                });
+            }
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("a property comes before any element"))?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::List {
+                        count: Scalar::parse(count)?,
+                        item: Scalar::parse(item)?,
+                    },
+                }),
+            ["property", scalar, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("a property comes before any element"))?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::Scalar(Scalar::parse(scalar)?),
+                }),
+            ["comment", ..] | ["obj_info", ..] | [] => (),
+            _ => {
+                return Err(invalid(format!(
+                    "can't understand the header line {}",
+                    line
+                )))
+            }
+        }
+    }
+
+    let body_bytes = &bytes[body_start..];
+
+    // every value takes at least a digit and a space, or its size in binary,
+    // so a header asking for more than the file holds can be caught before allocating
+    let ascii = format == Some("ascii");
+    let smallest_body = elements.iter().fold(0usize, |total, element| {
+        let smallest_element: usize = element
+            .properties
+            .iter()
+            .map(|property| match (&property.kind, ascii) {
+                (_, true) => 2,
+                (PropertyKind::Scalar(scalar), false) => scalar.size(),
+                (PropertyKind::List { count, item: _ }, false) => count.size(),
+            })
+            .sum();
+        total.saturating_add(element.count.saturating_mul(smallest_element))
+    });
+    if smallest_body > body_bytes.len() + 1 {
+        return Err(invalid("the file is shorter than its header says"));
+    }
+
+    let mut body = match format {
+        Some("ascii") => Body::Ascii(
+            std::str::from_utf8(body_bytes)
+                .map_err(|_| invalid("the ascii data isn't text"))?
+                .split_ascii_whitespace(),
+        ),
+        Some("binary_little_endian") => Body::Binary {
+            bytes: body_bytes,
+            position: 0,
+            big_endian: false,
+        },
+        Some("binary_big_endian") => Body::Binary {
+            bytes: body_bytes,
+            position: 0,
+            big_endian: true,
+        },
+        Some(format) => return Err(invalid(format!("unknown format {}", format))),
+        None => return Err(invalid("the header doesn't give a format")),
+    };
+
+    let mut data = MeshData::default();
+    let mut values = vec![];
+
+    for element in &elements {
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|property| names.contains(&property.name.as_str()))
+        };
+
+        let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+        let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+        let colour = [
+            find(&["red", "r", "diffuse_red"]),
+            find(&["green", "g", "diffuse_green"]),
+            find(&["blue", "b", "diffuse_blue"]),
+        ];
+        let uv = [
+            find(&["u", "s", "texture_u"]),
+            find(&["v", "t", "texture_v"]),
+        ];
+        let indices = find(&["vertex_indices", "vertex_index"]);
+
+        for _ in 0..element.count {
+            values.clear();
+            let mut face = vec![];
+
+            for (index, property) in element.properties.iter().enumerate() {
+                match property.kind {
+                    PropertyKind::Scalar(scalar) => {
+                        let value = body.read(scalar)?;
+                        // integer colours go up to 255
+                        let is_colour = colour.contains(&Some(index));
+                        values.push(if is_colour && !scalar.is_float() {
+                            value / 255.
+                        } else {
+                            value
+                        });
+                    }
+                    PropertyKind::List { count, item } => {
+                        let length = body.read_count(count)?;
+                        for _ in 0..length {
+                            let value = body.read(item)?;
+                            if Some(index) == indices {
+                                if value < 0. || value.fract() != 0. {
+                                    return Err(invalid(format!("{} can't be a vertex", value)));
+                                }
+                                face.push(value as usize);
+                            }
+                        }
+                        values.push(0.);
+                    }
+                }
+            }
+
+            let get = |index: Option<usize>| index.map(|index| values[index]);
+
+            if element.name == "vertex" {
+                if let [Some(x), Some(y), Some(z)] = position.map(get) {
+                    data.positions.push(Vec3 { x, y, z });
+                } else {
+                    return Err(invalid("vertices need an x, y and z"));
+                }
+                if let [Some(x), Some(y), Some(z)] = normal.map(get) {
+                    data.normals.push(Vec3 { x, y, z });
+                }
+                if let [Some(red), Some(green), Some(blue)] = colour.map(get) {
+                    data.colours.push((red, green, blue));
+                }
+                if let [Some(u), Some(v)] = uv.map(get) {
+                    data.uvs.push((u, v));
+                }
+            } else if element.name == "face" {
+                if face.len() < 3 {
+                    return Err(invalid(format!(
+                        "a face has {} vertices, it needs at least 3",
+                        face.len()
+                    )));
+                }
+
+                // polygons are split into a fan of triangles
+                for i in 1..face.len() - 1 {
+                    data.triangles.push([face[0], face[i], face[i + 1]]);
+                }
+                check_count("triangle count", data.triangles.len())?;
+            }
+        }
+    }
+
+    Ok(data)
+}
+
+/// Reads a binary or ascii STL file.
+/// Every triangle gets its own vertices, so the edges of CAD models stay sharp.
+pub fn parse_stl(bytes: &[u8]) -> Result<MeshData, MeshFileError> {
+    // binary files can start with solid too, so the size is checked first
+    if bytes.len() >= 84 {
+        let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+
+        if bytes.len() as u64 == 84 + 50 * count as u64 {
+            return parse_binary_stl(&bytes[84..], count);
+        }
+    }
+
+    let text = String::from_utf8_lossy(bytes);
+    if text.trim_start().starts_with("solid") {
+        return parse_ascii_stl(&text);
+    }
+
+    if bytes.len() < 84 {
+        Err(invalid("the file is too short for a binary stl"))
+    } else {
+        Err(invalid(
+            "the file's size doesn't match the number of triangles in it",
+        ))
+    }
+}
+
+fn parse_binary_stl(bytes: &[u8], count: usize) -> Result<MeshData, MeshFileError> {
+    check_count("triangle count", count)?;
+
+    let mut data = MeshData::default();
+
+    // 12 bytes for the normal, 3 lots of 12 for the corners, then 2 that aren't used
+    for triangle in bytes.chunks_exact(50) {
+        for corner in triangle[12..48].chunks_exact(12) {
+            let value = |offset: usize| Scalar::F32.decode(&corner[offset..offset + 4], false);
+
+            data.positions.push(Vec3 {
+                x: value(0),
+                y: value(4),
+                z: value(8),
+            });
+        }
+
+        let start = data.positions.len() - 3;
+        data.triangles.push([start, start + 1, start + 2]);
+    }
+
+    Ok(data)
+}
+
+fn parse_ascii_stl(text: &str) -> Result<MeshData, MeshFileError> {
+    let mut data = MeshData::default();
+    let mut corners = 0;
+    let mut tokens = text.split_ascii_whitespace();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "vertex" => {
+                let mut value = || {
+                    let token = tokens
+                        .next()
+                        .ok_or_else(|| invalid("the file ends in the middle of a vertex"))?;
+                    token
+                        .parse()
+                        .map_err(|_| invalid(format!("{} isn't a number", token)))
+                };
+
+                data.positions.push(Vec3 {
+                    x: value()?,
+                    y: value()?,
+                    z: value()?,
+                });
+                corners += 1;
+            }
+            "endloop" => {
+                if corners != 3 {
+                    return Err(invalid(format!(
+                        "facet {} has {} vertices, not 3",
+                        data.triangles.len(),
+                        corners
+                    )));
+                }
+
+                let start = data.positions.len() - 3;
+                data.triangles.push([start, start + 1, start + 2]);
+                check_count("triangle count", data.triangles.len())?;
+                corners = 0;
+            }
+            // the names, keywords and facet normals
+            _ => (),
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_message(result: Result<MeshData, MeshFileError>) -> String {
+        match result {
+            Err(MeshFileError::Invalid(message)) => message,
+            Err(error) => panic!("expected an invalid file, got {}", error),
+            Ok(_) => panic!("expected an invalid file"),
+        }
+    }
+
+    const ASCII_PLY: &str = "ply
+format ascii 1.0
+comment a square with a colour on each corner
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+
+    #[test]
+    fn ascii_ply() {
+        let data = parse_ply(ASCII_PLY.as_bytes()).unwrap();
+
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(
+            data.positions[2],
+            Vec3 {
+                x: 1.,
+                y: 1.,
+                z: 0.
+            }
+        );
+        // integer colours are out of 255
+        assert_eq!(data.colours[0], (1., 0., 0.));
+        // the quad is split into a fan
+        assert_eq!(data.triangles, [[0, 1, 2], [0, 2, 3]]);
+        assert!(data.normals.is_empty() && data.uvs.is_empty());
+    }
+
+    #[test]
+    fn binary_ply() {
+        let mut bytes = b"ply
+format binary_big_endian 1.0
+element vertex 3
+property double x
+property double y
+property double z
+element face 1
+property list uchar uint vertex_indices
+end_header
+"
+        .to_vec();
+        for value in [0f64, 0., 0., 1., 0., 0., 0., 1., 0.] {
+            bytes.extend(value.to_be_bytes());
+        }
+        bytes.push(3);
+        for index in [0u32, 1, 2] {
+            bytes.extend(index.to_be_bytes());
+        }
+
+        let data = parse_ply(&bytes).unwrap();
+        assert_eq!(
+            data.positions[1],
+            Vec3 {
+                x: 1.,
+                y: 0.,
+                z: 0.
+            }
+        );
+        assert_eq!(data.triangles, [[0, 1, 2]]);
+    }
+
+    #[test]
+    fn malformed_ply() {
+        assert!(invalid_message(parse_ply(b"not a ply")).contains("start with ply"));
+        assert!(invalid_message(parse_ply(b"ply\nformat ascii 1.0\n")).contains("never ends"));
+
+        let without_face = ASCII_PLY.replace("4 0 1 2 3\n", "");
+        assert!(invalid_message(parse_ply(without_face.as_bytes())).contains("ends before"));
+
+        let line = ASCII_PLY.replace("4 0 1 2 3", "2 0 1");
+        assert!(invalid_message(parse_ply(line.as_bytes())).contains("at least 3"));
+
+        let negative = ASCII_PLY.replace("4 0 1 2 3", "3 0 -1 2");
+        assert!(invalid_message(parse_ply(negative.as_bytes())).contains("can't be a vertex"));
+
+        let word = ASCII_PLY.replace("1 1 0 0 0 255", "1 one 0 0 0 255");
+        assert!(invalid_message(parse_ply(word.as_bytes())).contains("isn't a number"));
+
+        let unknown = ASCII_PLY.replace("property float z", "property quaternion z");
+        assert!(parse_ply(unknown.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn ply_header_asking_for_too_much() {
+        let huge = ASCII_PLY.replace("element vertex 4", "element vertex 4000000000");
+        assert!(matches!(
+            parse_ply(huge.as_bytes()),
+            Err(MeshFileError::TooLarge { .. })
+        ));
+
+        let more = ASCII_PLY.replace("element vertex 4", "element vertex 400");
+        assert!(invalid_message(parse_ply(more.as_bytes())).contains("shorter than its header"));
+    }
+
+    const ASCII_STL: &str = "solid triangle
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 0 1 0
+  endloop
+endfacet
+endsolid triangle
+";
+
+    #[test]
+    fn ascii_stl() {
+        let data = parse_stl(ASCII_STL.as_bytes()).unwrap();
+
+        assert_eq!(data.positions.len(), 3);
+        assert_eq!(
+            data.positions[2],
+            Vec3 {
+                x: 0.,
+                y: 1.,
+                z: 0.
+            }
+        );
+        assert_eq!(data.triangles, [[0, 1, 2]]);
+    }
+
+    #[test]
+    fn binary_stl() {
+        // the header can start with solid too, which doesn't make it ascii
+        let mut bytes = b"solid".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend(2u32.to_le_bytes());
+        for offset in [0., 5.] {
+            let mut triangle = vec![0u8; 12];
+            for value in [0., 0., 0., 1., 0., 0., 0., 1., 0.] {
+                triangle.extend((value as f32 + offset).to_le_bytes());
+            }
+            triangle.extend([0, 0]);
+            bytes.extend(triangle);
+        }
+
+        let data = parse_stl(&bytes).unwrap();
+        assert_eq!(data.positions.len(), 6);
+        assert_eq!(
+            data.positions[4],
+            Vec3 {
+                x: 6.,
+                y: 5.,
+                z: 5.
+            }
+        );
+        assert_eq!(data.triangles, [[0, 1, 2], [3, 4, 5]]);
+    }
+
+    #[test]
+    fn malformed_stl() {
+        assert!(invalid_message(parse_stl(b"tiny")).contains("too short"));
+
+        let mut wrong_count = vec![0u8; 80];
+        wrong_count.extend(5u32.to_le_bytes());
+        wrong_count.extend([0u8; 50]);
+        assert!(invalid_message(parse_stl(&wrong_count)).contains("doesn't match"));
+
+        let missing_corner = ASCII_STL.replace("    vertex 0 1 0\n", "");
+        assert!(invalid_message(parse_stl(missing_corner.as_bytes())).contains("not 3"));
+
+        let cut_off = "solid cut\nfacet normal 0 0 1\nouter loop\nvertex 0 0";
+        assert!(invalid_message(parse_stl(cut_off.as_bytes())).contains("ends in the middle"));
+    }
+}
@@ -1,14 +1,66 @@
 pub mod vec3;
 pub use vec3::*;
+pub mod colour;
+pub use colour::*;
 pub mod ray;
 pub use ray::*;
 pub mod objects;
 pub use objects::*;
+pub mod instance;
+pub use instance::*;
+pub mod csg;
+pub use csg::*;
+pub mod sdf;
+pub use sdf::*;
+pub mod noise;
+pub use noise::*;
+pub mod heightfield;
+pub use heightfield::*;
+pub mod mesh;
+pub use mesh::*;
+pub mod mesh_file;
+pub use mesh_file::*;
+pub mod sampling;
+pub use sampling::*;
+pub mod brdf;
+pub use brdf::*;
+pub mod volume;
+pub use volume::*;
+pub mod texture;
+pub use texture::*;
+pub mod normal_map;
+pub use normal_map::*;
+pub mod material_graph;
+pub use material_graph::*;
+pub mod library;
+pub use library::*;
+pub mod gltf_import;
+pub use gltf_import::*;
+pub mod tessellate;
+pub use tessellate::*;
+pub mod export;
+pub use export::*;
 pub mod solver;
 pub use solver::*;
 pub mod scene;
 pub use scene::*;
+pub mod scene_file;
+pub use scene_file::*;
+pub mod include;
+pub use include::*;
+pub mod generator;
+pub use generator::*;
+pub mod animation;
+pub use animation::*;
+pub mod camera_path;
+pub use camera_path::*;
+pub mod sequence;
+pub use sequence::*;
+pub mod motion;
+pub use motion::*;
 pub mod mat44;
 pub use mat44::*;
+pub mod transform;
+pub use transform::*;
 pub mod ray_tracer;
 pub use ray_tracer::*;
@@ -0,0 +1,351 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ray_tracer::{spin_point, Instance, Object, RayTracer, Scene, Transform, Vec3};
+
+/// How something moves while the shutter is open
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum Motion {
+    /// in units a second, from where it is at the time of the frame
+    Velocity(Vec3),
+    /// where it is when the shutter opens and when it closes, blended in between
+    Transforms { open: Transform, close: Transform },
+}
+
+impl Motion {
+    pub const KINDS: [&'static str; 2] = ["velocity", "transforms"];
+
+    /// A motion of a kind that doesn't move anything yet,
+    /// for something at a transform
+    pub fn new(kind: &str, transform: &Transform) -> Motion {
+        match kind {
+            "transforms" => Motion::Transforms {
+                open: *transform,
+                close: *transform,
+            },
+            _ => Motion::Velocity(Vec3 {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            }),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Motion::Velocity(_) => "velocity",
+            Motion::Transforms { .. } => "transforms",
+        }
+    }
+
+    /// Where something at a transform is at a time, in seconds from the frame
+    pub fn transform_at(&self, transform: &Transform, time: f64, blur: &MotionBlur) -> Transform {
+        match self {
+            Motion::Velocity(velocity) => Transform {
+                position: transform.position + *velocity * time,
+                ..*transform
+            },
+            Motion::Transforms { open, close } => open.lerp(close, blur.shutter_fraction(time)),
+        }
+    }
+}
+
+/// The camera's shutter, which blurs anything that moves while it's open.
+/// Each ray is sent at a random time while it's open,
+/// so more samples per pixel give smoother blur.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub struct MotionBlur {
+    /// in seconds from the time of the frame
+    pub shutter_open: f64,
+    /// nothing is blurred if this is the same as shutter_open
+    pub shutter_close: f64,
+    /// only the position and rotation are used
+    #[serde(default)]
+    pub camera: Option<Motion>,
+}
+
+impl MotionBlur {
+    pub fn is_open(&self) -> bool {
+        self.shutter_close > self.shutter_open
+    }
+
+    /// A random time while the shutter is open
+    pub fn sample_time(&self) -> f64 {
+        if !self.is_open() {
+            return self.shutter_open;
+        }
+
+        self.shutter_open + rand::random::<f64>() * (self.shutter_close - self.shutter_open)
+    }
+
+    /// How far through the shutter being open a time is, from 0 to 1
+    pub fn shutter_fraction(&self, time: f64) -> f64 {
+        if !self.is_open() {
+            return 0.;
+        }
+
+        ((time - self.shutter_open) / (self.shutter_close - self.shutter_open)).clamp(0., 1.)
+    }
+}
+
+impl Transform {
+    /// Blends each part on its own, which is close enough for the short time a shutter is open
+    pub fn lerp(&self, other: &Transform, t: f64) -> Transform {
+        Transform {
+            position: self.position + (other.position - self.position) * t,
+            rotation: self.rotation + (other.rotation - self.rotation) * t,
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+}
+
+impl Object {
+    /// The transform at a time in seconds from the frame,
+    /// including its own motion and the scene spinning
+    pub fn transform_at(&self, scene: &Scene, time: f64) -> Transform {
+        let mut transform = match &self.motion {
+            Some(motion) => motion.transform_at(&self.transform, time, &scene.motion_blur),
+            None => self.transform,
+        };
+
+        if scene.do_objects_spin && time != 0. {
+            transform.position += self.spin_offset(&transform, Scene::SPIN_SPEED * time);
+        }
+
+        transform
+    }
+
+    /// How far spinning by an angle moves it through the world, when it's at a transform
+    pub fn spin_offset(&self, transform: &Transform, angle: f64) -> Vec3 {
+        let center = self.geometry.position().transform_point(transform.matrix());
+        spin_point(center, angle) - center
+    }
+}
+
+impl Instance {
+    /// The transform at a time in seconds from the frame,
+    /// including its own motion and the scene spinning
+    pub fn transform_at(&self, scene: &Scene, time: f64) -> Transform {
+        let mut transform = match &self.motion {
+            Some(motion) => motion.transform_at(&self.transform, time, &scene.motion_blur),
+            None => self.transform,
+        };
+
+        if scene.do_objects_spin && time != 0. {
+            transform.position = spin_point(transform.position, Scene::SPIN_SPEED * time);
+        }
+
+        transform
+    }
+}
+
+impl RayTracer {
+    /// Gives whatever the animation moves while the shutter is open a motion,
+    /// for a frame at a time in seconds.
+    /// Anything with a motion of its own keeps it.
+    /// This goes before the scene is spun to the frame, as the motions are spun here.
+    pub fn blur_animation(&mut self, time: f64) {
+        let blur = self.scene.motion_blur;
+        if !blur.is_open() || self.scene.animation.tracks.is_empty() {
+            return;
+        }
+
+        let (mut open, mut close) = (self.clone(), self.clone());
+        open.animate(time + blur.shutter_open);
+        close.animate(time + blur.shutter_close);
+
+        // spinning while the shutter is open is added on by transform_at,
+        // so these only need spinning to the frame
+        if self.scene.do_objects_spin {
+            open.scene.spin(Scene::SPIN_SPEED * time);
+            close.scene.spin(Scene::SPIN_SPEED * time);
+        }
+
+        let motion = |open: Transform, close: Transform| {
+            (open != close).then(|| Motion::Transforms { open, close })
+        };
+
+        let objects = open.scene.objects.iter().zip(&close.scene.objects);
+        for (object, (open, close)) in self.scene.objects.iter_mut().zip(objects) {
+            if object.motion.is_none() {
+                object.motion = motion(open.transform, close.transform);
+            }
+        }
+
+        let instances = open.scene.instances.iter().zip(&close.scene.instances);
+        for (instance, (open, close)) in self.scene.instances.iter_mut().zip(instances) {
+            if instance.motion.is_none() {
+                instance.motion = motion(open.transform, close.transform);
+            }
+        }
+
+        let camera = |ray_tracer: &RayTracer| Transform {
+            position: ray_tracer.camera,
+            rotation: ray_tracer.rotation,
+            ..Transform::default()
+        };
+        if self.scene.motion_blur.camera.is_none() {
+            self.scene.motion_blur.camera = motion(camera(&open), camera(&close));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_tracer::SceneFormat;
+
+    /// Two instances moving along x, one with a velocity of its own, in a spinning scene
+    fn ray_tracer() -> RayTracer {
+        let text = r#"{
+            "instances": [
+                { "name": "animated", "prototype": "ball" },
+                { "name": "moving", "prototype": "ball", "motion": { "Velocity": { "x": 0, "y": 1, "z": 0 } } }
+            ],
+            "motion_blur": { "shutter_open": 0, "shutter_close": 0.1 },
+            "do_objects_spin": true,
+            "animation": {
+                "length": 10,
+                "tracks": [{
+                    "target": { "Object": { "name": "animated", "property": "Position" } },
+                    "keyframes": [
+                        { "time": 0, "value": { "Vector": { "x": 1, "y": 0, "z": 0 } }, "curve": "Linear" },
+                        { "time": 10, "value": { "Vector": { "x": 11, "y": 0, "z": 0 } }, "curve": "Linear" }
+                    ]
+                }, {
+                    "target": { "Object": { "name": "moving", "property": "Position" } },
+                    "keyframes": [
+                        { "time": 0, "value": { "Vector": { "x": 1, "y": 0, "z": 0 } }, "curve": "Linear" },
+                        { "time": 10, "value": { "Vector": { "x": 11, "y": 0, "z": 0 } }, "curve": "Linear" }
+                    ]
+                }]
+            }
+        }"#;
+        let origin = Vec3 {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        };
+
+        RayTracer {
+            camera: origin,
+            rotation: origin,
+            fov: 60.,
+            width: 1,
+            height: 1,
+            scene: Scene::from_text(text, SceneFormat::Json).unwrap(),
+        }
+    }
+
+    #[test]
+    fn animation_blur_is_spun_with_the_scene() {
+        let frame = ray_tracer().at_time(2.);
+        let scene = &frame.scene;
+        let instance = &scene.instances[0];
+
+        let (open, close) = match instance.motion {
+            Some(Motion::Transforms { open, close }) => (open, close),
+            motion => panic!("expected transforms, got {:?}", motion),
+        };
+
+        // where the animation has it, spun round to the frame
+        let spun = |x: f64| {
+            let position = Vec3 { x, y: 0., z: 0. };
+            spin_point(position, Scene::SPIN_SPEED * 2.)
+        };
+        assert!((open.position - spun(3.)).length() < 1e-9);
+        assert!((close.position - spun(3.1)).length() < 1e-9);
+
+        // so it's drawn where the frame has it when the shutter opens
+        let transform = instance.transform_at(scene, 0.);
+        assert!((transform.position - instance.transform.position).length() < 1e-9);
+    }
+
+    #[test]
+    fn own_motion_is_kept() {
+        let frame = ray_tracer().at_time(2.);
+
+        assert!(matches!(
+            frame.scene.instances[1].motion,
+            Some(Motion::Velocity(_))
+        ));
+    }
+
+    /// A scene with a sphere placed by its geometry and one placed by its transform
+    fn placed_spheres() -> Scene {
+        let text = r#"{
+            "objects": [{
+                "name": "by geometry",
+                "geometry": { "Sphere": { "center": { "x": 2, "y": 0, "z": 0 }, "radius": 1 } },
+                "material": { "colour": [1, 1, 1], "specular": 10, "metallic": 0 }
+            }, {
+                "name": "by transform",
+                "geometry": { "Sphere": { "center": { "x": 0, "y": 0, "z": 0 }, "radius": 1 } },
+                "material": { "colour": [1, 1, 1], "specular": 10, "metallic": 0 },
+                "transform": {
+                    "position": { "x": 2, "y": 0, "z": 0 },
+                    "rotation": { "x": 0.5, "y": 0, "z": 0.3 },
+                    "scale": { "x": 3, "y": 3, "z": 3 }
+                }
+            }],
+            "do_objects_spin": true
+        }"#;
+
+        Scene::from_text(text, SceneFormat::Json).unwrap()
+    }
+
+    /// Where the middle of an object's geometry is in the world
+    fn world_center(object: &Object, transform: &Transform) -> Vec3 {
+        object
+            .geometry
+            .position()
+            .transform_point(transform.matrix())
+    }
+
+    #[test]
+    fn objects_spin_around_the_world_axis() {
+        let mut scene = placed_spheres();
+        let angle = 1.;
+        let spun = spin_point(
+            Vec3 {
+                x: 2.,
+                y: 0.,
+                z: 0.,
+            },
+            angle,
+        );
+
+        let before = scene.objects[1].transform;
+        scene.spin(angle);
+
+        for object in &scene.objects {
+            let center = world_center(object, &object.transform);
+            assert!(
+                (center - spun).length() < 1e-9,
+                "{} is at {:?}",
+                object.name,
+                center
+            );
+        }
+
+        // only moved, not turned
+        let after = scene.objects[1].transform;
+        assert_eq!(after.rotation, before.rotation);
+        assert_eq!(after.scale, before.scale);
+        assert!(scene.objects[0].transform.is_identity());
+    }
+
+    #[test]
+    fn spinning_while_the_shutter_is_open_matches_spinning_the_scene() {
+        let scene = placed_spheres();
+        let time = 0.3;
+
+        let mut spun = scene.clone();
+        spun.spin(Scene::SPIN_SPEED * time);
+
+        for (object, spun) in scene.objects.iter().zip(&spun.objects) {
+            let center = world_center(object, &object.transform_at(&scene, time));
+            let expected = world_center(spun, &spun.transform);
+            assert!((center - expected).length() < 1e-9, "{}", object.name);
+        }
+    }
+}
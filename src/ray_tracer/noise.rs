@@ -0,0 +1,215 @@
+use crate::ray_tracer::Vec3;
+
+/// Mixes integer coordinates and a seed into a random looking number
+fn hash(x: i64, y: i64, z: i64, seed: u32) -> u32 {
+    let mut hash = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f)
+        ^ seed.wrapping_mul(0x1656_67b1);
+
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2c1b_3c6d);
+    hash ^= hash >> 12;
+    hash = hash.wrapping_mul(0x297a_2d39);
+    hash ^= hash >> 15;
+    hash
+}
+
+/// The gradient at a lattice point, one of the 12 edges of a cube like the original
+fn gradient(x: i64, y: i64, z: i64, seed: u32) -> Vec3 {
+    let (a, b) = match hash(x, y, z, seed) % 4 {
+        0 => (1., 1.),
+        1 => (-1., 1.),
+        2 => (1., -1.),
+        _ => (-1., -1.),
+    };
+
+    match hash(x, y, z, seed.wrapping_add(1)) % 3 {
+        0 => Vec3 { x: a, y: b, z: 0. },
+        1 => Vec3 { x: a, y: 0., z: b },
+        _ => Vec3 { x: 0., y: a, z: b },
+    }
+}
+
+/// Smooths the interpolation, so the noise has no visible grid
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// Gradient noise between about -1 and 1
+pub fn perlin(point: Vec3, seed: u32) -> f64 {
+    let cell = (
+        point.x.floor() as i64,
+        point.y.floor() as i64,
+        point.z.floor() as i64,
+    );
+    let local = Vec3 {
+        x: point.x - point.x.floor(),
+        y: point.y - point.y.floor(),
+        z: point.z - point.z.floor(),
+    };
+
+    // the dot product of each corner's gradient with the offset from that corner
+    let corner = |dx: i64, dy: i64, dz: i64| {
+        gradient(cell.0 + dx, cell.1 + dy, cell.2 + dz, seed).dot(Vec3 {
+            x: local.x - dx as f64,
+            y: local.y - dy as f64,
+            z: local.z - dz as f64,
+        })
+    };
+
+    let (u, v, w) = (fade(local.x), fade(local.y), fade(local.z));
+
+    lerp(
+        lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        ),
+        lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        ),
+        w,
+    )
+}
+
+/// Fractal noise, adding octaves of perlin noise at double the frequency and half the amplitude
+pub fn fbm(point: Vec3, seed: u32, octaves: u32) -> f64 {
+    let mut total = 0.;
+    let mut amplitude = 1.;
+    let mut max_amplitude = 0.;
+    let mut frequency = 1.;
+
+    for octave in 0..octaves {
+        total += perlin(point * frequency, seed.wrapping_add(octave)) * amplitude;
+        max_amplitude += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.;
+    }
+
+    if max_amplitude > 0. {
+        total / max_amplitude
+    } else {
+        0.
+    }
+}
+
+/// Simplex noise between about -1 and 1,
+/// smoother than perlin and without the grid lines
+pub fn simplex(point: Vec3, seed: u32) -> f64 {
+    // skewing the space turns the simplices into cubes, so finding the cell is easy
+    const SKEW: f64 = 1. / 3.;
+    const UNSKEW: f64 = 1. / 6.;
+
+    let skew = (point.x + point.y + point.z) * SKEW;
+    let cell = (
+        (point.x + skew).floor() as i64,
+        (point.y + skew).floor() as i64,
+        (point.z + skew).floor() as i64,
+    );
+
+    let unskew = (cell.0 + cell.1 + cell.2) as f64 * UNSKEW;
+    let offset = Vec3 {
+        x: point.x - (cell.0 as f64 - unskew),
+        y: point.y - (cell.1 as f64 - unskew),
+        z: point.z - (cell.2 as f64 - unskew),
+    };
+
+    // which of the 6 tetrahedra in the cube the point is in,
+    // as the order the axes are stepped along
+    let (second, third) = if offset.x >= offset.y {
+        if offset.y >= offset.z {
+            ((1, 0, 0), (1, 1, 0))
+        } else if offset.x >= offset.z {
+            ((1, 0, 0), (1, 0, 1))
+        } else {
+            ((0, 0, 1), (1, 0, 1))
+        }
+    } else if offset.y < offset.z {
+        ((0, 0, 1), (0, 1, 1))
+    } else if offset.x < offset.z {
+        ((0, 1, 0), (0, 1, 1))
+    } else {
+        ((0, 1, 0), (1, 1, 0))
+    };
+
+    [(0, 0, 0), second, third, (1, 1, 1)]
+        .iter()
+        .enumerate()
+        .map(|(index, (dx, dy, dz))| {
+            let corner = Vec3 {
+                x: offset.x - *dx as f64 + index as f64 * UNSKEW,
+                y: offset.y - *dy as f64 + index as f64 * UNSKEW,
+                z: offset.z - *dz as f64 + index as f64 * UNSKEW,
+            };
+
+            let falloff = 0.6 - corner.dot(corner);
+            if falloff <= 0. {
+                return 0.;
+            }
+
+            falloff.powi(4) * gradient(cell.0 + dx, cell.1 + dy, cell.2 + dz, seed).dot(corner)
+        })
+        .sum::<f64>()
+        * 32.
+}
+
+/// Cellular noise, the distance to the closest of a random point in each cell.
+/// It's 0 at the points, and usually not more than 1.
+pub fn worley(point: Vec3, seed: u32) -> f64 {
+    let cell = (
+        point.x.floor() as i64,
+        point.y.floor() as i64,
+        point.z.floor() as i64,
+    );
+
+    let mut closest = f64::INFINITY;
+
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            for dz in -1..=1 {
+                let (x, y, z) = (cell.0 + dx, cell.1 + dy, cell.2 + dz);
+
+                let random =
+                    |offset: u32| hash(x, y, z, seed.wrapping_add(offset)) as f64 / u32::MAX as f64;
+                let feature = Vec3 {
+                    x: x as f64 + random(0),
+                    y: y as f64 + random(1),
+                    z: z as f64 + random(2),
+                };
+
+                closest = closest.min((feature - point).length());
+            }
+        }
+    }
+
+    closest
+}
+
+/// Like fbm but with the absolute value of each octave, giving sharp creases.
+/// It's between 0 and about 1.
+pub fn turbulence(point: Vec3, seed: u32, octaves: u32) -> f64 {
+    let mut total = 0.;
+    let mut amplitude = 1.;
+    let mut max_amplitude = 0.;
+    let mut frequency = 1.;
+
+    for octave in 0..octaves {
+        total += perlin(point * frequency, seed.wrapping_add(octave)).abs() * amplitude;
+        max_amplitude += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.;
+    }
+
+    if max_amplitude > 0. {
+        total / max_amplitude
+    } else {
+        0.
+    }
+}
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ray_tracer::{fbm, CachedTransform, ImageTexture, Vec3};
+
+/// Changes the normal used for shading, without changing the shape
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub enum NormalMap {
+    /// A tangent space normal map, where blue is straight out of the surface,
+    /// and red and green lean it towards u and v.
+    Image {
+        texture: ImageTexture,
+        strength: f64,
+    },
+    /// Bumps from fractal noise in the object's own space, so they move with it
+    Noise {
+        frequency: f64,
+        strength: f64,
+        seed: u32,
+        octaves: u32,
+    },
+}
+
+impl NormalMap {
+    /// The normal and tangent are in world space, and the tangent has to be perpendicular
+    pub fn apply(
+        &self,
+        normal: Vec3,
+        tangent: Vec3,
+        uv: (f64, f64),
+        local_point: Vec3,
+        transform: &CachedTransform,
+    ) -> Vec3 {
+        match self {
+            NormalMap::Image { texture, strength } => {
+                let (r, g, b) = texture.sample(uv.0, uv.1);
+                let bitangent = normal * tangent;
+
+                // from 0 to 1 colours to -1 to 1 directions
+                let x = (r * 2. - 1.) * strength;
+                let y = (g * 2. - 1.) * strength;
+                let z = b * 2. - 1.;
+
+                (tangent * x + bitangent * y + normal * z).normalize()
+            }
+            NormalMap::Noise {
+                frequency,
+                strength,
+                seed,
+                octaves,
+            } => {
+                let height = |point: Vec3| fbm(point * *frequency, *seed, *octaves);
+                let h = 1e-3 / frequency.max(1e-6);
+
+                // central differences along each axis
+                let slope = |x: f64, y: f64, z: f64| {
+                    let offset = Vec3 { x, y, z } * h;
+                    (height(local_point + offset) - height(local_point - offset)) / (2. * h)
+                };
+                let local_gradient = Vec3 {
+                    x: slope(1., 0., 0.),
+                    y: slope(0., 1., 0.),
+                    z: slope(0., 0., 1.),
+                };
+
+                // a gradient changes space like a normal does
+                let gradient = if transform.is_identity() {
+                    local_gradient
+                } else {
+                    transform.normal_to_world(local_gradient) * local_gradient.length()
+                };
+
+                // only the part along the surface tilts the normal
+                let along_surface = gradient - normal * normal.dot(gradient);
+
+                (normal - along_surface * (*strength / *frequency)).normalize()
+            }
+        }
+    }
+}
@@ -1,11 +1,139 @@
-use crate::ray_tracer::{solve_quadratic, Ray, Vec3};
+use crate::ray_tracer::{
+    deserialize_colour, pair_up, solve_quadratic, solve_quartic, BsdfInput, CachedTransform,
+    CsgOperation, Heightmap, MaterialGraph, MaterialRef, MaterialTextures, Mesh, Motion, NormalMap,
+    Orientation, Ray, SdfNode, ShadingPoint, Span, Transform, TriangleHit, Vec3,
+};
 use serde::{Deserialize, Serialize};
 
+/// How a material reacts to light
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum MaterialModel {
+    /// lambert diffuse and phong highlights, with specular as the exponent,
+    /// and reflections blended in by metallic
+    Phong,
+    /// metallic / roughness like most other tools, where specular isn't used
+    Pbr,
+}
+
+impl Default for MaterialModel {
+    fn default() -> MaterialModel {
+        MaterialModel::Phong
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Material {
+    #[serde(default)]
+    pub model: MaterialModel,
+    #[serde(deserialize_with = "deserialize_colour")]
     pub colour: (f64, f64, f64),
     pub specular: f64,
     pub metallic: f64,
+    /// how blurry reflections are, 0 is a perfect mirror
+    #[serde(default)]
+    pub roughness: f64,
+    /// the colour of light it gives off, which also lights up the rest of the scene
+    #[serde(default, deserialize_with = "deserialize_colour")]
+    pub emission: (f64, f64, f64),
+    #[serde(default)]
+    pub emission_strength: f64,
+    #[serde(default)]
+    pub normal_map: Option<NormalMap>,
+    #[serde(default)]
+    pub textures: MaterialTextures,
+    /// nodes that calculate the inputs, on top of the textures
+    #[serde(default)]
+    pub graph: Option<MaterialGraph>,
+}
+
+/// What a material is like at one point, which is all the shading needs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Surface {
+    pub model: MaterialModel,
+    pub colour: (f64, f64, f64),
+    pub specular: f64,
+    pub metallic: f64,
+    pub roughness: f64,
+    pub emission: (f64, f64, f64),
+    pub emission_strength: f64,
+}
+
+impl Material {
+    /// The material at a point, with any textures and graph applied
+    pub fn evaluate(&self, shading: &ShadingPoint) -> Surface {
+        let mut surface = Surface {
+            model: self.model,
+            colour: self.colour,
+            specular: self.specular,
+            metallic: self.metallic,
+            roughness: self.roughness,
+            emission: self.emission,
+            emission_strength: self.emission_strength,
+        };
+
+        let textures = &self.textures;
+        let point = shading.local_point;
+
+        if let Some(colour) = &textures.colour {
+            surface.colour = colour.colour(point);
+        }
+        if let Some(specular) = &textures.specular {
+            surface.specular = specular.value(point);
+        }
+        if let Some(metallic) = &textures.metallic {
+            surface.metallic = metallic.value(point);
+        }
+        if let Some(roughness) = &textures.roughness {
+            surface.roughness = roughness.value(point);
+        }
+
+        if let Some(graph) = &self.graph {
+            let mut cache = Vec::new();
+            let mut output = |input: BsdfInput| {
+                graph
+                    .output(input)
+                    .as_ref()
+                    .map(|input| graph.evaluate(input, shading, &mut cache))
+            };
+            let average = |colour: (f64, f64, f64)| (colour.0 + colour.1 + colour.2) / 3.;
+
+            if let Some(colour) = output(BsdfInput::Colour) {
+                surface.colour = colour;
+            }
+            if let Some(specular) = output(BsdfInput::Specular) {
+                surface.specular = average(specular);
+            }
+            if let Some(metallic) = output(BsdfInput::Metallic) {
+                surface.metallic = average(metallic).clamp(0., 1.);
+            }
+            if let Some(roughness) = output(BsdfInput::Roughness) {
+                surface.roughness = average(roughness).clamp(0., 1.);
+            }
+            if let Some(emission) = output(BsdfInput::Emission) {
+                surface.emission = emission;
+            }
+        }
+
+        if let Some(tint) = shading.vertex_colour {
+            surface.colour = (
+                surface.colour.0 * tint.0,
+                surface.colour.1 * tint.1,
+                surface.colour.2 * tint.2,
+            );
+        }
+
+        surface
+    }
+
+    /// If it gives off light anywhere, from its own emission or the graph's
+    pub fn is_emissive(&self) -> bool {
+        let is_graph_emissive = self
+            .graph
+            .as_ref()
+            .map_or(false, |graph| graph.output(BsdfInput::Emission).is_some());
+
+        self.emission_strength > 0. && (self.emission != (0., 0., 0.) || is_graph_emissive)
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -19,6 +147,107 @@ pub enum Geometry {
         normal: Vec3,
         size: f64, // this is the length of each side
     },
+    Box {
+        center: Vec3,
+        half_extents: Vec3,
+        /// in radians, applied the same way as Transform::rotation
+        rotation: Orientation,
+    },
+    /// capped at both ends, center is the middle of the cylinder
+    Cylinder {
+        center: Vec3,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+    },
+    /// capped at the base, center is the middle of the base, axis points to the tip
+    Cone {
+        center: Vec3,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+    },
+    Disc {
+        center: Vec3,
+        normal: Vec3,
+        radius: f64,
+    },
+    /// the tube goes around the axis
+    Torus {
+        center: Vec3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    /// left and right are relative to center
+    Csg {
+        center: Vec3,
+        operation: CsgOperation,
+        left: Box<Geometry>,
+        right: Box<Geometry>,
+    },
+    /// Sphere traced, so much slower than the analytic geometry.
+    /// Nothing is drawn outside the bounding radius.
+    Sdf {
+        center: Vec3,
+        root: SdfNode,
+        bounding_radius: f64,
+    },
+    /// Terrain, where center is the middle of the base
+    /// and size.y is how high the top of the heightmap is.
+    Heightfield {
+        center: Vec3,
+        size: Vec3,
+        map: Heightmap,
+    },
+    /// Triangles, with their vertices relative to center
+    Mesh {
+        center: Vec3,
+        mesh: Mesh,
+    },
+}
+
+/// Closer than this to the ray origin counts as the surface the ray started on
+const MIN_DISTANCE: f64 = 1e-6;
+
+/// The closest distance in front of the ray
+fn nearest(distances: impl IntoIterator<Item = f64>) -> Option<f64> {
+    distances
+        .into_iter()
+        .filter(|distance| *distance > MIN_DISTANCE)
+        .fold(None, |nearest: Option<f64>, distance| match nearest {
+            Some(nearest) if nearest <= distance => Some(nearest),
+            _ => Some(distance),
+        })
+}
+
+/// Converts to a space where axis is +y, the other two axes are arbitrary
+fn to_axis_space(axis: Vec3, vector: Vec3) -> Vec3 {
+    let (tangent, bitangent) = axis.orthonormal_basis();
+    Vec3 {
+        x: vector.dot(tangent),
+        y: vector.dot(axis.normalize()),
+        z: vector.dot(bitangent),
+    }
+}
+
+fn from_axis_space(axis: Vec3, vector: Vec3) -> Vec3 {
+    let (tangent, bitangent) = axis.orthonormal_basis();
+    tangent * vector.x + axis.normalize() * vector.y + bitangent * vector.z
+}
+
+/// (u, v) for a unit direction from the center of a sphere
+fn spherical_uv(direction: Vec3) -> (f64, f64) {
+    let u = 0.5 + direction.z.atan2(direction.x) / (2. * std::f64::consts::PI);
+    let v = 0.5 + direction.y.clamp(-1., 1.).asin() / std::f64::consts::PI;
+    (u, v)
+}
+
+/// (u, v) for shapes wrapped around the y axis in axis space
+fn cylindrical_uv(local: Vec3, bottom: f64, height: f64) -> (f64, f64) {
+    let u = 0.5 + local.z.atan2(local.x) / (2. * std::f64::consts::PI);
+    let v = ((local.y - bottom) / height).clamp(0., 1.);
+    (u, v)
 }
 
 impl Geometry {
@@ -28,7 +257,8 @@ impl Geometry {
                 // working out in whiteboard
                 let new_origin = ray.origin - *center;
 
-                let a = 1.;
+                // the direction isn't always normalized in object space
+                let a = ray.direction.dot(ray.direction);
                 let b = 2. * ray.direction.dot(new_origin);
                 let c = new_origin.dot(new_origin) - radius.powi(2);
 
@@ -74,9 +304,404 @@ impl Geometry {
 
                 Some((t, hit_point))
             }
+            Geometry::Box {
+                center,
+                half_extents,
+                rotation,
+            } => {
+                let (t_near, t_far) = Geometry::box_slabs(*center, *half_extents, rotation, ray)?;
+
+                if t_far < 0. {
+                    return None;
+                }
+
+                // if the ray starts inside, the far side is the one it hits
+                let t = if t_near >= 0. { t_near } else { t_far };
+
+                Some((t, ray.origin + (ray.direction * t)))
+            }
+            Geometry::Disc {
+                center,
+                normal,
+                radius,
+            } => {
+                // same as the plane, but with a circular edge
+                let denominator = ray.direction.dot(*normal);
+
+                if denominator.abs() < 1e-6 {
+                    return None;
+                }
+
+                let t = (*center - ray.origin).dot(*normal) / denominator;
+                let hit_point = ray.origin + (ray.direction * t);
+
+                if (hit_point - *center).length() > *radius {
+                    return None;
+                }
+
+                Some((t, hit_point))
+            }
+            Geometry::Cylinder { .. } | Geometry::Cone { .. } | Geometry::Torus { .. } => {
+                nearest(self.crossings(ray)).map(|t| (t, ray.origin + (ray.direction * t)))
+            }
+            Geometry::Sdf {
+                center,
+                root,
+                bounding_radius,
+            } => nearest(Geometry::sdf_crossings(
+                *center,
+                root,
+                *bounding_radius,
+                ray,
+                true,
+            ))
+            .map(|t| (t, ray.origin + (ray.direction * t))),
+            Geometry::Heightfield { center, size, map } => nearest(map.crossings(
+                *size,
+                ray.origin - *center,
+                ray.direction,
+                MIN_DISTANCE,
+                true,
+            ))
+            .map(|t| (t, ray.origin + (ray.direction * t))),
+            Geometry::Mesh { center, mesh } => mesh
+                .intersect(ray.origin - *center, ray.direction, MIN_DISTANCE)
+                .map(|(t, _)| (t, ray.origin + (ray.direction * t))),
+            // spans moves the ray to the center itself
+            Geometry::Csg { .. } => {
+                // the first boundary in front of the ray, whether that's going in or out
+                self.spans(ray)
+                    .into_iter()
+                    .flat_map(|(entry, exit)| [entry, exit])
+                    .find(|distance| *distance > MIN_DISTANCE)
+                    .map(|t| (t, ray.origin + (ray.direction * t)))
+            }
         }
     }
 
+    /// Every distance along the ray where it crosses the surface,
+    /// including ones behind the ray.
+    fn crossings(&self, ray: &Ray) -> Vec<f64> {
+        match self {
+            Geometry::Sphere { center, radius } => {
+                let new_origin = ray.origin - *center;
+
+                match solve_quadratic(
+                    ray.direction.dot(ray.direction),
+                    2. * ray.direction.dot(new_origin),
+                    new_origin.dot(new_origin) - radius.powi(2),
+                ) {
+                    Some((plus, minus)) => vec![plus, minus],
+                    None => vec![],
+                }
+            }
+            Geometry::Box {
+                center,
+                half_extents,
+                rotation,
+            } => match Geometry::box_slabs(*center, *half_extents, rotation, ray) {
+                Some((t_near, t_far)) => vec![t_near, t_far],
+                None => vec![],
+            },
+            Geometry::Cylinder {
+                center,
+                axis,
+                radius,
+                height,
+            } => {
+                let origin = to_axis_space(*axis, ray.origin - *center);
+                let direction = to_axis_space(*axis, ray.direction);
+                let half_height = height / 2.;
+
+                let mut distances = vec![];
+
+                // the curved side, x^2 + z^2 = r^2
+                if let Some((plus, minus)) = solve_quadratic(
+                    direction.x.powi(2) + direction.z.powi(2),
+                    2. * (origin.x * direction.x + origin.z * direction.z),
+                    origin.x.powi(2) + origin.z.powi(2) - radius.powi(2),
+                ) {
+                    for t in [plus, minus] {
+                        if (origin.y + direction.y * t).abs() <= half_height {
+                            distances.push(t);
+                        }
+                    }
+                }
+
+                // the caps
+                for cap_y in [-half_height, half_height] {
+                    let t = (cap_y - origin.y) / direction.y;
+                    let x = origin.x + direction.x * t;
+                    let z = origin.z + direction.z * t;
+                    if x.powi(2) + z.powi(2) <= radius.powi(2) {
+                        distances.push(t);
+                    }
+                }
+
+                distances
+            }
+            Geometry::Cone {
+                center,
+                axis,
+                radius,
+                height,
+            } => {
+                let origin = to_axis_space(*axis, ray.origin - *center);
+                let direction = to_axis_space(*axis, ray.direction);
+                // how much the radius shrinks per unit up the axis
+                let k = (radius / height).powi(2);
+                let to_tip = height - origin.y;
+
+                let mut distances = vec![];
+
+                // the side, x^2 + z^2 = k(h - y)^2
+                if let Some((plus, minus)) = solve_quadratic(
+                    direction.x.powi(2) + direction.z.powi(2) - k * direction.y.powi(2),
+                    2. * (origin.x * direction.x
+                        + origin.z * direction.z
+                        + k * to_tip * direction.y),
+                    origin.x.powi(2) + origin.z.powi(2) - k * to_tip.powi(2),
+                ) {
+                    for t in [plus, minus] {
+                        let y = origin.y + direction.y * t;
+                        // the equation is a double cone, so ignore the upside down half
+                        if (0. ..=*height).contains(&y) {
+                            distances.push(t);
+                        }
+                    }
+                }
+
+                // the base
+                let t = -origin.y / direction.y;
+                let x = origin.x + direction.x * t;
+                let z = origin.z + direction.z * t;
+                if x.powi(2) + z.powi(2) <= radius.powi(2) {
+                    distances.push(t);
+                }
+
+                distances
+            }
+            Geometry::Torus {
+                center,
+                axis,
+                major_radius,
+                minor_radius,
+            } => {
+                // the quartic is much better behaved with a unit direction,
+                // so solve for that then scale the distance back
+                let length = ray.direction.length();
+                let origin = to_axis_space(*axis, ray.origin - *center);
+                let direction = to_axis_space(*axis, ray.direction / length);
+
+                // (|p|^2 - R^2 - r^2)^2 = 4R^2(r^2 - y^2), expanded out in t
+                let f = origin.dot(direction);
+                let e = origin.dot(origin) - major_radius.powi(2) - minor_radius.powi(2);
+                let four_r2 = 4. * major_radius.powi(2);
+
+                let roots = solve_quartic(
+                    1.,
+                    4. * f,
+                    2. * e + 4. * f.powi(2) + four_r2 * direction.y.powi(2),
+                    4. * f * e + 2. * four_r2 * origin.y * direction.y,
+                    e.powi(2) - four_r2 * (minor_radius.powi(2) - origin.y.powi(2)),
+                );
+
+                roots.into_iter().map(|t| t / length).collect()
+            }
+            Geometry::Plane { .. } | Geometry::Disc { .. } => self
+                .intersect(ray)
+                .map(|(distance, _)| vec![distance])
+                .unwrap_or_default(),
+            Geometry::Csg { .. } => self
+                .spans(ray)
+                .into_iter()
+                .flat_map(|(entry, exit)| [entry, exit])
+                .collect(),
+            Geometry::Sdf {
+                center,
+                root,
+                bounding_radius,
+            } => Geometry::sdf_crossings(*center, root, *bounding_radius, ray, false),
+            Geometry::Heightfield { center, size, map } => map.crossings(
+                *size,
+                ray.origin - *center,
+                ray.direction,
+                f64::NEG_INFINITY,
+                false,
+            ),
+            Geometry::Mesh { center, mesh } => mesh.crossings(
+                ray.origin - *center,
+                ray.direction,
+                f64::NEG_INFINITY,
+                false,
+            ),
+        }
+    }
+
+    fn sdf_crossings(
+        center: Vec3,
+        root: &SdfNode,
+        bounding_radius: f64,
+        ray: &Ray,
+        first_only: bool,
+    ) -> Vec<f64> {
+        // marching is done with a unit direction, so distances need scaling back
+        let length = ray.direction.length();
+        let origin = ray.origin - center;
+        let direction = ray.direction / length;
+
+        // only march where the ray is inside the bounding sphere
+        let (entry, exit) = match solve_quadratic(
+            1.,
+            2. * direction.dot(origin),
+            origin.dot(origin) - bounding_radius.powi(2),
+        ) {
+            Some((plus, minus)) => (plus.min(minus), plus.max(minus)),
+            None => return vec![],
+        };
+
+        if first_only && exit < 0. {
+            return vec![];
+        }
+
+        // the nearest hit starts a little way along, so rays leaving the surface don't hit it straight away,
+        // but csg needs every crossing, including ones behind the origin
+        let start = if first_only { entry.max(1e-4) } else { entry };
+
+        root.march(
+            origin,
+            direction,
+            start,
+            exit,
+            root.lipschitz(bounding_radius),
+            first_only,
+        )
+        .into_iter()
+        .map(|t| t / length)
+        .collect()
+    }
+
+    /// Where the ray is inside the geometry, sorted by distance.
+    /// Planes count as a half space, and discs as infinitely thin.
+    pub fn spans(&self, ray: &Ray) -> Vec<Span> {
+        match self {
+            Geometry::Plane {
+                center,
+                normal,
+                size: _,
+            } => {
+                let denominator = ray.direction.dot(*normal);
+                let numerator = (*center - ray.origin).dot(*normal);
+
+                if denominator.abs() < 1e-6 {
+                    // parallel, so it's either always or never inside
+                    return if numerator > 0. {
+                        vec![(f64::NEG_INFINITY, f64::INFINITY)]
+                    } else {
+                        vec![]
+                    };
+                }
+
+                let t = numerator / denominator;
+
+                // the inside is behind the normal
+                if denominator > 0. {
+                    vec![(f64::NEG_INFINITY, t)]
+                } else {
+                    vec![(t, f64::INFINITY)]
+                }
+            }
+            Geometry::Disc { .. } => match self.intersect(ray) {
+                Some((distance, _)) => vec![(distance, distance)],
+                None => vec![],
+            },
+            Geometry::Csg {
+                center,
+                operation,
+                left,
+                right,
+            } => {
+                let local_ray = Ray {
+                    origin: ray.origin - *center,
+                    direction: ray.direction,
+                    time: ray.time,
+                };
+
+                operation.combine(&left.spans(&local_ray), &right.spans(&local_ray))
+            }
+            Geometry::Sphere { .. }
+            | Geometry::Box { .. }
+            | Geometry::Cylinder { .. }
+            | Geometry::Cone { .. }
+            | Geometry::Torus { .. }
+            | Geometry::Sdf { .. }
+            | Geometry::Heightfield { .. }
+            | Geometry::Mesh { .. } => pair_up(self.crossings(ray)),
+        }
+    }
+
+    /// The entry and exit distances of the ray through a box
+    fn box_slabs(
+        center: Vec3,
+        half_extents: Vec3,
+        rotation: &Orientation,
+        ray: &Ray,
+    ) -> Option<Span> {
+        // slab test in the box's own space, where it's axis aligned
+        let local_ray = Ray {
+            origin: rotation.to_local(ray.origin - center),
+            direction: rotation.to_local(ray.direction),
+            time: ray.time,
+        };
+
+        let slab = |origin: f64, direction: f64, half_extent: f64| {
+            let t1 = (-half_extent - origin) / direction;
+            let t2 = (half_extent - origin) / direction;
+            (t1.min(t2), t1.max(t2))
+        };
+
+        let x = slab(local_ray.origin.x, local_ray.direction.x, half_extents.x);
+        let y = slab(local_ray.origin.y, local_ray.direction.y, half_extents.y);
+        let z = slab(local_ray.origin.z, local_ray.direction.z, half_extents.z);
+
+        let t_near = x.0.max(y.0).max(z.0);
+        let t_far = x.1.min(y.1).min(z.1);
+
+        if t_near > t_far {
+            return None;
+        }
+
+        Some((t_near, t_far))
+    }
+
+    /// Which face of a box a point is on, as (axis, sign),
+    /// along with the point in the box's space.
+    fn box_face(
+        center: Vec3,
+        half_extents: Vec3,
+        rotation: &Orientation,
+        point: Vec3,
+    ) -> (usize, f64, Vec3) {
+        let local = rotation.to_local(point - center);
+
+        // the face is on the axis the point is furthest along, relative to the box's size
+        let relative = [
+            local.x / half_extents.x,
+            local.y / half_extents.y,
+            local.z / half_extents.z,
+        ];
+        let axis = (0..3)
+            .max_by(|a, b| {
+                relative[*a]
+                    .abs()
+                    .partial_cmp(&relative[*b].abs())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .unwrap();
+
+        (axis, relative[axis].signum(), local)
+    }
+
     pub fn normal_at_point(&self, point: Vec3) -> Vec3 {
         match self {
             Geometry::Sphere { center, radius: _ } => {
@@ -91,9 +716,661 @@ impl Geometry {
                 // normal is the same everywhere
                 *normal
             }
+            Geometry::Box {
+                center,
+                half_extents,
+                rotation,
+            } => {
+                let (axis, sign, _) = Geometry::box_face(*center, *half_extents, rotation, point);
+
+                let mut local_normal = Vec3 {
+                    x: 0.,
+                    y: 0.,
+                    z: 0.,
+                };
+                match axis {
+                    0 => local_normal.x = sign,
+                    1 => local_normal.y = sign,
+                    _ => local_normal.z = sign,
+                }
+
+                rotation.to_world(local_normal)
+            }
+            Geometry::Cylinder {
+                center,
+                axis,
+                radius,
+                height,
+            } => {
+                let local = to_axis_space(*axis, point - *center);
+                let distance_from_axis = (local.x.powi(2) + local.z.powi(2)).sqrt();
+
+                // whichever surface the point is closest to
+                if (height / 2. - local.y.abs()) < (radius - distance_from_axis).abs() {
+                    axis.normalize() * local.y.signum()
+                } else {
+                    from_axis_space(
+                        *axis,
+                        Vec3 {
+                            x: local.x,
+                            y: 0.,
+                            z: local.z,
+                        },
+                    )
+                    .normalize()
+                }
+            }
+            Geometry::Cone {
+                center,
+                axis,
+                radius,
+                height,
+            } => {
+                let local = to_axis_space(*axis, point - *center);
+                let distance_from_axis = (local.x.powi(2) + local.z.powi(2)).sqrt();
+                let side_radius = radius * (height - local.y) / height;
+
+                if local.y.abs() < (side_radius - distance_from_axis).abs() {
+                    -axis.normalize()
+                } else {
+                    // the gradient of x^2 + z^2 - k(h - y)^2
+                    let k = (radius / height).powi(2);
+                    from_axis_space(
+                        *axis,
+                        Vec3 {
+                            x: local.x,
+                            y: k * (height - local.y),
+                            z: local.z,
+                        },
+                    )
+                    .normalize()
+                }
+            }
+            Geometry::Disc {
+                center: _,
+                normal,
+                radius: _,
+            } => *normal,
+            Geometry::Torus {
+                center,
+                axis,
+                major_radius,
+                minor_radius: _,
+            } => {
+                // away from the circle running through the middle of the tube
+                let local = to_axis_space(*axis, point - *center);
+                let ring = Vec3 {
+                    x: local.x,
+                    y: 0.,
+                    z: local.z,
+                }
+                .normalize()
+                    * *major_radius;
+
+                from_axis_space(*axis, local - ring).normalize()
+            }
+            Geometry::Csg { .. } => self.csg_surface(point).1,
+            Geometry::Sdf {
+                center,
+                root,
+                bounding_radius: _,
+            } => root.normal_at_point(point - *center),
+            Geometry::Heightfield { center, size, map } => {
+                map.normal_at_point(*size, point - *center)
+            }
+            Geometry::Mesh { center, mesh } => mesh.normal_at_point(point - *center),
+        }
+    }
+
+    /// Negative inside, positive outside.
+    /// Planes count as a half space, the same as in spans.
+    pub fn signed_distance(&self, point: Vec3) -> f64 {
+        match self {
+            Geometry::Sphere { center, radius } => (point - *center).length() - radius,
+            Geometry::Plane {
+                center,
+                normal,
+                size: _,
+            } => (point - *center).dot(*normal),
+            Geometry::Box {
+                center,
+                half_extents,
+                rotation,
+            } => {
+                let local = rotation.to_local(point - *center);
+                let q = Vec3 {
+                    x: local.x.abs() - half_extents.x,
+                    y: local.y.abs() - half_extents.y,
+                    z: local.z.abs() - half_extents.z,
+                };
+                let outside = Vec3 {
+                    x: q.x.max(0.),
+                    y: q.y.max(0.),
+                    z: q.z.max(0.),
+                };
+
+                outside.length() + q.x.max(q.y).max(q.z).min(0.)
+            }
+            Geometry::Cylinder {
+                center,
+                axis,
+                radius,
+                height,
+            } => {
+                let local = to_axis_space(*axis, point - *center);
+                let radial = (local.x.powi(2) + local.z.powi(2)).sqrt() - radius;
+                let vertical = local.y.abs() - height / 2.;
+
+                radial.max(vertical).min(0.)
+                    + (radial.max(0.).powi(2) + vertical.max(0.).powi(2)).sqrt()
+            }
+            Geometry::Cone {
+                center,
+                axis,
+                radius,
+                height,
+            } => {
+                // a capped cone with a top radius of 0, measured from its middle
+                let local = to_axis_space(*axis, point - *center);
+                let half_height = height / 2.;
+                let q = (
+                    (local.x.powi(2) + local.z.powi(2)).sqrt(),
+                    local.y - half_height,
+                );
+
+                // the nearest point on the cap, then on the slanted side
+                let cap = (
+                    q.0 - q.0.min(if q.1 < 0. { *radius } else { 0. }),
+                    q.1.abs() - half_height,
+                );
+                let slope = (-radius, *height);
+                let along = ((-q.0 * slope.0 + (half_height - q.1) * slope.1)
+                    / (slope.0.powi(2) + slope.1.powi(2)))
+                .clamp(0., 1.);
+                let side = (q.0 + slope.0 * along, q.1 - half_height + slope.1 * along);
+
+                let sign = if side.0 < 0. && cap.1 < 0. { -1. } else { 1. };
+                sign * (cap.0.powi(2) + cap.1.powi(2))
+                    .min(side.0.powi(2) + side.1.powi(2))
+                    .sqrt()
+            }
+            Geometry::Disc {
+                center,
+                normal,
+                radius,
+            } => {
+                let offset = point - *center;
+                let height = offset.dot(*normal);
+                let radial = ((offset - *normal * height).length() - radius).max(0.);
+
+                (height.powi(2) + radial.powi(2)).sqrt()
+            }
+            Geometry::Torus {
+                center,
+                axis,
+                major_radius,
+                minor_radius,
+            } => {
+                let local = to_axis_space(*axis, point - *center);
+                let ring_distance = (local.x.powi(2) + local.z.powi(2)).sqrt() - major_radius;
+
+                (ring_distance.powi(2) + local.y.powi(2)).sqrt() - minor_radius
+            }
+            Geometry::Csg {
+                center,
+                operation,
+                left,
+                right,
+            } => operation.combine_distances(
+                left.signed_distance(point - *center),
+                right.signed_distance(point - *center),
+            ),
+            Geometry::Sdf {
+                center,
+                root,
+                bounding_radius: _,
+            } => root.distance(point - *center),
+            Geometry::Heightfield { center, size, map } => {
+                // only a bound, as it's the height above the surface rather than the distance to it
+                let local = point - *center;
+                let bounds = Geometry::Box {
+                    center: Vec3 {
+                        x: 0.,
+                        y: size.y / 2.,
+                        z: 0.,
+                    },
+                    half_extents: *size / 2.,
+                    rotation: Orientation::default(),
+                };
+
+                bounds
+                    .signed_distance(local)
+                    .max(local.y - map.surface_height(*size, local))
+            }
+            Geometry::Mesh { center, mesh } => mesh.signed_distance(point - *center),
+        }
+    }
+
+    /// The leaf geometry whose surface is closest to the point,
+    /// as (distance to its surface, normal pointing out of the csg)
+    fn csg_surface(&self, point: Vec3) -> (f64, Vec3) {
+        match self {
+            Geometry::Csg {
+                center,
+                operation,
+                left,
+                right,
+            } => {
+                let local = point - *center;
+                let left = left.csg_surface(local);
+                let mut right = right.csg_surface(local);
+
+                // the cut out geometry's surface faces into it
+                if *operation == CsgOperation::Difference {
+                    right.1 = -right.1;
+                }
+
+                if left.0 <= right.0 {
+                    left
+                } else {
+                    right
+                }
+            }
+            _ => (
+                self.signed_distance(point).abs(),
+                self.normal_at_point(point),
+            ),
         }
     }
 
+    /// Texture coordinates in the range 0 to 1
+    pub fn uv_at_point(&self, point: Vec3) -> (f64, f64) {
+        match self {
+            Geometry::Sphere { center, radius } => spherical_uv((point - *center) / *radius),
+            Geometry::Plane {
+                center,
+                normal,
+                size,
+            } => {
+                let (tangent, bitangent) = normal.orthonormal_basis();
+                let offset = point - *center;
+
+                (
+                    (offset.dot(tangent) / size + 1.) / 2.,
+                    (offset.dot(bitangent) / size + 1.) / 2.,
+                )
+            }
+            Geometry::Box {
+                center,
+                half_extents,
+                rotation,
+            } => {
+                let (axis, sign, local) =
+                    Geometry::box_face(*center, *half_extents, rotation, point);
+
+                // the two axes across the face,
+                // flipped on the negative faces so the image isn't mirrored
+                let (u, v) = match axis {
+                    0 => (-sign * local.z / half_extents.z, local.y / half_extents.y),
+                    1 => (local.x / half_extents.x, -sign * local.z / half_extents.z),
+                    _ => (sign * local.x / half_extents.x, local.y / half_extents.y),
+                };
+
+                ((u + 1.) / 2., (v + 1.) / 2.)
+            }
+            Geometry::Cylinder {
+                center,
+                axis,
+                radius: _,
+                height,
+            } => cylindrical_uv(to_axis_space(*axis, point - *center), -height / 2., *height),
+            Geometry::Cone {
+                center,
+                axis,
+                radius: _,
+                height,
+            } => cylindrical_uv(to_axis_space(*axis, point - *center), 0., *height),
+            Geometry::Disc {
+                center,
+                normal,
+                radius,
+            } => {
+                let (tangent, bitangent) = normal.orthonormal_basis();
+                let offset = point - *center;
+
+                (
+                    (offset.dot(tangent) / radius + 1.) / 2.,
+                    (offset.dot(bitangent) / radius + 1.) / 2.,
+                )
+            }
+            Geometry::Torus {
+                center,
+                axis,
+                major_radius,
+                minor_radius: _,
+            } => {
+                let local = to_axis_space(*axis, point - *center);
+                let distance_from_axis = (local.x.powi(2) + local.z.powi(2)).sqrt();
+
+                // u goes around the axis, v goes around the tube
+                let u = 0.5 + local.z.atan2(local.x) / (2. * std::f64::consts::PI);
+                let v = 0.5
+                    + local.y.atan2(distance_from_axis - major_radius)
+                        / (2. * std::f64::consts::PI);
+
+                (u, v)
+            }
+            Geometry::Csg {
+                center,
+                operation: _,
+                left,
+                right,
+            } => {
+                // whichever child the point is on the surface of
+                let local = point - *center;
+                if left.signed_distance(local).abs() <= right.signed_distance(local).abs() {
+                    left.uv_at_point(local)
+                } else {
+                    right.uv_at_point(local)
+                }
+            }
+            Geometry::Sdf {
+                center,
+                root: _,
+                bounding_radius: _,
+            } => spherical_uv((point - *center).normalize()),
+            Geometry::Heightfield { center, size, map } => map.uv_at_point(*size, point - *center),
+            // wrapped like a sphere if it doesn't have its own
+            Geometry::Mesh { center, mesh } => {
+                mesh.uv_at_point(point - *center).unwrap_or_else(|| {
+                    spherical_uv((point - *center - mesh.bounding_sphere().0).normalize())
+                })
+            }
+        }
+    }
+
+    /// Only meshes can have colours on their vertices
+    pub fn vertex_colour(&self, triangle: Option<&TriangleHit>) -> Option<(f64, f64, f64)> {
+        match (self, triangle) {
+            (Geometry::Mesh { mesh, .. }, Some(triangle)) => mesh.colour_at(triangle),
+            _ => None,
+        }
+    }
+
+    pub fn uv_at_point_transformed(&self, transform: &CachedTransform, point: Vec3) -> (f64, f64) {
+        if transform.is_identity() {
+            return self.uv_at_point(point);
+        }
+
+        self.uv_at_point(transform.point_to_local(point))
+    }
+
+    /// The ray and returned point are in world space,
+    /// the geometry is in the space of the transform.
+    pub fn intersect_transformed(
+        &self,
+        transform: &CachedTransform,
+        ray: &Ray,
+    ) -> Option<(f64, Vec3)> {
+        self.hit_transformed(transform, ray)
+            .map(|(distance, point, _)| (distance, point))
+    }
+
+    /// Like intersect_transformed, along with the triangle for meshes,
+    /// so shading doesn't have to search for it again
+    pub fn hit_transformed(
+        &self,
+        transform: &CachedTransform,
+        ray: &Ray,
+    ) -> Option<(f64, Vec3, Option<TriangleHit>)> {
+        let local_ray;
+        let ray_in_space = if transform.is_identity() {
+            ray
+        } else {
+            local_ray = transform.ray_to_local(ray);
+            &local_ray
+        };
+
+        // the distance is the same in both spaces, so only the point needs working out again
+        let (distance, triangle) = match self {
+            Geometry::Mesh { center, mesh } => {
+                let (distance, triangle) = mesh.intersect(
+                    ray_in_space.origin - *center,
+                    ray_in_space.direction,
+                    MIN_DISTANCE,
+                )?;
+                (distance, Some(triangle))
+            }
+            _ => (self.intersect(ray_in_space)?.0, None),
+        };
+
+        Some((distance, ray.origin + (ray.direction * distance), triangle))
+    }
+
+    pub fn normal_at_point_transformed(&self, transform: &CachedTransform, point: Vec3) -> Vec3 {
+        if transform.is_identity() {
+            return self.normal_at_point(point);
+        }
+
+        let local_normal = self.normal_at_point(transform.point_to_local(point));
+        transform.normal_to_world(local_normal)
+    }
+
+    /// The direction u increases in at a point on the surface.
+    /// It isn't always perpendicular to the normal, so that's up to the caller.
+    pub fn tangent_at_point(&self, point: Vec3) -> Vec3 {
+        // going around the y axis, like the u of the round shapes
+        let around = |local: Vec3| Vec3 {
+            x: -local.z,
+            y: 0.,
+            z: local.x,
+        };
+
+        match self {
+            Geometry::Sphere { center, radius: _ } => around(point - *center),
+            Geometry::Plane {
+                center: _,
+                normal,
+                size: _,
+            }
+            | Geometry::Disc {
+                center: _,
+                normal,
+                radius: _,
+            } => normal.orthonormal_basis().0,
+            Geometry::Box {
+                center,
+                half_extents,
+                rotation,
+            } => {
+                let (axis, sign, _) = Geometry::box_face(*center, *half_extents, rotation, point);
+
+                // the same axes as uv_at_point uses for u
+                let local_tangent = match axis {
+                    0 => Vec3 {
+                        x: 0.,
+                        y: 0.,
+                        z: -sign,
+                    },
+                    1 => Vec3 {
+                        x: 1.,
+                        y: 0.,
+                        z: 0.,
+                    },
+                    _ => Vec3 {
+                        x: sign,
+                        y: 0.,
+                        z: 0.,
+                    },
+                };
+
+                rotation.to_world(local_tangent)
+            }
+            Geometry::Cylinder {
+                center,
+                axis,
+                radius: _,
+                height: _,
+            }
+            | Geometry::Cone {
+                center,
+                axis,
+                radius: _,
+                height: _,
+            }
+            | Geometry::Torus {
+                center,
+                axis,
+                major_radius: _,
+                minor_radius: _,
+            } => from_axis_space(*axis, around(to_axis_space(*axis, point - *center))),
+            Geometry::Csg {
+                center,
+                operation: _,
+                left,
+                right,
+            } => {
+                // whichever child the point is on the surface of
+                let local = point - *center;
+                if left.signed_distance(local).abs() <= right.signed_distance(local).abs() {
+                    left.tangent_at_point(local)
+                } else {
+                    right.tangent_at_point(local)
+                }
+            }
+            Geometry::Sdf {
+                center,
+                root: _,
+                bounding_radius: _,
+            } => around(point - *center),
+            Geometry::Heightfield { .. } => Vec3 {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            Geometry::Mesh { center, mesh } => {
+                let local = point - *center;
+                mesh.tangent_at_point(local)
+                    .unwrap_or_else(|| around(local - mesh.bounding_sphere().0))
+            }
+        }
+    }
+
+    pub fn tangent_at_point_transformed(&self, transform: &CachedTransform, point: Vec3) -> Vec3 {
+        if transform.is_identity() {
+            return self.tangent_at_point(point);
+        }
+
+        self.tangent_at_point(transform.point_to_local(point))
+            .transform_direction(transform.matrix)
+    }
+
+    /// A (center, radius) sphere that the whole geometry is inside,
+    /// or None if it goes on forever
+    pub fn bounding_sphere(&self) -> Option<(Vec3, f64)> {
+        match self {
+            Geometry::Sphere { center, radius } => Some((*center, *radius)),
+            Geometry::Plane { .. } => None,
+            Geometry::Box {
+                center,
+                half_extents,
+                rotation: _,
+            } => Some((*center, half_extents.length())),
+            Geometry::Cylinder {
+                center,
+                axis: _,
+                radius,
+                height,
+            } => Some((*center, (radius.powi(2) + (height / 2.).powi(2)).sqrt())),
+            Geometry::Cone {
+                center,
+                axis,
+                radius,
+                height,
+            } => Some((
+                *center + *axis * (height / 2.),
+                (radius.powi(2) + (height / 2.).powi(2)).sqrt(),
+            )),
+            Geometry::Disc {
+                center,
+                normal: _,
+                radius,
+            } => Some((*center, *radius)),
+            Geometry::Torus {
+                center,
+                axis: _,
+                major_radius,
+                minor_radius,
+            } => Some((*center, major_radius + minor_radius)),
+            Geometry::Csg {
+                center,
+                operation,
+                left,
+                right,
+            } => {
+                let (left, right) = (left.bounding_sphere(), right.bounding_sphere());
+
+                // the result is never bigger than the left, or the right for an intersection
+                let (first, second) = match (operation, left, right) {
+                    (CsgOperation::Difference, Some(left), _) => (left, left),
+                    (CsgOperation::Intersection, Some(only), None)
+                    | (CsgOperation::Intersection, None, Some(only)) => (only, only),
+                    (_, Some(left), Some(right)) => (left, right),
+                    _ => return None,
+                };
+
+                // the smallest sphere around both
+                let offset = second.0 - first.0;
+                let distance = offset.length();
+                let (bound_center, bound_radius) = if distance + second.1 <= first.1 {
+                    first
+                } else if distance + first.1 <= second.1 {
+                    second
+                } else {
+                    let radius = (distance + first.1 + second.1) / 2.;
+                    (first.0 + offset * ((radius - first.1) / distance), radius)
+                };
+
+                Some((*center + bound_center, bound_radius))
+            }
+            Geometry::Sdf {
+                center,
+                root: _,
+                bounding_radius,
+            } => Some((*center, *bounding_radius)),
+            Geometry::Heightfield {
+                center,
+                size,
+                map: _,
+            } => Some((
+                *center
+                    + Vec3 {
+                        x: 0.,
+                        y: size.y / 2.,
+                        z: 0.,
+                    },
+                (*size / 2.).length(),
+            )),
+            Geometry::Mesh { center, mesh } => {
+                let (middle, radius) = mesh.bounding_sphere();
+                Some((*center + middle, radius))
+            }
+        }
+    }
+
+    pub fn bounding_sphere_transformed(&self, transform: &CachedTransform) -> Option<(Vec3, f64)> {
+        let (center, radius) = self.bounding_sphere()?;
+
+        if transform.is_identity() {
+            return Some((center, radius));
+        }
+
+        let scale = transform.transform.scale;
+        let scale = scale.x.abs().max(scale.y.abs()).max(scale.z.abs());
+
+        Some((center.transform_point(transform.matrix), radius * scale))
+    }
+
     pub fn position(&self) -> &Vec3 {
         match self {
             Geometry::Sphere { center, radius: _ } => center,
@@ -102,6 +1379,51 @@ impl Geometry {
                 normal: _,
                 size: _,
             } => center,
+            Geometry::Box {
+                center,
+                half_extents: _,
+                rotation: _,
+            } => center,
+            Geometry::Cylinder {
+                center,
+                axis: _,
+                radius: _,
+                height: _,
+            } => center,
+            Geometry::Cone {
+                center,
+                axis: _,
+                radius: _,
+                height: _,
+            } => center,
+            Geometry::Disc {
+                center,
+                normal: _,
+                radius: _,
+            } => center,
+            Geometry::Torus {
+                center,
+                axis: _,
+                major_radius: _,
+                minor_radius: _,
+            } => center,
+            Geometry::Csg {
+                center,
+                operation: _,
+                left: _,
+                right: _,
+            } => center,
+            Geometry::Sdf {
+                center,
+                root: _,
+                bounding_radius: _,
+            } => center,
+            Geometry::Heightfield {
+                center,
+                size: _,
+                map: _,
+            } => center,
+            Geometry::Mesh { center, mesh: _ } => center,
         }
     }
 
@@ -113,6 +1435,51 @@ impl Geometry {
                 normal: _,
                 size: _,
             } => center,
+            Geometry::Box {
+                center,
+                half_extents: _,
+                rotation: _,
+            } => center,
+            Geometry::Cylinder {
+                center,
+                axis: _,
+                radius: _,
+                height: _,
+            } => center,
+            Geometry::Cone {
+                center,
+                axis: _,
+                radius: _,
+                height: _,
+            } => center,
+            Geometry::Disc {
+                center,
+                normal: _,
+                radius: _,
+            } => center,
+            Geometry::Torus {
+                center,
+                axis: _,
+                major_radius: _,
+                minor_radius: _,
+            } => center,
+            Geometry::Csg {
+                center,
+                operation: _,
+                left: _,
+                right: _,
+            } => center,
+            Geometry::Sdf {
+                center,
+                root: _,
+                bounding_radius: _,
+            } => center,
+            Geometry::Heightfield {
+                center,
+                size: _,
+                map: _,
+            } => center,
+            Geometry::Mesh { center, mesh: _ } => center,
         }
     }
 }
@@ -120,17 +1487,24 @@ impl Geometry {
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Object {
     pub name: String,
-    pub material: Material,
+    pub material: MaterialRef,
     pub geometry: Geometry,
+    #[serde(default)]
+    pub transform: Transform,
+    /// how it moves while the shutter is open
+    #[serde(default)]
+    pub motion: Option<Motion>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub enum Light {
     Direction {
+        #[serde(deserialize_with = "deserialize_colour")]
         intensity: (f64, f64, f64),
         direction: Vec3,
     },
     Point {
+        #[serde(deserialize_with = "deserialize_colour")]
         intensity: (f64, f64, f64),
         position: Vec3,
     },
@@ -163,3 +1537,92 @@ impl Light {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec3(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    fn sphere(x: f64, radius: f64) -> Box<Geometry> {
+        Box::new(Geometry::Sphere {
+            center: vec3(x, 0., 0.),
+            radius,
+        })
+    }
+
+    #[test]
+    fn offset_csg_is_hit_where_its_drawn() {
+        let union = Geometry::Csg {
+            center: vec3(0., 0., 5.),
+            operation: CsgOperation::Union,
+            left: sphere(-1., 1.),
+            right: sphere(1., 1.),
+        };
+
+        // straight down the z axis, through where the spheres touch
+        let ray = Ray {
+            origin: vec3(0., 0., -5.),
+            direction: vec3(0., 0., 1.),
+            time: 0.,
+        };
+        let (distance, point) = union.intersect(&ray).unwrap();
+        assert!((distance - 10.).abs() < 1e-9);
+        assert!((point - vec3(0., 0., 5.)).length() < 1e-9);
+
+        // through the middle of the left sphere
+        let ray = Ray {
+            origin: vec3(-1., 0., -5.),
+            direction: vec3(0., 0., 1.),
+            time: 0.,
+        };
+        let (distance, _) = union.intersect(&ray).unwrap();
+        assert!((distance - 9.).abs() < 1e-9);
+        assert!(
+            union
+                .signed_distance(ray.origin + ray.direction * distance)
+                .abs()
+                < 1e-9
+        );
+
+        // where the spheres would be without the offset
+        let ray = Ray {
+            origin: vec3(-1., 0., -5.),
+            direction: vec3(0., 1., 0.),
+            time: 0.,
+        };
+        assert!(union.intersect(&ray).is_none());
+    }
+
+    #[test]
+    fn sdf_spans_start_behind_the_ray() {
+        let sdf = Geometry::Sdf {
+            center: vec3(0., 0., 0.),
+            root: SdfNode::Primitive(sphere(0., 1.)),
+            bounding_radius: 2.,
+        };
+
+        // from inside, the span goes back to where the ray would have gone in
+        let ray = Ray {
+            origin: vec3(0., 0., 0.),
+            direction: vec3(0., 0., 1.),
+            time: 0.,
+        };
+        let spans = sdf.spans(&ray);
+        assert_eq!(spans.len(), 1);
+        assert!((spans[0].0 + 1.).abs() < 1e-3);
+        assert!((spans[0].1 - 1.).abs() < 1e-3);
+
+        // so cutting it out of a bigger sphere leaves the shell
+        let difference = Geometry::Csg {
+            center: vec3(0., 0., 0.),
+            operation: CsgOperation::Difference,
+            left: sphere(0., 3.),
+            right: Box::new(sdf),
+        };
+        let (distance, _) = difference.intersect(&ray).unwrap();
+        assert!((distance - 1.).abs() < 1e-3);
+    }
+}
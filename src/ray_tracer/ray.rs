@@ -3,5 +3,6 @@ use crate::ray_tracer::Vec3;
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
+    /// in seconds from the time of the frame, for motion blur
+    pub time: f64,
 }
-
@@ -14,12 +14,177 @@ pub struct ImagePlane {
     pub center: Vec3,
 }
 
+/// Where the rays for each pixel start, and the image plane they go through
+struct View {
+    camera: Vec3,
+    top_left: Vec3,
+    width_world_space: f64,
+    height_world_space: f64,
+    right: Vec3,
+    up: Vec3,
+}
+
 pub struct Hit<'a> {
     pub distance: f64,
     pub point: Vec3,
-    pub object: &'a Object,
+    pub geometry: &'a Geometry,
+    /// where the object was when the ray hit it
+    pub transform: CachedTransform,
+    pub material: &'a Material,
+    /// the triangle that was hit, for meshes
+    pub triangle: Option<TriangleHit>,
 }
 
+impl<'a> Hit<'a> {
+    /// The mesh and triangle, if a mesh was hit
+    fn mesh_triangle(&self) -> Option<(&Mesh, &TriangleHit)> {
+        match (self.geometry, &self.triangle) {
+            (Geometry::Mesh { mesh, .. }, Some(triangle)) => Some((mesh, triangle)),
+            _ => None,
+        }
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        match self.mesh_triangle() {
+            Some((mesh, triangle)) => self.transform.normal_to_world(mesh.normal_at(triangle)),
+            None => self
+                .geometry
+                .normal_at_point_transformed(&self.transform, self.point),
+        }
+    }
+
+    /// Where the hit is relative to the object, so textures stay stuck to it
+    pub fn local_point(&self) -> Vec3 {
+        self.transform.point_to_local(self.point) - *self.geometry.position()
+    }
+
+    pub fn vertex_colour(&self) -> Option<(f64, f64, f64)> {
+        self.geometry.vertex_colour(self.triangle.as_ref())
+    }
+
+    pub fn uv(&self) -> (f64, f64) {
+        match self
+            .mesh_triangle()
+            .and_then(|(mesh, triangle)| mesh.uv_at(triangle))
+        {
+            Some(uv) => uv,
+            None => self
+                .geometry
+                .uv_at_point_transformed(&self.transform, self.point),
+        }
+    }
+
+    fn tangent(&self) -> Vec3 {
+        match self
+            .mesh_triangle()
+            .and_then(|(mesh, triangle)| mesh.tangent_at(triangle))
+        {
+            Some(tangent) if self.transform.is_identity() => tangent,
+            Some(tangent) => tangent.transform_direction(self.transform.matrix),
+            None => self
+                .geometry
+                .tangent_at_point_transformed(&self.transform, self.point),
+        }
+    }
+
+    /// The material where it was hit, seen from a direction
+    pub fn surface(&self, normal: Vec3, to_view: Vec3) -> Surface {
+        self.material.evaluate(&ShadingPoint {
+            local_point: self.local_point(),
+            uv: self.uv(),
+            normal,
+            to_view,
+            vertex_colour: self.vertex_colour(),
+        })
+    }
+
+    /// The normal after any normal map, which is the one used for shading
+    pub fn shading_normal(&self) -> Vec3 {
+        let normal = self.normal();
+
+        let normal_map = match &self.material.normal_map {
+            Some(normal_map) => normal_map,
+            None => return normal,
+        };
+
+        // made perpendicular to the normal, or any perpendicular direction if it can't be
+        let tangent = self.tangent();
+        let tangent = tangent - normal * normal.dot(tangent);
+        let tangent = if tangent.length() > 1e-9 {
+            tangent.normalize()
+        } else {
+            normal.orthonormal_basis().0
+        };
+
+        normal_map.apply(
+            normal,
+            tangent,
+            self.uv(),
+            self.local_point(),
+            &self.transform,
+        )
+    }
+}
+
+/// An object or instance with its prototype and material looked up,
+/// which is done once a frame rather than for every ray
+struct Drawable<'a> {
+    geometry: &'a Geometry,
+    material: &'a Material,
+    placement: Placement<'a>,
+    /// the transform for every ray, if it doesn't move while the shutter is open
+    fixed: Option<CachedTransform>,
+}
+
+/// What a drawable's transform comes from
+#[derive(Clone, Copy)]
+enum Placement<'a> {
+    Object(&'a Object),
+    Instance(&'a Instance),
+}
+
+impl<'a> Drawable<'a> {
+    fn new(
+        scene: &Scene,
+        geometry: &'a Geometry,
+        material: &'a Material,
+        placement: Placement<'a>,
+    ) -> Drawable<'a> {
+        let mut drawable = Drawable {
+            geometry,
+            material,
+            placement,
+            fixed: None,
+        };
+
+        // every ray is at the same time if the shutter's closed
+        let blur = &scene.motion_blur;
+        let is_moving = match placement {
+            Placement::Object(object) => object.motion.is_some(),
+            Placement::Instance(instance) => instance.motion.is_some(),
+        } || scene.do_objects_spin;
+
+        if !blur.is_open() || !is_moving {
+            drawable.fixed = Some(drawable.transform_at(scene, blur.shutter_open));
+        }
+
+        drawable
+    }
+
+    fn transform_at(&self, scene: &Scene, time: f64) -> CachedTransform {
+        if let Some(transform) = self.fixed {
+            return transform;
+        }
+
+        match self.placement {
+            Placement::Object(object) => object.transform_at(scene, time),
+            Placement::Instance(instance) => instance.transform_at(scene, time),
+        }
+        .cached()
+    }
+}
+
+#[derive(Clone)]
 pub struct RayTracer {
     pub camera: Vec3,
     pub rotation: Vec3,
@@ -31,30 +196,53 @@ pub struct RayTracer {
 
 impl RayTracer {
     pub fn forward(&self) -> Vec3 {
+        RayTracer::forward_at(self.rotation)
+    }
+
+    pub fn right(&self) -> Vec3 {
+        RayTracer::right_at(self.rotation)
+    }
+
+    pub fn up(&self) -> Vec3 {
+        RayTracer::up_at(self.rotation)
+    }
+
+    fn forward_at(rotation: Vec3) -> Vec3 {
         Vec3 {
             x: 0.,
             y: 0.,
             z: 1.,
         }
-        .transform_point(Mat44::create_rotation(Axis::X, -self.rotation.x))
-        .transform_point(Mat44::create_rotation(Axis::Y, -self.rotation.y))
+        .transform_direction(Mat44::create_rotation(Axis::X, -rotation.x))
+        .transform_direction(Mat44::create_rotation(Axis::Y, -rotation.y))
     }
 
-    pub fn right(&self) -> Vec3 {
+    fn right_at(rotation: Vec3) -> Vec3 {
         let temp = Vec3 {
             x: 0.,
             y: 1.,
             z: 0.,
         }
-        .transform_point(Mat44::create_rotation(Axis::Z, -self.rotation.z));
-        (temp * self.forward()).normalize()
+        .transform_direction(Mat44::create_rotation(Axis::Z, -rotation.z));
+        (temp * RayTracer::forward_at(rotation)).normalize()
     }
 
-    pub fn up(&self) -> Vec3 {
-        (self.forward() * self.right()).normalize()
+    fn up_at(rotation: Vec3) -> Vec3 {
+        (RayTracer::forward_at(rotation) * RayTracer::right_at(rotation)).normalize()
     }
 
-    fn get_image_plane(&self, aspect_ratio: f64) -> ImagePlane {
+    /// Turns the camera to face a point
+    pub fn look_at(&mut self, target: Vec3) {
+        // the camera looks along -forward
+        let direction = (target - self.camera).normalize();
+        self.rotation = Vec3 {
+            x: (-direction.y).asin(),
+            y: direction.x.atan2(-direction.z),
+            z: 0.,
+        };
+    }
+
+    fn get_image_plane(&self, camera: Vec3, rotation: Vec3, aspect_ratio: f64) -> ImagePlane {
         // working for this is in whiteboard
         let fov_rad = self.fov * (std::f64::consts::PI / 180.);
         let width = 2. * f64::tan(fov_rad / 2.);
@@ -63,13 +251,13 @@ impl RayTracer {
         let height = width * aspect_ratio;
         let half_height = height / 2.;
 
-        let right = self.right();
-        let up = self.up();
-        let forward = self.forward();
+        let right = RayTracer::right_at(rotation);
+        let up = RayTracer::up_at(rotation);
+        let forward = RayTracer::forward_at(rotation);
 
         // the image plane is 1 unit away from the camera
         // this is - not + because the camera point in the -forward direction
-        let center = self.camera - forward;
+        let center = camera - forward;
 
         ImagePlane {
             left: center - (right * half_width),
@@ -84,12 +272,140 @@ impl RayTracer {
         (surface_normal * surface_normal.dot(ray)) * 2. - ray
     }
 
+    fn is_in_shadow(
+        &self,
+        drawables: &[Drawable],
+        point: Vec3,
+        time: f64,
+        point_to_light: Vec3,
+    ) -> bool {
+        self.ray_hit(
+            drawables,
+            &Ray {
+                origin: point,
+                direction: point_to_light.normalize(),
+                time,
+            },
+        )
+        .is_some()
+    }
+
+    /// Every light reaching a point that isn't in shadow, as (point to light, intensity),
+    /// including samples of any emissive geometry
+    fn light_samples(
+        &self,
+        drawables: &[Drawable],
+        point: Vec3,
+        time: f64,
+    ) -> Vec<(Vec3, (f64, f64, f64))> {
+        let mut samples: Vec<(Vec3, (f64, f64, f64))> = self
+            .scene
+            .lights
+            .iter()
+            .filter_map(|light| {
+                let point_to_light = light.point_to_light(point);
+
+                // ignore this light if object is in shadow
+                if self.is_in_shadow(drawables, point, time, point_to_light) {
+                    return None;
+                }
+
+                // anything in the way like smoke makes the light dimmer
+                let transmittance = self.light_transmittance(point, light, point_to_light);
+                let intensity = light.intensity(point);
+
+                Some((
+                    point_to_light,
+                    (
+                        intensity.0 * transmittance,
+                        intensity.1 * transmittance,
+                        intensity.2 * transmittance,
+                    ),
+                ))
+            })
+            .collect();
+
+        for emitter in drawables {
+            if emitter.material.is_emissive() {
+                self.sample_emitter(drawables, point, time, emitter, &mut samples);
+            }
+        }
+
+        samples
+    }
+
+    /// Treats emissive geometry as an area light,
+    /// by sending rays at the cone of directions its bounding sphere covers
+    fn sample_emitter(
+        &self,
+        drawables: &[Drawable],
+        point: Vec3,
+        time: f64,
+        emitter: &Drawable,
+        samples: &mut Vec<(Vec3, (f64, f64, f64))>,
+    ) {
+        let geometry = emitter.geometry;
+        let transform = emitter.transform_at(&self.scene, time);
+        let (center, radius) = match geometry.bounding_sphere_transformed(&transform) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        let to_center = center - point;
+        let distance = to_center.length();
+
+        // points inside the bounds are almost always on the emitter itself
+        if distance <= radius {
+            return;
+        }
+
+        let cos_max = (1. - (radius / distance).powi(2)).sqrt();
+        let solid_angle = 2. * std::f64::consts::PI * (1. - cos_max);
+        let count = self.scene.emission_samples.max(1);
+
+        // the radiance over the solid angle, on the same scale as the lights' intensity
+        let scale = solid_angle / (std::f64::consts::PI * count as f64);
+
+        for _ in 0..count {
+            let direction = sample_cone(to_center / distance, cos_max);
+
+            // it only counts if the emitter is the first thing in the way
+            match self.ray_hit(
+                drawables,
+                &Ray {
+                    origin: point,
+                    direction,
+                    time,
+                },
+            ) {
+                // instances share their geometry, so the transform tells them apart
+                Some(hit) if std::ptr::eq(hit.geometry, geometry) && hit.transform == transform => {
+                    // evaluated where it was hit, as the graph or textures can change the emission
+                    let surface = hit.surface(hit.shading_normal(), -direction);
+                    let strength = surface.emission_strength * scale;
+
+                    samples.push((
+                        direction,
+                        (
+                            surface.emission.0 * strength,
+                            surface.emission.1 * strength,
+                            surface.emission.2 * strength,
+                        ),
+                    ));
+                }
+                _ => (),
+            }
+        }
+    }
+
     fn calculate_light(
         &self,
+        drawables: &[Drawable],
         point: Vec3,
+        time: f64,
         normal: Vec3,
         camera_pos: Vec3,
-        material: &Material,
+        surface: &Surface,
     ) -> (f64, f64, f64) {
         let mut result = (
             self.scene.ambient_light.0,
@@ -97,20 +413,7 @@ impl RayTracer {
             self.scene.ambient_light.2,
         );
 
-        for light in self.scene.lights.iter() {
-            let point_to_light = light.point_to_light(point);
-
-            // ignore this light if object is in shadow
-            match self.ray_hit(&Ray {
-                origin: point,
-                direction: point_to_light.normalize(),
-            }) {
-                Some((_object, _point)) => continue,
-                None => (),
-            }
-
-            let intensity = light.intensity(point);
-
+        for (point_to_light, intensity) in self.light_samples(drawables, point, time) {
             let strength = (normal.dot(point_to_light)
                 / (normal.length() * point_to_light.length()))
             .clamp(0., 1.);
@@ -121,7 +424,7 @@ impl RayTracer {
             let specular = (reflection_vector.dot(camera_vector)
                 / (reflection_vector.length() * camera_vector.length()))
             .clamp(0., 1.)
-            .powf(material.specular);
+            .powf(surface.specular);
 
             result.0 += intensity.0 * (strength + specular);
             result.1 += intensity.1 * (strength + specular);
@@ -131,107 +434,526 @@ impl RayTracer {
         result
     }
 
-    fn ray_hit(&self, ray: &Ray) -> Option<(&Object, Vec3)> {
+    fn ray_hit<'a>(&self, drawables: &[Drawable<'a>], ray: &Ray) -> Option<Hit<'a>> {
         let mut hit: Option<Hit> = None;
 
-        for object in &self.scene.objects {
-            match object.geometry.intersect(ray) {
-                Some((distance, hit_point)) => {
-                    if distance < 1e-6 {
-                        continue;
+        fn check_hit<'a>(hit: &mut Option<Hit<'a>>, new_hit: Hit<'a>) {
+            if new_hit.distance < 1e-6 {
+                return;
+            }
+            match hit {
+                Some(h) if h.distance <= new_hit.distance => (),
+                _ => *hit = Some(new_hit),
+            }
+        }
+
+        for drawable in drawables {
+            let transform = drawable.transform_at(&self.scene, ray.time);
+
+            if let Some((distance, point, triangle)) =
+                drawable.geometry.hit_transformed(&transform, ray)
+            {
+                check_hit(
+                    &mut hit,
+                    Hit {
+                        distance,
+                        point,
+                        geometry: drawable.geometry,
+                        transform,
+                        material: drawable.material,
+                        triangle,
+                    },
+                );
+            }
+        }
+
+        hit
+    }
+
+    fn trace_ray(&self, drawables: &[Drawable], ray: &Ray, depth: u32) -> (f64, f64, f64) {
+        let hit = self.ray_hit(drawables, ray);
+        let distance = hit.as_ref().map_or(f64::INFINITY, |hit| hit.distance);
+
+        let colour = match hit {
+            Some(hit) => {
+                let hit_point = hit.point;
+                let normal = hit.shading_normal();
+                let surface = hit.surface(normal, -ray.direction);
+
+                let colour = match surface.model {
+                    MaterialModel::Phong => {
+                        self.shade_phong(drawables, ray, hit_point, normal, &surface, depth)
                     }
-                    match &hit {
-                        Some(h) => {
-                            if distance < h.distance {
-                                hit = Some(Hit {
-                                    distance,
-                                    point: hit_point,
-                                    object,
-                                });
-                            }
-                        }
-                        None => {
-                            hit = Some(Hit {
-                                distance,
-                                point: hit_point,
-                                object,
-                            });
-                        }
+                    MaterialModel::Pbr => {
+                        self.shade_pbr(drawables, ray, hit_point, normal, &surface, depth)
                     }
-                }
-                None => continue,
-            };
+                };
+
+                (
+                    colour.0 + surface.emission.0 * surface.emission_strength,
+                    colour.1 + surface.emission.1 * surface.emission_strength,
+                    colour.2 + surface.emission.2 * surface.emission_strength,
+                )
+            }
+            None => self.scene.background_colour,
+        };
+
+        self.apply_media(drawables, ray, distance, colour)
+    }
+
+    /// The (extinction, scattering) of all the media at a point,
+    /// where scattering is per channel so media can be coloured
+    fn medium_at(&self, point: Vec3) -> (f64, (f64, f64, f64)) {
+        let mut extinction = 0.;
+        let mut scattering = (0., 0., 0.);
+
+        let mut add_medium = |density: f64, albedo: f64, colour: (f64, f64, f64)| {
+            extinction += density;
+            scattering.0 += density * albedo * colour.0;
+            scattering.1 += density * albedo * colour.1;
+            scattering.2 += density * albedo * colour.2;
+        };
+
+        if let Some(fog) = &self.scene.fog {
+            add_medium(fog.density, fog.albedo, fog.colour);
         }
 
-        match &hit {
-            Some(h) => Some((h.object, h.point)),
-            None => None,
+        for volume in &self.scene.volumes {
+            let density = volume.density_at(point);
+            if density > 0. {
+                add_medium(density, volume.albedo, volume.colour);
+            }
         }
+
+        (extinction, scattering)
     }
 
-    fn trace_ray(&self, ray: &Ray, depth: u32) -> (f64, f64, f64) {
-        match self.ray_hit(&ray) {
-            Some((object, hit_point)) => {
-                let normal = object.geometry.normal_at_point(hit_point);
+    /// How much of a light gets through the media to a point
+    fn light_transmittance(&self, point: Vec3, light: &Light, point_to_light: Vec3) -> f64 {
+        const STEPS: u32 = 8;
+
+        let direction = point_to_light.normalize();
+        let distance = match light {
+            Light::Direction { .. } => f64::INFINITY,
+            Light::Point { .. } => point_to_light.length(),
+        };
+
+        let mut optical_depth = 0.;
+
+        // directional lights are infinitely far away, so they're counted as above the fog
+        if let Some(fog) = &self.scene.fog {
+            if distance.is_finite() {
+                optical_depth += fog.density * distance;
+            }
+        }
 
-                let brightness =
-                    self.calculate_light(hit_point, normal, self.camera, &object.material);
-                let local_colour = (
-                    brightness.0 * object.material.colour.0,
-                    brightness.1 * object.material.colour.1,
-                    brightness.2 * object.material.colour.2,
+        // volumes don't move, so the time doesn't matter
+        let ray = Ray {
+            origin: point,
+            direction,
+            time: 0.,
+        };
+
+        for volume in &self.scene.volumes {
+            if let Some((start, end)) = volume.span(&ray) {
+                let end = end.min(distance);
+                let step = (end - start) / STEPS as f64;
+
+                for i in 0..STEPS {
+                    let t = start + (i as f64 + 0.5) * step;
+                    optical_depth += volume.density_at(point + direction * t) * step.max(0.);
+                }
+            }
+        }
+
+        (-optical_depth).exp()
+    }
+
+    /// Dims the colour seen along a ray by the media it goes through,
+    /// and adds the light scattered towards the camera on the way
+    fn apply_media(
+        &self,
+        drawables: &[Drawable],
+        ray: &Ray,
+        distance: f64,
+        colour: (f64, f64, f64),
+    ) -> (f64, f64, f64) {
+        // past this many 1 / densities into the fog, there's almost nothing left to see
+        const FOG_MARCH_DEPTH: f64 = 3.;
+
+        if self.scene.fog.is_none() && self.scene.volumes.is_empty() {
+            return colour;
+        }
+
+        let length = ray.direction.length();
+        let ray = Ray {
+            origin: ray.origin,
+            direction: ray.direction / length,
+            time: ray.time,
+        };
+        let end = distance * length;
+
+        // only march where there's something to see
+        let mut march_end: f64 = 0.;
+
+        if let Some(fog) = &self.scene.fog {
+            if fog.density > 0. {
+                march_end = FOG_MARCH_DEPTH / fog.density;
+            }
+        }
+
+        for volume in &self.scene.volumes {
+            if let Some((_, exit)) = volume.span(&ray) {
+                march_end = march_end.max(exit);
+            }
+        }
+
+        let march_end = march_end.min(end);
+        let steps = self.scene.volume_steps.max(1);
+        let step = march_end / steps as f64;
+
+        let mut transmittance = 1.;
+        let mut scattered = (0., 0., 0.);
+
+        for i in 0..steps {
+            // jittered, so the steps don't show up as bands
+            let t = (i as f64 + rand::random::<f64>()) * step;
+            let point = ray.origin + ray.direction * t;
+
+            let (extinction, scattering) = self.medium_at(point);
+            if extinction <= 0. {
+                continue;
+            }
+
+            // ambient light comes from everywhere, so isn't changed by the phase function
+            let mut light = self.scene.ambient_light;
+
+            for scene_light in &self.scene.lights {
+                let point_to_light = scene_light.point_to_light(point);
+
+                // this is what makes the beams, as the shadows go through the media too
+                if self.is_in_shadow(drawables, point, ray.time, point_to_light) {
+                    continue;
+                }
+
+                let intensity = scene_light.intensity(point);
+                let light_transmittance =
+                    self.light_transmittance(point, scene_light, point_to_light);
+
+                // an isotropic phase function, scaled by pi like the surfaces are
+                light.0 += intensity.0 * light_transmittance / 4.;
+                light.1 += intensity.1 * light_transmittance / 4.;
+                light.2 += intensity.2 * light_transmittance / 4.;
+            }
+
+            // the light scattered over the whole step, which is also dimmed along it
+            let step_transmittance = (-extinction * step).exp();
+            let weight = transmittance * (1. - step_transmittance) / extinction;
+
+            scattered.0 += scattering.0 * light.0 * weight;
+            scattered.1 += scattering.1 * light.1 * weight;
+            scattered.2 += scattering.2 * light.2 * weight;
+
+            transmittance *= step_transmittance;
+        }
+
+        // past the march there's only fog, which is lit the same everywhere apart from shadows
+        if let Some(fog) = &self.scene.fog {
+            if end > march_end && fog.density > 0. {
+                let fog_transmittance = (-fog.density * (end - march_end)).exp();
+                let weight = transmittance * (1. - fog_transmittance) * fog.albedo;
+
+                let light = self.scene.lights.iter().fold(
+                    self.scene.ambient_light,
+                    |light, scene_light| {
+                        let intensity = scene_light.intensity(ray.origin);
+                        (
+                            light.0 + intensity.0 / 4.,
+                            light.1 + intensity.1 / 4.,
+                            light.2 + intensity.2 / 4.,
+                        )
+                    },
                 );
 
-                if object.material.metallic <= 0. || depth >= self.scene.reflection_limit {
-                    return local_colour;
+                scattered.0 += fog.colour.0 * light.0 * weight;
+                scattered.1 += fog.colour.1 * light.1 * weight;
+                scattered.2 += fog.colour.2 * light.2 * weight;
+
+                transmittance *= fog_transmittance;
+            }
+        }
+
+        (
+            colour.0 * transmittance + scattered.0,
+            colour.1 * transmittance + scattered.1,
+            colour.2 * transmittance + scattered.2,
+        )
+    }
+
+    fn shade_phong(
+        &self,
+        drawables: &[Drawable],
+        ray: &Ray,
+        point: Vec3,
+        normal: Vec3,
+        surface: &Surface,
+        depth: u32,
+    ) -> (f64, f64, f64) {
+        let brightness =
+            self.calculate_light(drawables, point, ray.time, normal, self.camera, surface);
+        let local_colour = (
+            brightness.0 * surface.colour.0,
+            brightness.1 * surface.colour.1,
+            brightness.2 * surface.colour.2,
+        );
+
+        if surface.metallic <= 0. || depth >= self.scene.reflection_limit {
+            return local_colour;
+        }
+
+        let reflected_colour =
+            self.trace_reflection(drawables, ray, point, normal, surface.roughness, depth);
+
+        (
+            local_colour.0 * (1. - surface.metallic) + reflected_colour.0 * surface.metallic,
+            local_colour.1 * (1. - surface.metallic) + reflected_colour.1 * surface.metallic,
+            local_colour.2 * (1. - surface.metallic) + reflected_colour.2 * surface.metallic,
+        )
+    }
+
+    fn shade_pbr(
+        &self,
+        drawables: &[Drawable],
+        ray: &Ray,
+        point: Vec3,
+        normal: Vec3,
+        surface: &Surface,
+        depth: u32,
+    ) -> (f64, f64, f64) {
+        let to_view = (-ray.direction).normalize();
+
+        // ambient light is only diffused, so metals don't get any
+        let diffuse = 1. - surface.metallic;
+        let mut result = (
+            self.scene.ambient_light.0 * surface.colour.0 * diffuse,
+            self.scene.ambient_light.1 * surface.colour.1 * diffuse,
+            self.scene.ambient_light.2 * surface.colour.2 * diffuse,
+        );
+
+        for (point_to_light, intensity) in self.light_samples(drawables, point, ray.time) {
+            let reflected = cook_torrance(surface, normal, to_view, point_to_light.normalize());
+
+            result.0 += intensity.0 * reflected.0;
+            result.1 += intensity.1 * reflected.1;
+            result.2 += intensity.2 * reflected.2;
+        }
+
+        if depth >= self.scene.reflection_limit {
+            return result;
+        }
+
+        let reflectance = environment_reflectance(surface, normal.dot(to_view));
+        let reflected_colour =
+            self.trace_reflection(drawables, ray, point, normal, surface.roughness, depth);
+
+        (
+            result.0 + reflected_colour.0 * reflectance.0,
+            result.1 + reflected_colour.1 * reflectance.1,
+            result.2 + reflected_colour.2 * reflectance.2,
+        )
+    }
+
+    fn trace_reflection(
+        &self,
+        drawables: &[Drawable],
+        ray: &Ray,
+        point: Vec3,
+        normal: Vec3,
+        roughness: f64,
+        depth: u32,
+    ) -> (f64, f64, f64) {
+        let mirror_direction = RayTracer::reflect_ray(-ray.direction, normal);
+
+        // only the first bounce is split into many rays,
+        // otherwise the cost would grow exponentially with the reflection limit
+        let samples = if roughness <= 0. || depth > 0 {
+            1
+        } else {
+            self.scene.reflection_samples.max(1)
+        };
+
+        let mut result = (0., 0., 0.);
+
+        for _ in 0..samples {
+            let direction = if roughness <= 0. {
+                mirror_direction
+            } else {
+                let microfacet_normal = sample_ggx(normal, roughness);
+                let direction = RayTracer::reflect_ray(-ray.direction, microfacet_normal);
+
+                // a reflection into the surface isn't possible, so use the mirror one instead
+                if direction.dot(normal) > 0. {
+                    direction
+                } else {
+                    mirror_direction
                 }
+            };
 
-                let reflection_ray = Ray {
-                    origin: hit_point,
-                    direction: RayTracer::reflect_ray(-ray.direction, normal),
-                };
-                let reflected_colour = self.trace_ray(&reflection_ray, depth + 1);
+            let colour = self.trace_ray(
+                drawables,
+                &Ray {
+                    origin: point,
+                    direction,
+                    time: ray.time,
+                },
+                depth + 1,
+            );
 
-                (
-                    local_colour.0 * (1. - object.material.metallic)
-                        + reflected_colour.0 * object.material.metallic,
-                    local_colour.1 * (1. - object.material.metallic)
-                        + reflected_colour.1 * object.material.metallic,
-                    local_colour.2 * (1. - object.material.metallic)
-                        + reflected_colour.2 * object.material.metallic,
-                )
+            result.0 += colour.0 / samples as f64;
+            result.1 += colour.1 / samples as f64;
+            result.2 += colour.2 / samples as f64;
+        }
+
+        result
+    }
+
+    /// Everything the rays can hit, skipping instances of prototypes that don't exist
+    fn drawables(&self) -> Vec<Drawable<'_>> {
+        let scene = &self.scene;
+
+        let objects = scene.objects.iter().map(|object| {
+            Drawable::new(
+                scene,
+                &object.geometry,
+                scene.material(&object.material),
+                Placement::Object(object),
+            )
+        });
+        let instances = scene.instances.iter().filter_map(|instance| {
+            let prototype = scene.prototype(&instance.prototype)?;
+            Some(Drawable::new(
+                scene,
+                &prototype.geometry,
+                scene.material(instance.material(prototype)),
+                Placement::Instance(instance),
+            ))
+        });
+
+        objects.chain(instances).collect()
+    }
+
+    /// The camera's position and rotation at a time, in seconds from the frame
+    fn camera_at(&self, time: f64) -> (Vec3, Vec3) {
+        let blur = &self.scene.motion_blur;
+
+        match &blur.camera {
+            Some(motion) => {
+                let transform = Transform {
+                    position: self.camera,
+                    rotation: self.rotation,
+                    ..Transform::default()
+                };
+                let transform = motion.transform_at(&transform, time, blur);
+                (transform.position, transform.rotation)
             }
-            None => self.scene.background_colour,
+            None => (self.camera, self.rotation),
+        }
+    }
+
+    fn get_view(&self, camera: Vec3, rotation: Vec3) -> View {
+        let image_plane =
+            self.get_image_plane(camera, rotation, self.height as f64 / self.width as f64);
+
+        View {
+            camera,
+            // working for this in whiteboard
+            top_left: image_plane.left + image_plane.top - image_plane.center,
+            width_world_space: (image_plane.right - image_plane.left).length(),
+            height_world_space: (image_plane.top - image_plane.bottom).length(),
+            right: RayTracer::right_at(rotation),
+            up: RayTracer::up_at(rotation),
         }
     }
 
+    /// Offset is where in the pixel the ray goes through, from (0, 0) to (1, 1)
     fn render_pixel(
         &self,
+        drawables: &[Drawable],
         x: u32,
         y: u32,
-        top_left: Vec3,
-        width_world_space: f64,
-        height_world_space: f64,
-        right: Vec3,
-        up: Vec3,
+        offset: (f64, f64),
+        view: &View,
+        time: f64,
     ) -> (f64, f64, f64) {
-        let x_screen_space = (x as f64 + 0.5) / self.width as f64;
-        let y_screen_space = (y as f64 + 0.5) / self.height as f64;
+        let x_screen_space = (x as f64 + offset.0) / self.width as f64;
+        let y_screen_space = (y as f64 + offset.1) / self.height as f64;
 
-        let x_offset = right * (x_screen_space * width_world_space);
+        let x_offset = view.right * (x_screen_space * view.width_world_space);
         // mul -1 because it's offset down
-        let y_offset = (-up) * (y_screen_space * height_world_space);
+        let y_offset = (-view.up) * (y_screen_space * view.height_world_space);
 
-        let pixel_world_space = top_left + x_offset + y_offset;
+        let pixel_world_space = view.top_left + x_offset + y_offset;
 
-        let direction = (pixel_world_space - self.camera).normalize();
+        let direction = (pixel_world_space - view.camera).normalize();
 
         let ray = Ray {
-            origin: self.camera,
+            origin: view.camera,
             direction,
+            time,
         };
 
-        self.trace_ray(&ray, 0)
+        self.trace_ray(drawables, &ray, 0)
+    }
+
+    /// Traces every pixel, row by row, and converts each colour for the slice
+    fn render_into<T: Send>(
+        &self,
+        pixels: &mut [T],
+        convert: impl Fn((f64, f64, f64)) -> T + Sync,
+    ) {
+        let view = self.get_view(self.camera, self.rotation);
+        let drawables = self.drawables();
+        let blur = &self.scene.motion_blur;
+        let samples = self.scene.pixel_samples.max(1);
+
+        pixels
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(index, colour)| {
+                let y = (index as u32) / (self.width as u32);
+                let x = index as u32 % self.width;
+
+                let mut result = (0., 0., 0.);
+
+                for _ in 0..samples {
+                    let time = blur.sample_time();
+
+                    // the view only changes if the camera moves
+                    let moved_view;
+                    let view = if blur.camera.is_some() {
+                        let (camera, rotation) = self.camera_at(time);
+                        moved_view = self.get_view(camera, rotation);
+                        &moved_view
+                    } else {
+                        &view
+                    };
+
+                    // a single ray goes through the middle, so the image stays sharp
+                    let offset = if samples == 1 {
+                        (0.5, 0.5)
+                    } else {
+                        (rand::random(), rand::random())
+                    };
+
+                    let pixel = self.render_pixel(&drawables, x, y, offset, view, time);
+
+                    result.0 += pixel.0 / samples as f64;
+                    result.1 += pixel.1 / samples as f64;
+                    result.2 += pixel.2 / samples as f64;
+                }
+
+                *colour = convert(result);
+            });
     }
 
     pub fn rs_render(&self, image: &mut eframe::epaint::ColorImage) {
@@ -242,40 +964,22 @@ impl RayTracer {
             );
         }
 
-        let image_plane = self.get_image_plane(self.height as f64 / self.width as f64);
-
-        // working for this in whiteboard
-        let top_left_point = image_plane.left + image_plane.top - image_plane.center;
-
-        let width_world_space = (image_plane.right - image_plane.left).length();
-        let height_world_space = (image_plane.top - image_plane.bottom).length();
-        let right = self.right();
-        let up = self.up();
-
-        image
-            .pixels
-            .par_iter_mut()
-            .enumerate()
-            .for_each(|(index, colour)| {
-                let y = (index as u32) / (self.width as u32);
-                let x = index as u32 % self.width;
+        self.render_into(&mut image.pixels, |pixel| {
+            eframe::epaint::Color32::from_rgb(
+                (pixel.0 * 255.) as u8,
+                (pixel.1 * 255.) as u8,
+                (pixel.2 * 255.) as u8,
+            )
+        });
+    }
 
-                let pixel = self.render_pixel(
-                    x,
-                    y,
-                    top_left_point,
-                    width_world_space,
-                    height_world_space,
-                    right,
-                    up,
-                );
+    /// The colours as they are, without being clamped to 1
+    pub fn render_hdr(&self) -> image::Rgb32FImage {
+        let mut pixels = vec![[0f32; 3]; self.width as usize * self.height as usize];
+        self.render_into(&mut pixels, |(r, g, b)| [r as f32, g as f32, b as f32]);
 
-                *colour = eframe::epaint::Color32::from_rgb(
-                    (pixel.0 * 255.) as u8,
-                    (pixel.1 * 255.) as u8,
-                    (pixel.2 * 255.) as u8,
-                );
-            });
+        image::Rgb32FImage::from_raw(self.width, self.height, pixels.concat())
+            .expect("there's a pixel for every position")
     }
 }
 
@@ -306,14 +1010,22 @@ impl Options {
             width,
             height,
             scene: Scene {
+                includes: vec![],
                 objects: vec![
                     Object {
                         name: "sphere".to_string(),
-                        material: Material {
+                        material: MaterialRef::Own(Box::new(Material {
+                            model: MaterialModel::Phong,
                             colour: (1.0, 0.5212054252624512, 0.0),
                             specular: 5.0,
                             metallic: 1.0,
-                        },
+                            roughness: 0.,
+                            emission: (0., 0., 0.),
+                            emission_strength: 0.,
+                            normal_map: None,
+                            textures: MaterialTextures::default(),
+                            graph: None,
+                        })),
                         geometry: Geometry::Sphere {
                             center: Vec3 {
                                 x: 1.5,
@@ -322,14 +1034,23 @@ impl Options {
                             },
                             radius: 1.0,
                         },
+                        transform: Transform::default(),
+                        motion: None,
                     },
                     Object {
                         name: "sphere".to_string(),
-                        material: Material {
+                        material: MaterialRef::Own(Box::new(Material {
+                            model: MaterialModel::Phong,
                             colour: (1.0, 0.3486607074737549, 0.0),
                             specular: 800.0,
                             metallic: 0.2,
-                        },
+                            roughness: 0.,
+                            emission: (0., 0., 0.),
+                            emission_strength: 0.,
+                            normal_map: None,
+                            textures: MaterialTextures::default(),
+                            graph: None,
+                        })),
                         geometry: Geometry::Sphere {
                             center: Vec3 {
                                 x: 3.1,
@@ -338,14 +1059,23 @@ impl Options {
                             },
                             radius: 1.0,
                         },
+                        transform: Transform::default(),
+                        motion: None,
                     },
                     Object {
                         name: "sphere".to_string(),
-                        material: Material {
+                        material: MaterialRef::Own(Box::new(Material {
+                            model: MaterialModel::Phong,
                             colour: (0.0, 0.6445307731628418, 1.0),
                             specular: 80.0,
                             metallic: 0.,
-                        },
+                            roughness: 0.,
+                            emission: (0., 0., 0.),
+                            emission_strength: 0.,
+                            normal_map: None,
+                            textures: MaterialTextures::default(),
+                            graph: None,
+                        })),
                         geometry: Geometry::Sphere {
                             center: Vec3 {
                                 x: -8.3,
@@ -354,14 +1084,23 @@ impl Options {
                             },
                             radius: 1.0,
                         },
+                        transform: Transform::default(),
+                        motion: None,
                     },
                     Object {
                         name: "plane".to_string(),
-                        material: Material {
+                        material: MaterialRef::Own(Box::new(Material {
+                            model: MaterialModel::Phong,
                             colour: (0.8, 0.8, 1.),
                             specular: 50.,
                             metallic: 0.2,
-                        },
+                            roughness: 0.,
+                            emission: (0., 0., 0.),
+                            emission_strength: 0.,
+                            normal_map: None,
+                            textures: MaterialTextures::default(),
+                            graph: None,
+                        })),
                         geometry: Geometry::Plane {
                             center: Vec3 {
                                 x: 0.,
@@ -375,8 +1114,13 @@ impl Options {
                             },
                             size: 5.,
                         },
+                        transform: Transform::default(),
+                        motion: None,
                     },
                 ],
+                materials: vec![],
+                prototypes: vec![],
+                instances: vec![],
                 lights: vec![
                     Light::Direction {
                         intensity: (0.4, 0.4, 0.4),
@@ -399,7 +1143,15 @@ impl Options {
                 background_colour: (0.5, 0.8, 1.),
                 ambient_light: (0.2, 0.2, 0.2),
                 reflection_limit: 4,
+                reflection_samples: 8,
+                emission_samples: 4,
+                fog: None,
+                volumes: vec![],
+                volume_steps: 32,
+                pixel_samples: 1,
+                motion_blur: MotionBlur::default(),
                 do_objects_spin: false,
+                animation: Animation::default(),
             },
         }
     }
@@ -435,3 +1187,49 @@ pub fn render_image() {
         .unwrap()
         .add(end, frame_time as f32);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_tracer::SceneFormat;
+
+    fn vec3(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    #[test]
+    fn emission_from_the_graph_lights_the_scene() {
+        // the flat emission is black, only the graph makes it glow orange
+        let text = r#"{
+            "objects": [{
+                "name": "lamp",
+                "geometry": { "Sphere": { "center": { "x": 0, "y": 0, "z": 5 }, "radius": 1 } },
+                "material": {
+                    "colour": [1, 1, 1], "specular": 10, "metallic": 0,
+                    "emission": [0, 0, 0], "emission_strength": 2,
+                    "graph": { "nodes": [], "emission": { "Colour": [1, 0.5, 0] } }
+                }
+            }],
+            "emission_samples": 8
+        }"#;
+        let ray_tracer = RayTracer {
+            camera: vec3(0., 0., 0.),
+            rotation: vec3(0., 0., 0.),
+            fov: 60.,
+            width: 1,
+            height: 1,
+            scene: Scene::from_text(text, SceneFormat::Json).unwrap(),
+        };
+        let drawables = ray_tracer.drawables();
+
+        let samples = ray_tracer.light_samples(&drawables, vec3(0., 0., 0.), 0.);
+
+        // every sample is aimed inside the sphere's cone, so they all hit it
+        assert_eq!(samples.len(), 8);
+        for (_, intensity) in samples {
+            assert!(intensity.0 > 0.);
+            assert!((intensity.1 - intensity.0 * 0.5).abs() < 1e-12);
+            assert_eq!(intensity.2, 0.);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use crate::ray_tracer::Vec3;
+
+/// A random microfacet normal from the GGX distribution around normal,
+/// a roughness of 0 always gives the normal itself.
+pub fn sample_ggx(normal: Vec3, roughness: f64) -> Vec3 {
+    // squaring the roughness makes it look more linear
+    let alpha = roughness.powi(2);
+    let (u1, u2): (f64, f64) = (rand::random(), rand::random());
+
+    let cos_theta = ((1. - u1) / (1. + (alpha.powi(2) - 1.) * u1)).sqrt();
+    let sin_theta = (1. - cos_theta.powi(2)).max(0.).sqrt();
+    let phi = 2. * std::f64::consts::PI * u2;
+
+    let (tangent, bitangent) = normal.orthonormal_basis();
+
+    (tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + normal * cos_theta)
+        .normalize()
+}
+
+/// A random direction in the cone around axis,
+/// out to the angle with a cosine of cos_max
+pub fn sample_cone(axis: Vec3, cos_max: f64) -> Vec3 {
+    let (u1, u2): (f64, f64) = (rand::random(), rand::random());
+
+    let cos_theta = 1. - u1 * (1. - cos_max);
+    let sin_theta = (1. - cos_theta.powi(2)).max(0.).sqrt();
+    let phi = 2. * std::f64::consts::PI * u2;
+
+    let (tangent, bitangent) = axis.orthonormal_basis();
+
+    (tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + axis * cos_theta)
+        .normalize()
+}
@@ -1,12 +1,152 @@
-use crate::ray_tracer::{Light, Object};
+use crate::ray_tracer::{
+    deserialize_colour, Animation, Axis, Fog, Include, Instance, LibraryMaterial, Light, Mat44,
+    MotionBlur, Object, Prototype, Vec3, Volume,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct Scene {
+    /// other scene files, added to this one when it's loaded
+    #[serde(default)]
+    pub includes: Vec<Include>,
+    #[serde(default)]
     pub objects: Vec<Object>,
+    /// materials shared by name
+    #[serde(default)]
+    pub materials: Vec<LibraryMaterial>,
+    #[serde(default)]
+    pub prototypes: Vec<Prototype>,
+    #[serde(default)]
+    pub instances: Vec<Instance>,
+    #[serde(default)]
     pub lights: Vec<Light>,
+    #[serde(default, deserialize_with = "deserialize_colour")]
     pub background_colour: (f64, f64, f64),
+    #[serde(default, deserialize_with = "deserialize_colour")]
     pub ambient_light: (f64, f64, f64),
+    #[serde(default = "default_reflection_limit")]
     pub reflection_limit: u32,
+    /// how many rays are averaged for rough reflections
+    #[serde(default = "default_reflection_samples")]
+    pub reflection_samples: u32,
+    /// how many rays are sent at each emissive object to light a point
+    #[serde(default = "default_emission_samples")]
+    pub emission_samples: u32,
+    #[serde(default)]
+    pub fog: Option<Fog>,
+    #[serde(default)]
+    pub volumes: Vec<Volume>,
+    /// how many steps the media along each ray are split into
+    #[serde(default = "default_volume_steps")]
+    pub volume_steps: u32,
+    /// how many rays go through each pixel, spread over it and over the time the shutter is open
+    #[serde(default = "default_pixel_samples")]
+    pub pixel_samples: u32,
+    #[serde(default)]
+    pub motion_blur: MotionBlur,
+    #[serde(default)]
     pub do_objects_spin: bool,
+    /// keyframes for anything in the scene, or the camera
+    #[serde(default)]
+    pub animation: Animation,
+}
+
+fn default_reflection_limit() -> u32 {
+    4
+}
+
+fn default_reflection_samples() -> u32 {
+    8
+}
+
+fn default_emission_samples() -> u32 {
+    4
+}
+
+fn default_volume_steps() -> u32 {
+    32
+}
+
+fn default_pixel_samples() -> u32 {
+    1
+}
+
+/// Turns a point around the y axis, by an angle in radians
+pub fn spin_point(position: Vec3, angle: f64) -> Vec3 {
+    let length = position.length();
+
+    let spun = position.transform_point(Mat44::create_rotation(Axis::Y, angle));
+
+    // fix rounding errors?
+    if length > 0. {
+        spun * (length / spun.length())
+    } else {
+        spun
+    }
+}
+
+impl Scene {
+    /// How fast objects spin when do_objects_spin is on, in radians a second
+    pub const SPIN_SPEED: f64 = 0.5 * std::f64::consts::PI;
+
+    /// Turns every object and instance around the y axis, by an angle in radians.
+    /// They go round the world's axis wherever their transform puts them, without turning.
+    pub fn spin(&mut self, angle: f64) {
+        let spin = |position: &mut Vec3| *position = spin_point(*position, angle);
+
+        for object in &mut self.objects {
+            // the geometry is moved when it can be, so plain transforms stay plain
+            if object.transform.is_identity() {
+                spin(object.geometry.position_as_mut());
+            } else {
+                let offset = object.spin_offset(&object.transform, angle);
+                object.transform.position += offset;
+            }
+        }
+        self.instances
+            .iter_mut()
+            .for_each(|instance| spin(&mut instance.transform.position));
+    }
+
+    pub fn prototype(&self, name: &str) -> Option<&Prototype> {
+        self.prototypes
+            .iter()
+            .find(|prototype| prototype.name == name)
+    }
+
+    /// The name, or the name with a number after it if a prototype already has it
+    pub fn unique_prototype_name(&self, name: &str) -> String {
+        if self.prototype(name).is_none() {
+            return name.to_string();
+        }
+
+        (2..)
+            .map(|number| format!("{} {}", name, number))
+            .find(|name| self.prototype(name).is_none())
+            .unwrap()
+    }
+
+    /// Renames a prototype, and the instances that draw it
+    pub fn rename_prototype(&mut self, old_name: &str, new_name: &str) {
+        self.instances
+            .iter_mut()
+            .filter(|instance| instance.prototype == old_name)
+            .for_each(|instance| instance.prototype = new_name.to_string());
+
+        if let Some(prototype) = self
+            .prototypes
+            .iter_mut()
+            .find(|prototype| prototype.name == old_name)
+        {
+            prototype.name = new_name.to_string();
+        }
+    }
+
+    /// Removes a prototype, along with the instances that draw it
+    pub fn remove_prototype(&mut self, index: usize) {
+        let prototype = self.prototypes.remove(index);
+
+        self.instances
+            .retain(|instance| instance.prototype != prototype.name);
+    }
 }
@@ -0,0 +1,153 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::ray_tracer::Scene;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SceneFormat {
+    Json,
+    Ron,
+    Toml,
+    Yaml,
+}
+
+impl SceneFormat {
+    /// The format a file is in, from its extension
+    pub fn from_path(path: &Path) -> Result<SceneFormat, SceneFileError> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match extension.as_str() {
+            "json" => Ok(SceneFormat::Json),
+            "ron" => Ok(SceneFormat::Ron),
+            "toml" => Ok(SceneFormat::Toml),
+            "yaml" | "yml" => Ok(SceneFormat::Yaml),
+            _ => Err(SceneFileError::UnknownFormat(extension)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(std::io::Error),
+    UnknownFormat(String),
+    /// the file couldn't be read as a scene, or the scene couldn't be written in the format
+    Invalid(String),
+    /// something went wrong in an included file
+    Include {
+        path: String,
+        error: Box<SceneFileError>,
+    },
+    /// the files that include each other, starting and ending with the same one
+    IncludeCycle(Vec<String>),
+    /// an include names an object or instance that isn't in its file
+    UnknownObject(String),
+    /// an included object or instance is animated, but the include moves it
+    AnimatedInclude(String),
+    /// an included library material or prototype isn't the same as the one with its name
+    NameCollision(&'static str, String),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneFileError::Io(error) => write!(f, "{}", error),
+            SceneFileError::UnknownFormat(extension) => write!(
+                f,
+                "can't use .{} files, only .json, .ron, .toml and .yaml",
+                extension
+            ),
+            SceneFileError::Invalid(message) => write!(f, "invalid scene: {}", message),
+            SceneFileError::Include { path, error } => write!(f, "in {}: {}", path, error),
+            SceneFileError::IncludeCycle(paths) => {
+                write!(f, "scenes include each other: {}", paths.join(" -> "))
+            }
+            SceneFileError::UnknownObject(name) => {
+                write!(f, "there's no object or instance called \"{}\"", name)
+            }
+            SceneFileError::AnimatedInclude(name) => write!(
+                f,
+                "\"{}\" is animated, so it can't be included with a transform",
+                name
+            ),
+            SceneFileError::NameCollision(kind, name) => write!(
+                f,
+                "there's already a different {} called \"{}\"",
+                kind, name
+            ),
+        }
+    }
+}
+
+fn invalid(error: impl fmt::Display) -> SceneFileError {
+    SceneFileError::Invalid(error.to_string())
+}
+
+/// Toml has no null, so missing options are left out instead
+fn remove_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(remove_nulls);
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(remove_nulls),
+        _ => {}
+    }
+}
+
+impl Scene {
+    pub fn from_text(text: &str, format: SceneFormat) -> Result<Scene, SceneFileError> {
+        match format {
+            SceneFormat::Json => serde_json::from_str(text).map_err(invalid),
+            SceneFormat::Ron => ron::from_str(text).map_err(invalid),
+            // toml can only read enums without fields, so it's read as json would be
+            SceneFormat::Toml => {
+                let value = toml::from_str::<toml::Value>(text).map_err(invalid)?;
+                let value = serde_json::to_value(value).map_err(invalid)?;
+                serde_json::from_value(value).map_err(invalid)
+            }
+            SceneFormat::Yaml => serde_yaml::from_str(text).map_err(invalid),
+        }
+    }
+
+    pub fn to_text(&self, format: SceneFormat) -> Result<String, SceneFileError> {
+        match format {
+            SceneFormat::Json => serde_json::to_string_pretty(self).map_err(invalid),
+            SceneFormat::Ron => {
+                ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new()).map_err(invalid)
+            }
+            SceneFormat::Toml => {
+                // toml can't write enums with fields directly,
+                // but it can write them as the tables json would use
+                let mut value = serde_json::to_value(self).map_err(invalid)?;
+                remove_nulls(&mut value);
+                let value = toml::Value::try_from(value).map_err(invalid)?;
+                toml::to_string_pretty(&value).map_err(invalid)
+            }
+            SceneFormat::Yaml => serde_yaml::to_string(self).map_err(invalid),
+        }
+    }
+
+    /// Loads a .json, .ron, .toml or .yaml scene, with everything it includes
+    pub fn load(path: &str) -> Result<Scene, SceneFileError> {
+        let mut scene = Scene::load_unresolved(Path::new(path))?;
+        scene.resolve_includes(path)?;
+        Ok(scene)
+    }
+
+    /// Loads a scene, leaving its includes as they are
+    pub fn load_unresolved(path: &Path) -> Result<Scene, SceneFileError> {
+        let format = SceneFormat::from_path(path)?;
+        let text = std::fs::read_to_string(path).map_err(SceneFileError::Io)?;
+        Scene::from_text(&text, format)
+    }
+
+    /// Saves the scene in the format its extension says
+    pub fn save(&self, path: &str) -> Result<(), SceneFileError> {
+        let text = self.to_text(SceneFormat::from_path(Path::new(path))?)?;
+        std::fs::write(path, text).map_err(SceneFileError::Io)
+    }
+}
@@ -0,0 +1,203 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ray_tracer::{RayTracer, Scene},
+    Time,
+};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum FrameFormat {
+    Png,
+    /// keeps colours brighter than 1, for grading later
+    Exr,
+}
+
+impl FrameFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FrameFormat::Png => "png",
+            FrameFormat::Exr => "exr",
+        }
+    }
+}
+
+/// Which frames of an animation to render, and where to put them
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SequenceSettings {
+    /// frames go in here, named frame_0000.png and so on
+    pub folder: String,
+    pub format: FrameFormat,
+    pub first_frame: u32,
+    /// rendered too
+    pub last_frame: u32,
+    pub frames_per_second: f64,
+    /// skips frames that already have a file, to carry on after stopping
+    pub resume: bool,
+}
+
+impl SequenceSettings {
+    /// Far more frames than anyone would render, to keep the numbers sane
+    pub const MAX_FRAME: u32 = 99_999;
+
+    pub fn frame_path(&self, frame: u32) -> PathBuf {
+        PathBuf::from(&self.folder).join(format!("frame_{:04}.{}", frame, self.format.extension()))
+    }
+
+    /// In seconds
+    pub fn frame_time(&self, frame: u32) -> f64 {
+        frame as f64 / self.frames_per_second
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        if self.last_frame < self.first_frame {
+            return 0;
+        }
+
+        (self.last_frame - self.first_frame).saturating_add(1)
+    }
+}
+
+impl RayTracer {
+    /// How everything that moves on its own is at a time,
+    /// taking this as the start
+    pub fn at_time(&self, time: f64) -> RayTracer {
+        let mut ray_tracer = self.clone();
+        ray_tracer.animate(time);
+        ray_tracer.blur_animation(time);
+
+        if ray_tracer.scene.do_objects_spin {
+            ray_tracer.scene.spin(Scene::SPIN_SPEED * time);
+        }
+
+        ray_tracer
+    }
+
+    /// Renders one frame of a sequence, and saves it
+    pub fn render_frame(&self, settings: &SequenceSettings, frame: u32) -> Result<(), String> {
+        let image = self.at_time(settings.frame_time(frame)).render_hdr();
+        let path = settings.frame_path(frame);
+
+        // written next to it first, so a frame that was stopped halfway isn't resumed from
+        let temporary = path.with_extension(format!("part.{}", settings.format.extension()));
+        match settings.format {
+            FrameFormat::Png => image::DynamicImage::ImageRgb32F(image)
+                .into_rgb8()
+                .save(&temporary),
+            FrameFormat::Exr => image.save(&temporary),
+        }
+        .map_err(|error| error.to_string())?;
+
+        std::fs::rename(&temporary, &path).map_err(|error| error.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SequenceProgress {
+    /// the frame being rendered now
+    pub frame: u32,
+    /// frames rendered or skipped so far
+    pub done: u32,
+    pub total: u32,
+    /// frames that already had a file
+    pub skipped: u32,
+    /// how long the last frame took, in seconds
+    pub last_frame_time: f64,
+    pub is_finished: bool,
+    pub error: Option<String>,
+}
+
+/// Renders a sequence in the background, one frame after the other
+#[derive(Clone)]
+pub struct SequenceJob {
+    progress: Arc<Mutex<SequenceProgress>>,
+    is_cancelled: Arc<AtomicBool>,
+}
+
+impl SequenceJob {
+    pub fn start(ray_tracer: RayTracer, settings: SequenceSettings) -> SequenceJob {
+        let job = SequenceJob {
+            progress: Arc::new(Mutex::new(SequenceProgress {
+                frame: settings.first_frame,
+                total: settings.frame_count(),
+                ..SequenceProgress::default()
+            })),
+            is_cancelled: Arc::new(AtomicBool::new(false)),
+        };
+
+        let background = job.clone();
+        rayon::spawn(move || background.run(&ray_tracer, &settings));
+
+        job
+    }
+
+    fn run(&self, ray_tracer: &RayTracer, settings: &SequenceSettings) {
+        let result = std::fs::create_dir_all(&settings.folder).map_err(|error| error.to_string());
+
+        let result = result.and_then(|()| {
+            for frame in settings.first_frame..=settings.last_frame {
+                if self.is_cancelled.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                self.progress.lock().unwrap().frame = frame;
+
+                let is_done = settings.resume && settings.frame_path(frame).exists();
+                let start = Time::now();
+                if !is_done {
+                    ray_tracer.render_frame(settings, frame)?;
+                }
+
+                let mut progress = self.progress.lock().unwrap();
+                progress.done += 1;
+                if is_done {
+                    progress.skipped += 1;
+                } else {
+                    progress.last_frame_time = (Time::now() - start) / 1000.;
+                }
+            }
+            Ok(())
+        });
+
+        let mut progress = self.progress.lock().unwrap();
+        progress.error = result.err();
+        progress.is_finished = true;
+    }
+
+    pub fn progress(&self) -> SequenceProgress {
+        self.progress.lock().unwrap().clone()
+    }
+
+    /// Stops after the frame being rendered now
+    pub fn cancel(&self) {
+        self.is_cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(first_frame: u32, last_frame: u32) -> SequenceSettings {
+        SequenceSettings {
+            folder: String::new(),
+            format: FrameFormat::Png,
+            first_frame,
+            last_frame,
+            frames_per_second: 30.,
+            resume: false,
+        }
+    }
+
+    #[test]
+    fn frame_counts() {
+        assert_eq!(settings(0, 0).frame_count(), 1);
+        assert_eq!(settings(10, 39).frame_count(), 30);
+        assert_eq!(settings(5, 4).frame_count(), 0);
+        assert_eq!(settings(0, u32::MAX).frame_count(), u32::MAX);
+        assert_eq!(settings(u32::MAX, u32::MAX).frame_count(), 1);
+    }
+}
@@ -16,3 +16,170 @@ pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
     Some((plus, minus))
 }
 
+/// The real roots of ax^3 + bx^2 + cx + d, in no particular order
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        return match solve_quadratic(b, c, d) {
+            Some((plus, minus)) => vec![plus, minus],
+            None => vec![],
+        };
+    }
+
+    // divide through by a, then substitute x = t - b/3
+    // to get the depressed cubic t^3 + pt + q
+    let (b, c, d) = (b / a, c / a, d / a);
+    let p = c - b.powi(2) / 3.;
+    let q = 2. * b.powi(3) / 27. - b * c / 3. + d;
+    let offset = -b / 3.;
+
+    let discriminant = (q / 2.).powi(2) + (p / 3.).powi(3);
+
+    if discriminant.abs() < 1e-12 {
+        // a repeated root
+        let u = (-q / 2.).cbrt();
+        vec![2. * u + offset, -u + offset]
+    } else if discriminant > 0. {
+        // just the one real root, from Cardano's formula
+        let sqrt_discriminant = discriminant.sqrt();
+        let u = (-q / 2. + sqrt_discriminant).cbrt();
+        let v = (-q / 2. - sqrt_discriminant).cbrt();
+        vec![u + v + offset]
+    } else {
+        // three real roots, where the trigonometric method avoids complex numbers
+        let r = (-p / 3.).sqrt();
+        let phi = (-q / (2. * r.powi(3))).clamp(-1., 1.).acos();
+
+        (0..3)
+            .map(|k| 2. * r * ((phi + 2. * std::f64::consts::PI * k as f64) / 3.).cos() + offset)
+            .collect()
+    }
+}
+
+/// The real roots of ax^4 + bx^3 + cx^2 + dx + e, sorted smallest first
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        let mut roots = solve_cubic(b, c, d, e);
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        return roots;
+    }
+
+    // divide through by a, then substitute x = y - b/4
+    // to get the depressed quartic y^4 + py^2 + qy + r
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+    let p = c - 3. * b.powi(2) / 8.;
+    let q = d - b * c / 2. + b.powi(3) / 8.;
+    let r = e - b * d / 4. + b.powi(2) * c / 16. - 3. * b.powi(4) / 256.;
+    let offset = -b / 4.;
+
+    let mut depressed_roots = vec![];
+
+    let mut push_quadratic = |a: f64, b: f64, c: f64| {
+        if let Some((plus, minus)) = solve_quadratic(a, b, c) {
+            depressed_roots.push(plus);
+            depressed_roots.push(minus);
+        }
+    };
+
+    if q.abs() < 1e-12 {
+        // biquadratic, so solve for y^2
+        if let Some((plus, minus)) = solve_quadratic(1., p, r) {
+            for z in [plus, minus] {
+                if z >= 0. {
+                    push_quadratic(1., 0., -z);
+                }
+            }
+        }
+    } else {
+        // Ferrari's method, m is a root of the resolvent cubic,
+        // and there's always a positive one when q isn't 0
+        let m = solve_cubic(1., p, p.powi(2) / 4. - r, -q.powi(2) / 8.)
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        if m > 0. {
+            let s = (2. * m).sqrt();
+            push_quadratic(1., -s, p / 2. + m + s * q / (4. * m));
+            push_quadratic(1., s, p / 2. + m - s * q / (4. * m));
+        }
+    }
+
+    let mut roots: Vec<f64> = depressed_roots
+        .into_iter()
+        .map(|root| {
+            // polish the root with a few steps of Newton's method,
+            // as the closed form loses a lot of precision
+            let mut x = root + offset;
+            for _ in 0..3 {
+                let value = (((x + b) * x + c) * x + d) * x + e;
+                let derivative = ((4. * x + 3. * b) * x + 2. * c) * x + d;
+                if derivative.abs() < 1e-12 {
+                    break;
+                }
+                x -= value / derivative;
+            }
+            x
+        })
+        .collect();
+
+    roots.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+        assert_eq!(roots.len(), expected.len(), "{:?}", roots);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-6, "{:?}", roots);
+        }
+    }
+
+    #[test]
+    fn four_real_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(solve_quartic(1., -10., 35., -50., 24.), &[1., 2., 3., 4.]);
+        // scaled, and with negative roots: 2(x + 3)(x + 1)(x - 0.5)(x - 2)
+        assert_roots(solve_quartic(2., 3., -12., -7., 6.), &[-3., -1., 0.5, 2.]);
+    }
+
+    #[test]
+    fn biquadratic() {
+        // (x^2 - 1)(x^2 - 4), where q is 0
+        assert_roots(solve_quartic(1., 0., -5., 0., 4.), &[-2., -1., 1., 2.]);
+        // (x^2 + 1)(x^2 - 9), only the positive y^2 gives roots
+        assert_roots(solve_quartic(1., 0., -8., 0., -9.), &[-3., 3.]);
+    }
+
+    #[test]
+    fn two_real_roots() {
+        // (x - 1)(x - 5)(x^2 + x + 1)
+        assert_roots(solve_quartic(1., -5., 0., -1., 5.), &[1., 5.]);
+    }
+
+    #[test]
+    fn no_real_roots() {
+        assert!(solve_quartic(1., 0., 0., 0., 1.).is_empty());
+        // (x^2 + 1)(x^2 + 4)
+        assert!(solve_quartic(1., 0., 5., 0., 4.).is_empty());
+        // (x^2 + 1)(x^2 + 2x + 2), where q isn't 0
+        assert!(solve_quartic(1., 2., 3., 2., 2.).is_empty());
+    }
+
+    #[test]
+    fn falls_back_to_a_cubic() {
+        // (x - 1)(x - 2)(x + 3)
+        assert_roots(solve_quartic(0., 1., 0., -7., 6.), &[-3., 1., 2.]);
+        // and on to a quadratic
+        assert_roots(solve_quartic(0., 0., 1., 0., -4.), &[-2., 2.]);
+    }
+
+    #[test]
+    fn bad_coefficients_dont_panic() {
+        let roots = solve_quartic(f64::NAN, 1., 2., 3., 4.);
+        assert!(roots.iter().all(|root| !root.is_finite()));
+        solve_quartic(1., f64::INFINITY, 0., 0., 1.);
+        solve_quartic(0., 0., 0., 0., 0.);
+    }
+}
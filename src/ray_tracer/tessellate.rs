@@ -0,0 +1,470 @@
+use std::f64::consts::PI;
+
+use crate::ray_tracer::{Geometry, MeshData, Transform, Vec3};
+
+/// Fewer segments than this can't go round anything
+pub const MIN_SEGMENTS: usize = 3;
+/// Enough segments for round shapes to look round
+pub const DEFAULT_SEGMENTS: usize = 32;
+
+/// Collects triangles, wound anticlockwise when seen from the side the normals face
+#[derive(Default)]
+struct Builder {
+    data: MeshData,
+}
+
+impl Builder {
+    fn vertex(&mut self, position: Vec3, normal: Vec3) -> usize {
+        self.data.positions.push(position);
+        self.data.normals.push(normal.normalize());
+        self.data.positions.len() - 1
+    }
+
+    /// Triangles with no area, like the ones at the poles of a sphere, are left out
+    fn triangle(&mut self, a: usize, b: usize, c: usize) {
+        let positions = &self.data.positions;
+        let edge1 = positions[b] - positions[a];
+        let edge2 = positions[c] - positions[a];
+        let face_normal = edge1 * edge2;
+
+        let longest = edge1.length().max(edge2.length());
+        if face_normal.length() <= 1e-10 * longest * longest {
+            return;
+        }
+
+        let normals = &self.data.normals;
+        if face_normal.dot(normals[a] + normals[b] + normals[c]) < 0. {
+            self.data.triangles.push([a, c, b]);
+        } else {
+            self.data.triangles.push([a, b, c]);
+        }
+    }
+
+    /// A sheet of quads, from a function of u and v that both go from 0 to 1
+    fn grid(&mut self, columns: usize, rows: usize, surface: impl Fn(f64, f64) -> (Vec3, Vec3)) {
+        let start = self.data.positions.len();
+
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let (position, normal) =
+                    surface(column as f64 / columns as f64, row as f64 / rows as f64);
+                self.vertex(position, normal);
+            }
+        }
+
+        let index = |column: usize, row: usize| start + row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                let corners = [
+                    index(column, row),
+                    index(column + 1, row),
+                    index(column + 1, row + 1),
+                    index(column, row + 1),
+                ];
+                self.triangle(corners[0], corners[1], corners[2]);
+                self.triangle(corners[0], corners[2], corners[3]);
+            }
+        }
+    }
+
+    /// A flat circle, facing along the normal
+    fn disc(&mut self, center: Vec3, normal: Vec3, radius: f64, segments: usize) {
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        let middle = self.vertex(center, normal);
+
+        let start = self.data.positions.len();
+        for segment in 0..segments {
+            let angle = segment as f64 / segments as f64 * 2. * PI;
+            let offset = tangent * angle.cos() + bitangent * angle.sin();
+            self.vertex(center + offset * radius, normal);
+        }
+
+        for segment in 0..segments {
+            self.triangle(middle, start + segment, start + (segment + 1) % segments);
+        }
+    }
+}
+
+/// A direction in the plane around an axis, at an angle from 0 to 1 turns
+fn around(axis: Vec3, turns: f64) -> Vec3 {
+    let (tangent, bitangent) = axis.orthonormal_basis();
+    let angle = turns * 2. * PI;
+    tangent * angle.cos() + bitangent * angle.sin()
+}
+
+impl Geometry {
+    /// Triangles in the geometry's own space, with smooth normals.
+    /// Round shapes have this many segments around them,
+    /// and shapes without a simple surface are found on a grid this many cells across.
+    pub fn tessellate(&self, segments: usize) -> MeshData {
+        let segments = segments.max(MIN_SEGMENTS);
+        let mut builder = Builder::default();
+
+        match self {
+            Geometry::Sphere { center, radius } => builder.grid(segments, segments / 2, |u, v| {
+                let direction = around(
+                    Vec3 {
+                        x: 0.,
+                        y: 1.,
+                        z: 0.,
+                    },
+                    u,
+                ) * (v * PI).sin()
+                    + Vec3 {
+                        x: 0.,
+                        y: (v * PI).cos(),
+                        z: 0.,
+                    };
+                (*center + direction * *radius, direction)
+            }),
+            Geometry::Plane {
+                center,
+                normal,
+                size,
+            } => {
+                let (tangent, bitangent) = normal.orthonormal_basis();
+                builder.grid(1, 1, |u, v| {
+                    (
+                        *center + tangent * ((u - 0.5) * size) + bitangent * ((v - 0.5) * size),
+                        *normal,
+                    )
+                })
+            }
+            Geometry::Box {
+                center,
+                half_extents,
+                rotation,
+            } => {
+                let matrix = Transform {
+                    position: *center,
+                    rotation: rotation.angles(),
+                    ..Transform::default()
+                }
+                .matrix();
+                let axes = [
+                    Vec3 {
+                        x: half_extents.x,
+                        y: 0.,
+                        z: 0.,
+                    },
+                    Vec3 {
+                        x: 0.,
+                        y: half_extents.y,
+                        z: 0.,
+                    },
+                    Vec3 {
+                        x: 0.,
+                        y: 0.,
+                        z: half_extents.z,
+                    },
+                ];
+
+                for axis in 0..3 {
+                    for sign in [-1., 1.] {
+                        let (first, second) = (axes[(axis + 1) % 3], axes[(axis + 2) % 3]);
+                        let normal = (axes[axis] * sign).transform_direction(matrix);
+
+                        builder.grid(1, 1, |u, v| {
+                            let local =
+                                axes[axis] * sign + first * (u * 2. - 1.) + second * (v * 2. - 1.);
+                            (local.transform_point(matrix), normal)
+                        });
+                    }
+                }
+            }
+            Geometry::Cylinder {
+                center,
+                axis,
+                radius,
+                height,
+            } => {
+                let axis = axis.normalize();
+                builder.grid(segments, 1, |u, v| {
+                    let radial = around(axis, u);
+                    (
+                        *center + radial * *radius + axis * ((v - 0.5) * height),
+                        radial,
+                    )
+                });
+                builder.disc(*center + axis * (height / 2.), axis, *radius, segments);
+                builder.disc(*center - axis * (height / 2.), -axis, *radius, segments);
+            }
+            Geometry::Cone {
+                center,
+                axis,
+                radius,
+                height,
+            } => {
+                let axis = axis.normalize();
+                builder.grid(segments, segments / 4 + 1, |u, v| {
+                    let radial = around(axis, u);
+                    (
+                        *center + radial * (radius * (1. - v)) + axis * (height * v),
+                        radial * *height + axis * *radius,
+                    )
+                });
+                builder.disc(*center, -axis, *radius, segments);
+            }
+            Geometry::Disc {
+                center,
+                normal,
+                radius,
+            } => builder.disc(*center, *normal, *radius, segments),
+            Geometry::Torus {
+                center,
+                axis,
+                major_radius,
+                minor_radius,
+            } => {
+                let axis = axis.normalize();
+                builder.grid(segments, segments / 2, |u, v| {
+                    let radial = around(axis, u);
+                    let angle = v * 2. * PI;
+                    let normal = radial * angle.cos() + axis * angle.sin();
+                    (
+                        *center + radial * *major_radius + normal * *minor_radius,
+                        normal,
+                    )
+                });
+            }
+            Geometry::Heightfield { center, size, map } => {
+                // just the top surface
+                builder.grid(segments * 2, segments * 2, |u, v| {
+                    let mut local = Vec3 {
+                        x: (u - 0.5) * size.x,
+                        y: 0.,
+                        z: (v - 0.5) * size.z,
+                    };
+                    local.y = map.surface_height(*size, local);
+                    (*center + local, map.normal_at_point(*size, local))
+                });
+            }
+            Geometry::Mesh { center, mesh } => {
+                let mut data = mesh.data().clone();
+                for position in &mut data.positions {
+                    *position += *center;
+                }
+                return data;
+            }
+            Geometry::Csg { .. } | Geometry::Sdf { .. } => {
+                if let Some((middle, radius)) = self.bounding_sphere() {
+                    marching_tetrahedra(&mut builder, middle, radius, segments, |point| {
+                        self.signed_distance(point)
+                    });
+                }
+            }
+        }
+
+        builder.data
+    }
+}
+
+/// Finds the surface where the distance is 0, in a cube around the bounding sphere.
+/// Each cell is split into 6 tetrahedra, which only have 3 ways to be cut.
+fn marching_tetrahedra(
+    builder: &mut Builder,
+    middle: Vec3,
+    radius: f64,
+    cells: usize,
+    distance: impl Fn(Vec3) -> f64,
+) {
+    // a little bigger, so surfaces touching the bounds are closed
+    let size = radius * 2. * 1.05;
+    let step = size / cells as f64;
+    let corner = middle
+        - Vec3 {
+            x: size / 2.,
+            y: size / 2.,
+            z: size / 2.,
+        };
+
+    let point = |x: usize, y: usize, z: usize| {
+        corner
+            + Vec3 {
+                x: x as f64 * step,
+                y: y as f64 * step,
+                z: z as f64 * step,
+            }
+    };
+
+    let samples = cells + 1;
+    let mut distances = Vec::with_capacity(samples.pow(3));
+    for z in 0..samples {
+        for y in 0..samples {
+            for x in 0..samples {
+                distances.push(distance(point(x, y, z)));
+            }
+        }
+    }
+
+    // the normal is the direction the distance grows fastest in
+    let normal = |point: Vec3| {
+        let epsilon = step * 1e-3;
+        let along = |offset: Vec3| distance(point + offset) - distance(point - offset);
+        Vec3 {
+            x: along(Vec3 {
+                x: epsilon,
+                y: 0.,
+                z: 0.,
+            }),
+            y: along(Vec3 {
+                x: 0.,
+                y: epsilon,
+                z: 0.,
+            }),
+            z: along(Vec3 {
+                x: 0.,
+                y: 0.,
+                z: epsilon,
+            }),
+        }
+    };
+
+    // the corners of a cell are numbered with x, y and z as bits,
+    // and all the tetrahedra share the diagonal from 0 to 7
+    const TETRAHEDRA: [[usize; 4]; 6] = [
+        [0, 1, 3, 7],
+        [0, 3, 2, 7],
+        [0, 2, 6, 7],
+        [0, 6, 4, 7],
+        [0, 4, 5, 7],
+        [0, 5, 1, 7],
+    ];
+
+    for z in 0..cells {
+        for y in 0..cells {
+            for x in 0..cells {
+                let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|bits: usize| {
+                    let (x, y, z) = (x + (bits & 1), y + (bits >> 1 & 1), z + (bits >> 2));
+                    (point(x, y, z), distances[(z * samples + y) * samples + x])
+                });
+
+                for tetrahedron in TETRAHEDRA {
+                    let tetrahedron = tetrahedron.map(|index| corners[index]);
+                    let (inside, outside): (Vec<_>, Vec<_>) =
+                        tetrahedron.iter().partition(|(_, distance)| *distance < 0.);
+
+                    // where the distance crosses 0 on an edge
+                    let cross = |(a, da): &(Vec3, f64), (b, db): &(Vec3, f64)| {
+                        *a + (*b - *a) * (da / (da - db))
+                    };
+
+                    let points = match (inside.len(), outside.len()) {
+                        (1, 3) => vec![
+                            cross(inside[0], outside[0]),
+                            cross(inside[0], outside[1]),
+                            cross(inside[0], outside[2]),
+                        ],
+                        (3, 1) => vec![
+                            cross(outside[0], inside[0]),
+                            cross(outside[0], inside[1]),
+                            cross(outside[0], inside[2]),
+                        ],
+                        // the cut is a quad, with its corners in order around it
+                        (2, 2) => vec![
+                            cross(inside[0], outside[0]),
+                            cross(inside[0], outside[1]),
+                            cross(inside[1], outside[1]),
+                            cross(inside[1], outside[0]),
+                        ],
+                        _ => continue,
+                    };
+
+                    let indices: Vec<usize> = points
+                        .iter()
+                        .map(|point| builder.vertex(*point, normal(*point)))
+                        .collect();
+
+                    builder.triangle(indices[0], indices[1], indices[2]);
+                    if indices.len() == 4 {
+                        builder.triangle(indices[0], indices[2], indices[3]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_tracer::Orientation;
+
+    fn vec3(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    /// Shapes with an inside, so their normals have to point out of it
+    fn solids() -> Vec<Geometry> {
+        let center = vec3(1., 2., 3.);
+        let axis = vec3(0.3, 1., -0.2);
+
+        vec![
+            Geometry::Sphere { center, radius: 2. },
+            Geometry::Box {
+                center,
+                half_extents: vec3(1., 2., 0.5),
+                rotation: Orientation::new(vec3(0.3, 0.5, -0.2)),
+            },
+            Geometry::Cylinder {
+                center,
+                axis,
+                radius: 1.,
+                height: 3.,
+            },
+            Geometry::Cone {
+                center,
+                axis,
+                radius: 1.,
+                height: 3.,
+            },
+            Geometry::Torus {
+                center,
+                axis,
+                major_radius: 2.,
+                minor_radius: 0.5,
+            },
+        ]
+    }
+
+    #[test]
+    fn triangles_are_wound_the_way_their_normals_face() {
+        let flat = [
+            Geometry::Disc {
+                center: vec3(0., 1., 0.),
+                normal: vec3(0., 0., -1.),
+                radius: 2.,
+            },
+            Geometry::Plane {
+                center: vec3(0., 1., 0.),
+                normal: vec3(1., 1., 0.).normalize(),
+                size: 4.,
+            },
+        ];
+
+        for geometry in solids().iter().chain(&flat) {
+            let data = geometry.tessellate(16);
+            assert!(!data.triangles.is_empty(), "{:?}", geometry);
+
+            for [a, b, c] in &data.triangles {
+                let positions = &data.positions;
+                let face_normal = (positions[*b] - positions[*a]) * (positions[*c] - positions[*a]);
+                let normals = data.normals[*a] + data.normals[*b] + data.normals[*c];
+                assert!(face_normal.dot(normals) > 0., "{:?}", geometry);
+            }
+        }
+    }
+
+    #[test]
+    fn normals_point_out_of_solids() {
+        for geometry in solids() {
+            let data = geometry.tessellate(16);
+
+            for (position, normal) in data.positions.iter().zip(&data.normals) {
+                let outside = geometry.signed_distance(*position + *normal * 1e-3);
+                let inside = geometry.signed_distance(*position - *normal * 1e-3);
+                assert!(outside > inside, "{:?} at {:?}", geometry, position);
+            }
+        }
+    }
+}
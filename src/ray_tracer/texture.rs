@@ -0,0 +1,278 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ray_tracer::{deserialize_colour, fbm, perlin, simplex, turbulence, worley, Vec3};
+
+/// An image loaded from a file.
+/// Only the path is saved, the image is loaded again with the scene.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub struct ImageTexture {
+    pub path: String,
+    /// shared, so cloning the scene doesn't copy it
+    image: Arc<image::Rgb32FImage>,
+}
+
+impl TryFrom<String> for ImageTexture {
+    type Error = image::ImageError;
+
+    fn try_from(path: String) -> Result<ImageTexture, image::ImageError> {
+        ImageTexture::load(path)
+    }
+}
+
+impl From<ImageTexture> for String {
+    fn from(texture: ImageTexture) -> String {
+        texture.path
+    }
+}
+
+impl ImageTexture {
+    pub fn load(path: String) -> Result<ImageTexture, image::ImageError> {
+        let image = image::open(&path)?.to_rgb32f();
+
+        Ok(ImageTexture::new(path, image))
+    }
+
+    /// An image that's already loaded, which should also be saved at the path
+    pub fn new(path: String, image: image::Rgb32FImage) -> ImageTexture {
+        ImageTexture {
+            path,
+            image: Arc::new(image),
+        }
+    }
+
+    /// Bilinearly filtered, and repeating outside of 0 to 1.
+    /// v goes up the image, like the uvs from the geometry.
+    pub fn sample(&self, u: f64, v: f64) -> (f64, f64, f64) {
+        let width = self.image.width() as i64;
+        let height = self.image.height() as i64;
+
+        // pixel centers are at the halves
+        let x = u.rem_euclid(1.) * width as f64 - 0.5;
+        let y = (1. - v.rem_euclid(1.)) * height as f64 - 0.5;
+
+        let pixel = |x: i64, y: i64| {
+            let pixel = self
+                .image
+                .get_pixel(x.rem_euclid(width) as u32, y.rem_euclid(height) as u32);
+            (pixel[0] as f64, pixel[1] as f64, pixel[2] as f64)
+        };
+
+        let (x0, y0) = (x.floor() as i64, y.floor() as i64);
+        let (tx, ty) = (x - x.floor(), y - y.floor());
+
+        let corners = [
+            (pixel(x0, y0), (1. - tx) * (1. - ty)),
+            (pixel(x0 + 1, y0), tx * (1. - ty)),
+            (pixel(x0, y0 + 1), (1. - tx) * ty),
+            (pixel(x0 + 1, y0 + 1), tx * ty),
+        ];
+
+        corners
+            .iter()
+            .fold((0., 0., 0.), |result, (colour, weight)| {
+                (
+                    result.0 + colour.0 * weight,
+                    result.1 + colour.1 * weight,
+                    result.2 + colour.2 * weight,
+                )
+            })
+    }
+}
+
+/// The shape of a solid texture, which all go from 0 to 1
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum Pattern {
+    Perlin,
+    Simplex,
+    Worley,
+    Fbm {
+        octaves: u32,
+    },
+    Turbulence {
+        octaves: u32,
+    },
+    /// stripes along x, bent by turbulence into veins
+    Marble {
+        octaves: u32,
+        turbulence: f64,
+    },
+    /// rings around the y axis, bent by noise
+    Wood {
+        turbulence: f64,
+    },
+}
+
+/// A value that changes through 3D space,
+/// so it's carved out of the object rather than wrapped around it
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SolidTexture {
+    pub pattern: Pattern,
+    /// how many times it repeats per unit, roughly
+    pub frequency: f64,
+    pub seed: u32,
+}
+
+impl SolidTexture {
+    /// From 0 to 1
+    pub fn value(&self, point: Vec3) -> f64 {
+        let point = point * self.frequency;
+        let seed = self.seed;
+
+        let value = match &self.pattern {
+            Pattern::Perlin => perlin(point, seed) * 0.5 + 0.5,
+            Pattern::Simplex => simplex(point, seed) * 0.5 + 0.5,
+            Pattern::Worley => worley(point, seed),
+            Pattern::Fbm { octaves } => fbm(point, seed, *octaves) * 0.5 + 0.5,
+            Pattern::Turbulence { octaves } => turbulence(point, seed, *octaves),
+            Pattern::Marble {
+                octaves,
+                turbulence: amount,
+            } => {
+                let angle =
+                    (point.x + amount * turbulence(point, seed, *octaves)) * std::f64::consts::PI;
+                angle.sin() * 0.5 + 0.5
+            }
+            Pattern::Wood { turbulence: amount } => {
+                let distance = (point.x.powi(2) + point.z.powi(2)).sqrt();
+                (distance + amount * perlin(point, seed)).rem_euclid(1.)
+            }
+        };
+
+        value.clamp(0., 1.)
+    }
+}
+
+/// A colour blended between two others by a texture
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ColourTexture {
+    pub texture: SolidTexture,
+    #[serde(deserialize_with = "deserialize_colour")]
+    pub from: (f64, f64, f64),
+    #[serde(deserialize_with = "deserialize_colour")]
+    pub to: (f64, f64, f64),
+}
+
+impl ColourTexture {
+    pub fn colour(&self, point: Vec3) -> (f64, f64, f64) {
+        let t = self.texture.value(point);
+
+        (
+            self.from.0 + (self.to.0 - self.from.0) * t,
+            self.from.1 + (self.to.1 - self.from.1) * t,
+            self.from.2 + (self.to.2 - self.from.2) * t,
+        )
+    }
+}
+
+/// A number blended between two others by a texture
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ValueTexture {
+    pub texture: SolidTexture,
+    pub from: f64,
+    pub to: f64,
+}
+
+impl ValueTexture {
+    pub fn value(&self, point: Vec3) -> f64 {
+        self.from + (self.to - self.from) * self.texture.value(point)
+    }
+}
+
+/// Textures that replace the plain values of a material, None keeps the plain value
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct MaterialTextures {
+    #[serde(default)]
+    pub colour: Option<ColourTexture>,
+    #[serde(default)]
+    pub specular: Option<ValueTexture>,
+    #[serde(default)]
+    pub metallic: Option<ValueTexture>,
+    #[serde(default)]
+    pub roughness: Option<ValueTexture>,
+}
+
+impl MaterialTextures {
+    pub const PRESETS: [&'static str; 4] = ["marble", "wood", "clouds", "rust"];
+
+    pub fn is_empty(&self) -> bool {
+        self.colour.is_none()
+            && self.specular.is_none()
+            && self.metallic.is_none()
+            && self.roughness.is_none()
+    }
+
+    /// Ready made textures for some common materials
+    pub fn preset(name: &str) -> MaterialTextures {
+        match name {
+            "marble" => MaterialTextures {
+                colour: Some(ColourTexture {
+                    texture: SolidTexture {
+                        pattern: Pattern::Marble {
+                            octaves: 5,
+                            turbulence: 4.,
+                        },
+                        frequency: 1.,
+                        seed: 0,
+                    },
+                    from: (0.35, 0.35, 0.4),
+                    to: (0.95, 0.95, 0.95),
+                }),
+                ..MaterialTextures::default()
+            },
+            "wood" => MaterialTextures {
+                colour: Some(ColourTexture {
+                    texture: SolidTexture {
+                        pattern: Pattern::Wood { turbulence: 0.6 },
+                        frequency: 4.,
+                        seed: 0,
+                    },
+                    from: (0.55, 0.33, 0.14),
+                    to: (0.3, 0.16, 0.06),
+                }),
+                ..MaterialTextures::default()
+            },
+            "clouds" => MaterialTextures {
+                colour: Some(ColourTexture {
+                    texture: SolidTexture {
+                        pattern: Pattern::Fbm { octaves: 6 },
+                        frequency: 1.,
+                        seed: 0,
+                    },
+                    from: (0.3, 0.5, 0.9),
+                    to: (1., 1., 1.),
+                }),
+                ..MaterialTextures::default()
+            },
+            // patches of rust on bare metal, where the rust is rough and not metallic
+            _ => {
+                let patches = SolidTexture {
+                    pattern: Pattern::Turbulence { octaves: 5 },
+                    frequency: 2.,
+                    seed: 0,
+                };
+
+                MaterialTextures {
+                    colour: Some(ColourTexture {
+                        texture: patches.clone(),
+                        from: (0.7, 0.7, 0.72),
+                        to: (0.45, 0.2, 0.08),
+                    }),
+                    specular: None,
+                    metallic: Some(ValueTexture {
+                        texture: patches.clone(),
+                        from: 1.,
+                        to: 0.,
+                    }),
+                    roughness: Some(ValueTexture {
+                        texture: patches,
+                        from: 0.2,
+                        to: 0.9,
+                    }),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ray_tracer::{Axis, Mat44, Ray, Vec3};
+
+/// Moves an object from its own space into the world.
+/// Applied as scale, then rotation (x, y, then z), then translation.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Transform {
+    pub position: Vec3,
+    /// in radians
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform {
+            position: Vec3 {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            rotation: Vec3 {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            scale: Vec3 {
+                x: 1.,
+                y: 1.,
+                z: 1.,
+            },
+        }
+    }
+}
+
+impl Transform {
+    /// Smaller scales are made this big, so a flattened object can still be turned back into its own space
+    pub const MIN_SCALE: f64 = 1e-6;
+
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::default()
+    }
+
+    /// Splits a matrix into a transform.
+    /// Skew from non-uniform scale under a rotation can't be kept, so it's lost.
+    pub fn from_matrix(matrix: Mat44) -> Transform {
+        let axis = |x, y, z| Vec3 { x, y, z }.transform_direction(matrix);
+        let (mut x_axis, y_axis, z_axis) = (axis(1., 0., 0.), axis(0., 1., 0.), axis(0., 0., 1.));
+
+        let mut scale = Vec3 {
+            x: x_axis.length(),
+            y: y_axis.length(),
+            z: z_axis.length(),
+        };
+
+        // a mirrored matrix can't be a rotation, so flip one axis into the scale
+        if (x_axis * y_axis).dot(z_axis) < 0. {
+            scale.x = -scale.x;
+            x_axis = -x_axis;
+        }
+
+        let (x_axis, y_axis, z_axis) = (x_axis.normalize(), y_axis.normalize(), z_axis.normalize());
+
+        Transform {
+            position: Vec3 {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            }
+            .transform_point(matrix),
+            // the inverse of rotating around x, then y, then z
+            rotation: Vec3 {
+                x: y_axis.z.atan2(z_axis.z),
+                y: (-x_axis.z).clamp(-1., 1.).asin(),
+                z: x_axis.y.atan2(x_axis.x),
+            },
+            scale,
+        }
+    }
+
+    /// The scale with each axis kept away from 0, keeping its sign
+    fn safe_scale(&self) -> Vec3 {
+        let safe = |scale: f64| {
+            if scale.abs() < Transform::MIN_SCALE {
+                Transform::MIN_SCALE.copysign(scale)
+            } else {
+                scale
+            }
+        };
+
+        Vec3 {
+            x: safe(self.scale.x),
+            y: safe(self.scale.y),
+            z: safe(self.scale.z),
+        }
+    }
+
+    pub fn matrix(&self) -> Mat44 {
+        Mat44::create_scale(self.safe_scale())
+            * Mat44::create_rotation(Axis::X, self.rotation.x)
+            * Mat44::create_rotation(Axis::Y, self.rotation.y)
+            * Mat44::create_rotation(Axis::Z, self.rotation.z)
+            * Mat44::create_translation(self.position)
+    }
+
+    /// Built from the parts rather than Mat44::inverse, as it's quicker
+    pub fn inverse_matrix(&self) -> Mat44 {
+        let scale = self.safe_scale();
+
+        Mat44::create_translation(-self.position)
+            * Mat44::create_rotation(Axis::Z, -self.rotation.z)
+            * Mat44::create_rotation(Axis::Y, -self.rotation.y)
+            * Mat44::create_rotation(Axis::X, -self.rotation.x)
+            * Mat44::create_scale(Vec3 {
+                x: 1. / scale.x,
+                y: 1. / scale.y,
+                z: 1. / scale.z,
+            })
+    }
+
+    /// Works out the matrices, for when it's used for a lot of rays
+    pub fn cached(&self) -> CachedTransform {
+        CachedTransform {
+            transform: *self,
+            matrix: self.matrix(),
+            inverse: self.inverse_matrix(),
+            is_identity: self.is_identity(),
+        }
+    }
+
+    /// The direction isn't normalized,
+    /// so distances along the ray are the same in both spaces.
+    pub fn ray_to_local(&self, ray: &Ray) -> Ray {
+        let inverse = self.inverse_matrix();
+
+        Ray {
+            origin: ray.origin.transform_point(inverse),
+            direction: ray.direction.transform_direction(inverse),
+            time: ray.time,
+        }
+    }
+
+    pub fn point_to_local(&self, point: Vec3) -> Vec3 {
+        point.transform_point(self.inverse_matrix())
+    }
+
+    pub fn normal_to_world(&self, normal: Vec3) -> Vec3 {
+        // normals use the inverse transpose to stay perpendicular under non-uniform scale
+        normal
+            .transform_direction(self.inverse_matrix().transpose())
+            .normalize()
+    }
+}
+
+/// A transform along with its matrices,
+/// so they aren't built again for every ray that's tested against it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CachedTransform {
+    pub transform: Transform,
+    pub matrix: Mat44,
+    pub inverse: Mat44,
+    is_identity: bool,
+}
+
+impl CachedTransform {
+    pub fn is_identity(&self) -> bool {
+        self.is_identity
+    }
+
+    /// Like Transform::ray_to_local
+    pub fn ray_to_local(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: ray.origin.transform_point(self.inverse),
+            direction: ray.direction.transform_direction(self.inverse),
+            time: ray.time,
+        }
+    }
+
+    pub fn point_to_local(&self, point: Vec3) -> Vec3 {
+        point.transform_point(self.inverse)
+    }
+
+    pub fn normal_to_world(&self, normal: Vec3) -> Vec3 {
+        normal
+            .transform_direction(self.inverse.transpose())
+            .normalize()
+    }
+}
+
+/// Rotation angles in radians, applied the same way as Transform::rotation,
+/// with the rotation worked out once rather than for every ray.
+/// Only the angles are saved.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(from = "Vec3", into = "Vec3")]
+pub struct Orientation {
+    angles: Vec3,
+    /// where the x, y and z axes end up, which are the rows of the rotation matrix
+    axes: [Vec3; 3],
+}
+
+impl From<Vec3> for Orientation {
+    fn from(angles: Vec3) -> Orientation {
+        Orientation::new(angles)
+    }
+}
+
+impl From<Orientation> for Vec3 {
+    fn from(orientation: Orientation) -> Vec3 {
+        orientation.angles
+    }
+}
+
+impl Default for Orientation {
+    fn default() -> Orientation {
+        let axis = |x, y, z| Vec3 { x, y, z };
+
+        Orientation {
+            angles: axis(0., 0., 0.),
+            axes: [axis(1., 0., 0.), axis(0., 1., 0.), axis(0., 0., 1.)],
+        }
+    }
+}
+
+impl Orientation {
+    pub fn new(angles: Vec3) -> Orientation {
+        let matrix = Transform {
+            rotation: angles,
+            ..Transform::default()
+        }
+        .matrix();
+        let row = |index: usize| Vec3 {
+            x: matrix[index][0],
+            y: matrix[index][1],
+            z: matrix[index][2],
+        };
+
+        Orientation {
+            angles,
+            axes: [row(0), row(1), row(2)],
+        }
+    }
+
+    pub fn angles(&self) -> Vec3 {
+        self.angles
+    }
+
+    /// Turns a direction from the rotated space into the world
+    pub fn to_world(&self, direction: Vec3) -> Vec3 {
+        let [x, y, z] = self.axes;
+        x * direction.x + y * direction.y + z * direction.z
+    }
+
+    /// Turns a direction from the world into the rotated space,
+    /// which is the transpose as it's only a rotation
+    pub fn to_local(&self, direction: Vec3) -> Vec3 {
+        let [x, y, z] = self.axes;
+        Vec3 {
+            x: direction.dot(x),
+            y: direction.dot(y),
+            z: direction.dot(z),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec3(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    #[test]
+    fn zero_scale_can_still_be_undone() {
+        let transform = Transform {
+            position: vec3(1., 2., 3.),
+            rotation: vec3(0.3, 0., 0.),
+            scale: vec3(0., 2., -0.),
+        };
+
+        let point = vec3(1., 5., 3.);
+        let local = transform.point_to_local(point);
+        assert!(local.x.is_finite() && local.y.is_finite() && local.z.is_finite());
+
+        // the point is on the flattened object, so it goes back to where it was
+        let back = local.transform_point(transform.matrix());
+        assert!((back - point).length() < 1e-6);
+    }
+
+    #[test]
+    fn cached_matches_the_transform() {
+        let transform = Transform {
+            position: vec3(1., -2., 0.5),
+            rotation: vec3(0.1, 0.7, -0.4),
+            scale: vec3(2., 1., 0.5),
+        };
+        let cached = transform.cached();
+        assert!(!cached.is_identity());
+        assert!(Transform::default().cached().is_identity());
+
+        let ray = Ray {
+            origin: vec3(3., 1., -4.),
+            direction: vec3(0.2, -0.5, 1.),
+            time: 0.,
+        };
+        let (a, b) = (transform.ray_to_local(&ray), cached.ray_to_local(&ray));
+        assert!((a.origin - b.origin).length() < 1e-12);
+        assert!((a.direction - b.direction).length() < 1e-12);
+
+        let normal = vec3(0., 1., 0.);
+        assert!(
+            (transform.normal_to_world(normal) - cached.normal_to_world(normal)).length() < 1e-12
+        );
+    }
+
+    #[test]
+    fn orientation_matches_the_rotation() {
+        let angles = vec3(0.1, 0.7, -0.4);
+        let orientation = Orientation::new(angles);
+        let transform = Transform {
+            rotation: angles,
+            ..Transform::default()
+        };
+
+        let direction = vec3(0.2, -0.5, 1.);
+        assert!(
+            (orientation.to_local(direction) - transform.point_to_local(direction)).length()
+                < 1e-12
+        );
+        assert!(
+            (orientation.to_world(orientation.to_local(direction)) - direction).length() < 1e-12
+        );
+
+        // only the angles are saved
+        let json = serde_json::to_string(&orientation).unwrap();
+        assert_eq!(json, serde_json::to_string(&angles).unwrap());
+        assert_eq!(
+            serde_json::from_str::<Orientation>(&json).unwrap(),
+            orientation
+        );
+    }
+}
@@ -37,6 +37,41 @@ impl Vec3 {
     }
 
     pub fn transform_point(&self, mat: Mat44) -> Vec3 {
+        Vec3 {
+            x: self.x * mat[0][0] + self.y * mat[1][0] + self.z * mat[2][0] + mat[3][0],
+            y: self.x * mat[0][1] + self.y * mat[1][1] + self.z * mat[2][1] + mat[3][1],
+            z: self.x * mat[0][2] + self.y * mat[1][2] + self.z * mat[2][2] + mat[3][2],
+        }
+    }
+
+    /// Two unit vectors perpendicular to this and each other,
+    /// which one is which is arbitrary but consistent.
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        let normal = self.normalize();
+
+        // anything not parallel to the normal will do
+        let helper = if normal.x.abs() > 0.9 {
+            Vec3 {
+                x: 0.,
+                y: 1.,
+                z: 0.,
+            }
+        } else {
+            Vec3 {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            }
+        };
+
+        let tangent = (helper * normal).normalize();
+        let bitangent = normal * tangent;
+
+        (tangent, bitangent)
+    }
+
+    /// Like transform_point, but ignores the translation
+    pub fn transform_direction(&self, mat: Mat44) -> Vec3 {
         Vec3 {
             x: self.x * mat[0][0] + self.y * mat[1][0] + self.z * mat[2][0],
             y: self.x * mat[0][1] + self.y * mat[1][1] + self.z * mat[2][1],
@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ray_tracer::{deserialize_colour, fbm, Ray, Vec3};
+
+/// Fog that fills the whole scene with the same density
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Fog {
+    #[serde(deserialize_with = "deserialize_colour")]
+    pub colour: (f64, f64, f64),
+    /// how much light is lost per unit travelled
+    pub density: f64,
+    /// the fraction of the lost light that's scattered rather than absorbed
+    pub albedo: f64,
+}
+
+/// Where the density inside a volume comes from
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub enum DensitySource {
+    Uniform,
+    /// clumpy like smoke, with about half the volume empty
+    Noise {
+        frequency: f64,
+        seed: u32,
+        octaves: u32,
+    },
+    /// a grid of values from 0 to 1 stretched over the box, x first, then y, then z
+    Voxels {
+        resolution: (usize, usize, usize),
+        values: Vec<f64>,
+    },
+}
+
+/// A medium like smoke or cloud inside a box
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Volume {
+    pub name: String,
+    pub center: Vec3,
+    pub half_extents: Vec3,
+    #[serde(deserialize_with = "deserialize_colour")]
+    pub colour: (f64, f64, f64),
+    /// the most light that's lost per unit travelled, where the source is 1
+    pub density: f64,
+    /// the fraction of the lost light that's scattered rather than absorbed
+    pub albedo: f64,
+    pub source: DensitySource,
+}
+
+impl Volume {
+    /// Where a ray with a unit direction is inside the box
+    pub fn span(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let slab = |origin: f64, direction: f64, center: f64, half_extent: f64| {
+            let t1 = (center - half_extent - origin) / direction;
+            let t2 = (center + half_extent - origin) / direction;
+            (t1.min(t2), t1.max(t2))
+        };
+
+        let x = slab(
+            ray.origin.x,
+            ray.direction.x,
+            self.center.x,
+            self.half_extents.x,
+        );
+        let y = slab(
+            ray.origin.y,
+            ray.direction.y,
+            self.center.y,
+            self.half_extents.y,
+        );
+        let z = slab(
+            ray.origin.z,
+            ray.direction.z,
+            self.center.z,
+            self.half_extents.z,
+        );
+
+        let t_near = x.0.max(y.0).max(z.0).max(0.);
+        let t_far = x.1.min(y.1).min(z.1);
+
+        if t_near >= t_far {
+            return None;
+        }
+
+        Some((t_near, t_far))
+    }
+
+    pub fn density_at(&self, point: Vec3) -> f64 {
+        let local = point - self.center;
+
+        if local.x.abs() > self.half_extents.x
+            || local.y.abs() > self.half_extents.y
+            || local.z.abs() > self.half_extents.z
+        {
+            return 0.;
+        }
+
+        let value = match &self.source {
+            DensitySource::Uniform => 1.,
+            DensitySource::Noise {
+                frequency,
+                seed,
+                octaves,
+            } => (fbm(local * *frequency, *seed, *octaves) * 2.).clamp(0., 1.),
+            DensitySource::Voxels { resolution, values } => {
+                // from 0 to the resolution - 1 across the box
+                let grid = |local: f64, half_extent: f64, resolution: usize| {
+                    ((local / half_extent + 1.) / 2. * (resolution.max(1) - 1) as f64)
+                        .clamp(0., (resolution.max(1) - 1) as f64)
+                };
+
+                let x = grid(local.x, self.half_extents.x, resolution.0);
+                let y = grid(local.y, self.half_extents.y, resolution.1);
+                let z = grid(local.z, self.half_extents.z, resolution.2);
+
+                let value = |i: usize, j: usize, k: usize| {
+                    let i = i.min(resolution.0.max(1) - 1);
+                    let j = j.min(resolution.1.max(1) - 1);
+                    let k = k.min(resolution.2.max(1) - 1);
+
+                    values
+                        .get((k * resolution.1 + j) * resolution.0 + i)
+                        .cloned()
+                        .unwrap_or(0.)
+                };
+
+                // trilinear interpolation between the 8 voxels around the point
+                let (i, j, k) = (x as usize, y as usize, z as usize);
+                let (u, v, w) = (x.fract(), y.fract(), z.fract());
+                let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+
+                lerp(
+                    lerp(
+                        lerp(value(i, j, k), value(i + 1, j, k), u),
+                        lerp(value(i, j + 1, k), value(i + 1, j + 1, k), u),
+                        v,
+                    ),
+                    lerp(
+                        lerp(value(i, j, k + 1), value(i + 1, j, k + 1), u),
+                        lerp(value(i, j + 1, k + 1), value(i + 1, j + 1, k + 1), u),
+                        v,
+                    ),
+                    w,
+                )
+            }
+        };
+
+        value * self.density
+    }
+}